@@ -1,5 +1,6 @@
 use casper_delta_bot::{
-    Bot, UnwrapWcspr, CD_LONG_ID, CD_SHORT_ID, LP_LONG_WCSPR_ID, LP_WCSPR_SHORT_ID,
+    Backtest, Bot, ContractNames, PnlReport, Record, UnwrapWcspr, CD_LONG_ID, CD_SHORT_ID,
+    LP_LONG_WCSPR_ID, LP_WCSPR_SHORT_ID,
 };
 use casper_delta_contracts::{
     market::Market, position_token::PositionToken, wrapped_native::WrappedNativeToken,
@@ -22,7 +23,8 @@ pub fn main() {
         .with_ansi(false)
         .with_level(true)
         .init();
-    OdraCli::new()
+    let names = ContractNames::from_args(&std::env::args().collect::<Vec<_>>());
+    let cli = OdraCli::new()
         .about("Casper Delta CLI Tool")
         .contract::<StyksPriceFeed>()
         .contract::<Market>()
@@ -33,9 +35,25 @@ pub fn main() {
         .contract::<Factory>()
         .contract::<PairFactory>()
         .named_contract::<Pair>(LP_LONG_WCSPR_ID.to_string())
-        .named_contract::<Pair>(LP_WCSPR_SHORT_ID.to_string())
-        .scenario(Bot)
+        .named_contract::<Pair>(LP_WCSPR_SHORT_ID.to_string());
+    // The markets of the bot config, and the pairs routes may go through
+    let cli = names
+        .markets
+        .into_iter()
+        .fold(cli, |cli, name| cli.named_contract::<Market>(name));
+    let cli = names
+        .position_tokens
+        .into_iter()
+        .fold(cli, |cli, name| cli.named_contract::<PositionToken>(name));
+    let cli = names
+        .pairs
+        .into_iter()
+        .fold(cli, |cli, name| cli.named_contract::<Pair>(name));
+    cli.scenario(Bot)
         .scenario(UnwrapWcspr)
+        .scenario(PnlReport)
+        .scenario(Backtest)
+        .scenario(Record)
         .build()
         .run();
 }
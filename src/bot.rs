@@ -8,20 +8,60 @@ use odra_cli::{
     DeployedContractsContainer,
 };
 
-use crate::bot::asset_manager::{DryRunTokenManager, RealBalances, RealTokenManager, TokenManager};
+use crate::bot::asset_manager::{
+    DryRunTokenManager, RealBalances, RealTokenManager, TokenManager, WcsprBalances,
+    WcsprTokenManager,
+};
 use crate::bot::{
-    asset_manager::AssetManager, utils::PriceCalculator,
+    asset_manager::AssetManager,
+    config::BotConfig,
+    error::{BotError, ErrorPolicy},
+    journal::TradeJournal,
+    limits::RiskLimits,
+    retry::{Retry, RetryingBalances, RetryingTokenManager},
+    strategy::{self, DEFAULT_STRATEGY},
+    utils::PriceCalculator,
 };
 use crate::contracts::ContractRefs;
 
-use self::engine::BotEngine;
-use self::events::{EventSource, TimerEventSource};
+pub use self::backtest::Backtest;
+pub use self::recorder::Record;
+pub use self::registry::ContractNames;
+pub use self::report::PnlReport;
+
+use self::engine::{BotEngine, MarketContext};
+use self::events::{
+    Backoff, BotEvent, CompositeEventSource, EventSource, ShutdownEventSource, ShutdownSignal,
+    SseEventSource, TimerEventSource, WatchedContract,
+};
 
 mod asset_manager;
+mod backtest;
+mod breaker;
+mod config;
 mod data;
+#[cfg(test)]
+mod e2e;
 mod engine;
+mod error;
 mod events;
+mod graph;
+mod journal;
+mod limits;
+mod oracle;
 mod path;
+mod pnl;
+mod pool_guard;
+mod price;
+mod recorder;
+mod registry;
+mod report;
+mod retry;
+mod serde_u256;
+mod sizing;
+mod slippage;
+mod snapshot;
+mod strategy;
 mod utils;
 
 pub struct Bot;
@@ -33,11 +73,19 @@ impl ScenarioMetadata for Bot {
 
 impl Scenario for Bot {
     fn args(&self) -> Vec<odra_cli::CommandArg> {
-        vec![odra_cli::CommandArg::new(
-            "dry-run",
-            "Dry run the bot",
-            NamedCLType::Bool,
-        )]
+        vec![
+            odra_cli::CommandArg::new("dry-run", "Dry run the bot", NamedCLType::Bool),
+            odra_cli::CommandArg::new(
+                "config",
+                "Path to the bot config TOML file. Defaults are used if omitted.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "strategy",
+                "Trade strategy: deviation (default), mint-and-sell or buy-and-redeem.",
+                NamedCLType::String,
+            ),
+        ]
     }
 
     fn run(
@@ -46,46 +94,256 @@ impl Scenario for Bot {
         container: &DeployedContractsContainer,
         args: Args,
     ) -> Result<(), Error> {
-        let contracts = ContractRefs::new(env, container);
-        let calc = PriceCalculator::new(&contracts);
-        let caller = env.caller();
+        let config_path: Option<String> = args.get_single("config").ok();
+        let config = BotConfig::load(config_path.as_deref())?;
+        tracing::info!("Config: {:?}", config);
+        let shutdown = ShutdownSignal::new();
+        shutdown.install_handler()?;
 
+        let retry = Retry::new(&config.retry, shutdown.clone());
+        let strategy_name = args
+            .get_single::<String>("strategy")
+            .unwrap_or_else(|_| DEFAULT_STRATEGY.to_string());
+        let caller = env.caller();
         let dry_run = args.get_single("dry-run").unwrap_or(false);
-        let token_manager = self.build_token_manager(dry_run, env, &contracts);
-        let balances = RealBalances::new(env, &contracts);
-        let asset_manager = AssetManager::new(&balances, &*token_manager);
-        token_manager.approve_markets()?;
-        asset_manager.print_balances()?;
+        if dry_run {
+            tracing::info!("Dry run mode enabled");
+        }
 
-        let engine = BotEngine::new(calc, asset_manager, &contracts, caller);
-        let mut event_source = TimerEventSource::new(Duration::from_secs(180));
+        // Every market trades through its own contracts, journal, limits and
+        // engine, all from the same account.
+        let markets = config.markets();
+        let contracts = markets
+            .iter()
+            .map(|market| ContractRefs::new(env, container).for_market(market.names()))
+            .collect::<Vec<_>>();
+        let journals = markets
+            .iter()
+            .map(|market| TradeJournal::open(&config.market_journal_path(market), dry_run))
+            .collect::<Result<Vec<_>, _>>()?;
+        let limits = markets
+            .iter()
+            .map(|market| RiskLimits::open(&config.market_limits(market), dry_run))
+            .collect::<Result<Vec<_>, _>>()?;
+        let real_balances = contracts
+            .iter()
+            .map(|contracts| RealBalances::new(env, contracts))
+            .collect::<Vec<_>>();
+        let balances = real_balances
+            .iter()
+            .map(|real_balances| RetryingBalances::new(real_balances, &retry))
+            .collect::<Vec<_>>();
+        let inner_token_managers = contracts
+            .iter()
+            .map(|contracts| self.build_token_manager(dry_run, env, contracts, &config))
+            .collect::<Vec<_>>();
+        let token_managers = inner_token_managers
+            .iter()
+            .zip(&balances)
+            .map(|(inner, balances)| RetryingTokenManager::new(&**inner, balances, &retry))
+            .collect::<Vec<_>>();
 
+        let mut engines = vec![];
+        for (index, market) in markets.iter().enumerate() {
+            let _span = tracing::info_span!("market", market = %market.name).entered();
+            let strategy = strategy::by_name(&strategy_name, &config, market)?;
+            tracing::info!("Strategy: {}", strategy.name());
+            let asset_manager =
+                AssetManager::new(&balances[index], &token_managers[index], &config)
+                    .with_limits(&limits[index])
+                    .with_treasury_share(market.treasury_bps)?;
+            token_managers[index].approve_markets()?;
+            asset_manager.print_balances()?;
+
+            let context = MarketContext {
+                contracts: &contracts[index],
+                journal: &journals[index],
+                limits: &limits[index],
+                breaker: config.market_breaker(market),
+            };
+            let engine = BotEngine::new(
+                PriceCalculator::new(&contracts[index]).with_retry(&retry),
+                strategy,
+                asset_manager,
+                context,
+                caller,
+                &config,
+                shutdown.clone(),
+            );
+            engines.push((market.name.as_str(), engine));
+        }
+        // CSPR and wCSPR balances belong to the account, not to a market, so
+        // they are looked after once per event by a treasury that only wraps
+        // and unwraps, within limits of its own
+        let treasury_contracts = ContractRefs::new(env, container);
+        let treasury_limits = RiskLimits::open(&config.treasury_limits(), dry_run)?;
+        let treasury_real_balances = WcsprBalances::new(env, &treasury_contracts);
+        let treasury_balances = RetryingBalances::new(&treasury_real_balances, &retry);
+        let treasury_inner_token_manager: Box<dyn TokenManager> = if dry_run {
+            Box::new(DryRunTokenManager)
+        } else {
+            Box::new(WcsprTokenManager::new(env, &treasury_contracts, &config))
+        };
+        let treasury_token_manager =
+            RetryingTokenManager::new(&*treasury_inner_token_manager, &treasury_balances, &retry);
+        let treasury = AssetManager::new(&treasury_balances, &treasury_token_manager, &config)
+            .with_limits(&treasury_limits);
+        let mut event_source =
+            build_event_source(&contracts, &config, config.tick.interval(), &shutdown)?;
+        let mut backoff =
+            Backoff::new(config.errors.backoff_initial(), config.errors.backoff_max());
+
+        let mut halted = None;
         while let Some(event) = event_source.next_event() {
             tracing::info!("Event: {:?}", event);
-            match engine.handle_event(&event) {
-                Ok(true) => continue,
+            match self.handle_event(&treasury, &engines, &event) {
+                Ok(true) => backoff.reset(),
                 Ok(false) => break,
-                Err(e) => {
-                    tracing::error!("Error handling event: {:?}", e);
-                }
+                Err(e) => match e.policy() {
+                    ErrorPolicy::Retry | ErrorPolicy::Skip => {
+                        tracing::warn!("Skipping event: {}", e);
+                    }
+                    ErrorPolicy::BackOff => {
+                        let delay = backoff.next_delay();
+                        tracing::warn!("{}, pausing for {:?}", e, delay);
+                        if shutdown.sleep(delay) {
+                            break;
+                        }
+                    }
+                    ErrorPolicy::Halt => {
+                        tracing::error!("Halting: {}", e);
+                        halted = Some(e);
+                        break;
+                    }
+                },
             }
         }
-        Ok(())
+        for (name, engine) in &engines {
+            let _span = tracing::info_span!("market", market = %name).entered();
+            engine.finish()?;
+        }
+        let stats = retry.stats();
+        tracing::info!(
+            retries = stats.retries,
+            recovered = stats.recovered,
+            exhausted = stats.exhausted,
+            landed = stats.landed,
+            "Node call retries"
+        );
+        match halted {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
     }
 }
 
 impl Bot {
+    /// Lets every market's engine handle `event`, so one failing market holds
+    /// up none of the others. Returns the error that asks the most of the run
+    /// loop, once all markets are done.
+    fn handle_event(
+        &self,
+        treasury: &AssetManager,
+        engines: &[(&str, BotEngine)],
+        event: &BotEvent,
+    ) -> Result<bool, BotError> {
+        let mut keep_running = true;
+        let mut worst: Option<BotError> = None;
+        let mut keep_worst = |e: BotError| {
+            let outranked = worst
+                .as_ref()
+                .is_some_and(|worst| severity(worst.policy()) >= severity(e.policy()));
+            if outranked {
+                tracing::warn!("{}", e);
+            } else if let Some(outranked) = worst.replace(e) {
+                tracing::warn!("{}", outranked);
+            }
+        };
+        if !matches!(event, BotEvent::Shutdown) {
+            if let Err(e) = treasury.manage_cspr_level() {
+                keep_worst(e);
+            }
+        }
+        for (name, engine) in engines {
+            let _span = tracing::info_span!("market", market = %name).entered();
+            match self.handle_market_event(engine, event) {
+                Ok(running) => keep_running &= running,
+                Err(e) => keep_worst(e),
+            }
+        }
+        match worst {
+            Some(e) => Err(e),
+            None => Ok(keep_running),
+        }
+    }
+
+    /// Handles `event`, handling it once more if the first attempt asked for a retry.
+    fn handle_market_event(&self, engine: &BotEngine, event: &BotEvent) -> Result<bool, BotError> {
+        match engine.handle_event(event) {
+            Err(e) if e.policy() == ErrorPolicy::Retry => {
+                tracing::warn!("{}, retrying", e);
+                engine.handle_event(event)
+            }
+            result => result,
+        }
+    }
+
     fn build_token_manager<'a>(
         &self,
         dry_run: bool,
         env: &'a HostEnv,
         contracts: &'a ContractRefs<'a>,
+        config: &'a BotConfig,
     ) -> Box<dyn TokenManager + 'a> {
         if dry_run {
-            tracing::info!("Dry run mode enabled");
             Box::new(DryRunTokenManager)
         } else {
-            Box::new(RealTokenManager::new(env, contracts))
+            Box::new(RealTokenManager::new(env, contracts, config))
+        }
+    }
+}
+
+/// How much a failure of `policy` asks of the run loop.
+fn severity(policy: ErrorPolicy) -> u8 {
+    match policy {
+        ErrorPolicy::Retry | ErrorPolicy::Skip => 0,
+        ErrorPolicy::BackOff => 1,
+        ErrorPolicy::Halt => 2,
+    }
+}
+
+/// Shutdown signals and a timer ticking every `interval`, merged with the node
+/// event stream of every market's pairs when one is configured.
+fn build_event_source(
+    markets: &[ContractRefs],
+    config: &BotConfig,
+    interval: Duration,
+    shutdown: &ShutdownSignal,
+) -> Result<CompositeEventSource, Error> {
+    let mut sources: Vec<Box<dyn EventSource + Send>> = vec![
+        Box::new(ShutdownEventSource::new(shutdown.clone())),
+        Box::new(TimerEventSource::new(interval, shutdown.clone())),
+    ];
+    if let Some(url) = config.events.sse_url.clone() {
+        tracing::info!("Listening to node events at {}", url);
+        let mut watched = vec![];
+        // Every market reads the same pairs of the contracts container
+        if let Some(contracts) = markets.first() {
+            for (name, pair) in contracts.pairs()? {
+                watched.push(WatchedContract::pair(&name, &pair.address()));
+            }
+        }
+        for contracts in markets {
+            watched.push(WatchedContract::market(
+                &contracts.names().market,
+                &contracts.market()?.address(),
+            ));
         }
+        let backoff = Backoff::new(
+            config.events.reconnect_initial(),
+            config.events.reconnect_max(),
+        );
+        sources.push(Box::new(SseEventSource::new(url, watched, backoff)));
     }
+    Ok(CompositeEventSource::new(sources, config.events.debounce()))
 }
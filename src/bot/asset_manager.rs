@@ -1,77 +1,84 @@
+use std::cell::Cell;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
 use odra::{
     casper_types::U256,
     host::{HostEnv, HostRef},
     prelude::{Address, Addressable},
     uints::ToU256,
 };
-use odra_cli::{cspr, scenario::Error};
 
 use crate::{
-    bot::{data::PriceData, path::Path},
+    bot::{
+        config::BotConfig,
+        data::PriceData,
+        error::{BotError, CallResult},
+        graph::RouteGraph,
+        limits::RiskLimits,
+        path::{Path, Token},
+        price::{motes_to_cspr, Gain},
+        sizing,
+        slippage::{self, SwapMode, SwapOrder},
+        strategy::{Inventory, Position},
+    },
     contracts::ContractRefs,
 };
 
-const TOP_UP_AMOUNT: u64 = 2_000_000_000_000; // 2_000 cspr
-const MIN_CSPR_BALANCE: u64 = 100_000_000_000; // 100 CSPR
-const MIN_WCSPR_BALANCE: u64 = 1_500_000_000_000; // 1_500 CSPR
-const UNWRAP_AMOUNT: u64 = 1_500_000_000_000; // 1_500 CSPR
-
 #[cfg(test)]
 use mockall::automock;
 
 #[cfg_attr(test, automock)]
 pub trait Balances {
-    fn my_cspr_balance(&self) -> Result<U256, Error>;
-    fn my_wcspr_balance(&self) -> Result<U256, Error>;
-    fn my_long_balance(&self) -> Result<U256, Error>;
-    fn my_short_balance(&self) -> Result<U256, Error>;
+    fn my_cspr_balance(&self) -> Result<U256, BotError>;
+    fn my_wcspr_balance(&self) -> Result<U256, BotError>;
+    fn my_long_balance(&self) -> Result<U256, BotError>;
+    fn my_short_balance(&self) -> Result<U256, BotError>;
 }
 
 #[cfg_attr(test, automock)]
 pub trait TokenManager {
-    fn approve_markets(&self) -> Result<(), Error>;
-    fn wrap_cspr(&self) -> Result<(), Error>;
-    fn unwrap_wcspr(&self, amount: U256) -> Result<(), Error>;
-    fn buy_longs(&self) -> Result<(), Error>;
-    fn buy_shorts(&self) -> Result<(), Error>;
-    fn swap(
-        &self,
-        path: Path,
-        amount_in: U256,
-        amount_out: U256,
-        recipient: Address,
-    ) -> Result<Vec<U256>, Error>;
+    fn approve_markets(&self) -> Result<(), BotError>;
+    fn wrap_cspr(&self, amount: U256) -> Result<(), BotError>;
+    fn unwrap_wcspr(&self, amount: U256) -> Result<(), BotError>;
+    fn buy_longs(&self, amount: U256) -> Result<(), BotError>;
+    fn buy_shorts(&self, amount: U256) -> Result<(), BotError>;
+    fn redeem_longs(&self, amount: U256) -> Result<(), BotError>;
+    fn redeem_shorts(&self, amount: U256) -> Result<(), BotError>;
+    fn swap(&self, path: Path, order: SwapOrder, recipient: Address)
+        -> Result<Vec<U256>, BotError>;
 }
 
 pub struct RealTokenManager<'a> {
     env: &'a HostEnv,
     refs: &'a ContractRefs<'a>,
+    config: &'a BotConfig,
 }
 
 impl<'a> RealTokenManager<'a> {
-    pub fn new(env: &'a HostEnv, refs: &'a ContractRefs<'a>) -> Self {
-        Self { env, refs }
+    pub fn new(env: &'a HostEnv, refs: &'a ContractRefs<'a>, config: &'a BotConfig) -> Self {
+        Self { env, refs, config }
     }
 
-    pub fn wcspr_allowance(&self, spender: &Address) -> Result<U256, Error> {
+    pub fn wcspr_allowance(&self, spender: &Address) -> Result<U256, BotError> {
         let me = self.env.caller();
         Ok(self.refs.wcspr()?.allowance(&me, spender))
     }
 
-    pub fn long_allowance(&self, spender: &Address) -> Result<U256, Error> {
+    pub fn long_allowance(&self, spender: &Address) -> Result<U256, BotError> {
         let me = self.env.caller();
         Ok(self.refs.long()?.allowance(&me, spender))
     }
 
-    pub fn short_allowance(&self, spender: &Address) -> Result<U256, Error> {
+    pub fn short_allowance(&self, spender: &Address) -> Result<U256, BotError> {
         let me = self.env.caller();
         Ok(self.refs.short()?.allowance(&me, spender))
     }
 }
 
 impl TokenManager for RealTokenManager<'_> {
-    fn approve_markets(&self) -> Result<(), Error> {
-        self.env.set_gas(cspr!(4));
+    fn approve_markets(&self) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
         let cspr_trade_address = self.refs.router()?.address();
         let cspr_delta_address = self.refs.market()?.address();
         // Casper trade must be able to spend wcspr, long and short tokens
@@ -92,90 +99,195 @@ impl TokenManager for RealTokenManager<'_> {
         Ok(())
     }
 
-    fn wrap_cspr(&self) -> Result<(), Error> {
-        self.env.set_gas(cspr!(4));
+    fn wrap_cspr(&self, amount: U256) -> Result<(), BotError> {
+        WcsprTokenManager::new(self.env, self.refs, self.config).wrap_cspr(amount)
+    }
+
+    fn unwrap_wcspr(&self, amount: U256) -> Result<(), BotError> {
+        WcsprTokenManager::new(self.env, self.refs, self.config).unwrap_wcspr(amount)
+    }
+
+    fn buy_longs(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
         self.refs
-            .wcspr()?
-            .with_tokens(TOP_UP_AMOUNT.into())
-            .try_deposit()?;
+            .market()?
+            .try_deposit_long(amount)
+            .during("deposit_long")?;
         Ok(())
     }
 
-    fn unwrap_wcspr(&self, amount: U256) -> Result<(), Error> {
-        self.env.set_gas(cspr!(4));
-        self.refs.wcspr()?.try_withdraw(&amount)?;
+    fn buy_shorts(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
+        self.refs
+            .market()?
+            .try_deposit_short(amount)
+            .during("deposit_short")?;
         Ok(())
     }
 
-    fn buy_longs(&self) -> Result<(), Error> {
-        self.env.set_gas(cspr!(4));
-        self.refs.market()?.try_deposit_long(TOP_UP_AMOUNT.into())?;
+    fn redeem_longs(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
+        self.refs
+            .market()?
+            .try_withdraw_long(amount)
+            .during("withdraw_long")?;
         Ok(())
     }
 
-    fn buy_shorts(&self) -> Result<(), Error> {
-        self.env.set_gas(cspr!(4));
+    fn redeem_shorts(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
         self.refs
             .market()?
-            .try_deposit_short(TOP_UP_AMOUNT.into())?;
+            .try_withdraw_short(amount)
+            .during("withdraw_short")?;
         Ok(())
     }
 
     fn swap(
         &self,
         path: Path,
-        amount_in: U256,
-        amount_out: U256,
+        order: SwapOrder,
         recipient: Address,
-    ) -> Result<Vec<U256>, Error> {
-        if path.is_multi_hop() {
-            self.env.set_gas(cspr!(13));
-        } else {
-            self.env.set_gas(cspr!(8));
-        }
-        let result = self.refs.router()?.swap_tokens_for_exact_tokens(
-            amount_out,
-            amount_in,
-            path.build(self.refs)?,
-            recipient,
-            u64::MAX,
-        );
-        Ok(result)
+    ) -> Result<Vec<U256>, BotError> {
+        self.env.set_gas(self.config.gas.swap(path));
+        let tokens = path.build(self.refs)?;
+        let deadline = swap_deadline(self.env.block_time(), self.config.deadline.ttl());
+        let result = match order.mode {
+            SwapMode::ExactIn => self
+                .refs
+                .router()?
+                .try_swap_exact_tokens_for_tokens(
+                    order.amount_in,
+                    order.amount_out,
+                    tokens,
+                    recipient,
+                    deadline,
+                )
+                .during(SWAP_EXACT_IN),
+            SwapMode::ExactOut => self
+                .refs
+                .router()?
+                .try_swap_tokens_for_exact_tokens(
+                    order.amount_out,
+                    order.amount_in,
+                    tokens,
+                    recipient,
+                    deadline,
+                )
+                .during(SWAP_EXACT_OUT),
+        };
+        result.map_err(|e| swap_error(e, deadline))
+    }
+}
+
+/// Wraps and unwraps the wCSPR every market shares, and nothing else: the
+/// token manager of the treasury, which belongs to no market.
+pub struct WcsprTokenManager<'a> {
+    env: &'a HostEnv,
+    refs: &'a ContractRefs<'a>,
+    config: &'a BotConfig,
+}
+
+impl<'a> WcsprTokenManager<'a> {
+    pub fn new(env: &'a HostEnv, refs: &'a ContractRefs<'a>, config: &'a BotConfig) -> Self {
+        Self { env, refs, config }
     }
 }
 
+impl TokenManager for WcsprTokenManager<'_> {
+    fn approve_markets(&self) -> Result<(), BotError> {
+        // wCSPR is only ever wrapped and unwrapped, which needs no allowance
+        Ok(())
+    }
+
+    fn wrap_cspr(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
+        self.refs
+            .wcspr()?
+            .with_tokens(amount)
+            .try_deposit()
+            .during("deposit")?;
+        Ok(())
+    }
+
+    fn unwrap_wcspr(&self, amount: U256) -> Result<(), BotError> {
+        self.env.set_gas(self.config.gas.default_call);
+        self.refs
+            .wcspr()?
+            .try_withdraw(&amount)
+            .during("withdraw")?;
+        Ok(())
+    }
+
+    fn buy_longs(&self, _amount: U256) -> Result<(), BotError> {
+        Err(wcspr_only("buy_longs"))
+    }
+
+    fn buy_shorts(&self, _amount: U256) -> Result<(), BotError> {
+        Err(wcspr_only("buy_shorts"))
+    }
+
+    fn redeem_longs(&self, _amount: U256) -> Result<(), BotError> {
+        Err(wcspr_only("redeem_longs"))
+    }
+
+    fn redeem_shorts(&self, _amount: U256) -> Result<(), BotError> {
+        Err(wcspr_only("redeem_shorts"))
+    }
+
+    fn swap(
+        &self,
+        _path: Path,
+        _order: SwapOrder,
+        _recipient: Address,
+    ) -> Result<Vec<U256>, BotError> {
+        Err(wcspr_only("swap"))
+    }
+}
+
+fn wcspr_only(call: &str) -> BotError {
+    BotError::Invariant(format!("{} called on the wCSPR-only treasury", call))
+}
+
 pub struct DryRunTokenManager;
 
 impl TokenManager for DryRunTokenManager {
-    fn approve_markets(&self) -> Result<(), Error> {
+    fn approve_markets(&self) -> Result<(), BotError> {
+        Ok(())
+    }
+
+    fn wrap_cspr(&self, _amount: U256) -> Result<(), BotError> {
+        Ok(())
+    }
+
+    fn unwrap_wcspr(&self, _amount: U256) -> Result<(), BotError> {
         Ok(())
     }
 
-    fn wrap_cspr(&self) -> Result<(), Error> {
+    fn buy_longs(&self, _amount: U256) -> Result<(), BotError> {
         Ok(())
     }
 
-    fn unwrap_wcspr(&self, _amount: U256) -> Result<(), Error> {
+    fn buy_shorts(&self, _amount: U256) -> Result<(), BotError> {
         Ok(())
     }
 
-    fn buy_longs(&self) -> Result<(), Error> {
+    fn redeem_longs(&self, _amount: U256) -> Result<(), BotError> {
         Ok(())
     }
 
-    fn buy_shorts(&self) -> Result<(), Error> {
+    fn redeem_shorts(&self, _amount: U256) -> Result<(), BotError> {
         Ok(())
     }
 
     fn swap(
         &self,
         _path: Path,
-        amount_in: U256,
-        amount_out: U256,
+        order: SwapOrder,
         _recipient: Address,
-    ) -> Result<Vec<U256>, Error> {
+    ) -> Result<Vec<U256>, BotError> {
         tracing::info!("Dry run - swap skipped");
-        Ok(vec![amount_in, amount_out])
+        Ok(vec![order.amount_in, order.amount_out])
     }
 }
 
@@ -191,114 +303,316 @@ impl<'a> RealBalances<'a> {
 }
 
 impl Balances for RealBalances<'_> {
-    fn my_cspr_balance(&self) -> Result<U256, Error> {
+    fn my_cspr_balance(&self) -> Result<U256, BotError> {
+        WcsprBalances::new(self.env, self.refs).my_cspr_balance()
+    }
+
+    fn my_wcspr_balance(&self) -> Result<U256, BotError> {
+        WcsprBalances::new(self.env, self.refs).my_wcspr_balance()
+    }
+
+    fn my_long_balance(&self) -> Result<U256, BotError> {
         let me = self.env.caller();
-        Ok(self
-            .env
-            .balance_of(&me)
-            .to_u256()
-            .map_err(|_| Error::OdraError {
-                message: "Failed to convert cspr balance to u256".to_string(),
-            })?)
+        self.refs.long()?.try_balance_of(&me).during("balance_of")
     }
 
-    fn my_wcspr_balance(&self) -> Result<U256, Error> {
+    fn my_short_balance(&self) -> Result<U256, BotError> {
         let me = self.env.caller();
-        Ok(self.refs.wcspr()?.balance_of(&me))
+        self.refs.short()?.try_balance_of(&me).during("balance_of")
     }
+}
+
+/// The CSPR and wCSPR balances every market shares, the only ones the
+/// treasury looks at.
+pub struct WcsprBalances<'a> {
+    env: &'a HostEnv,
+    refs: &'a ContractRefs<'a>,
+}
+
+impl<'a> WcsprBalances<'a> {
+    pub fn new(env: &'a HostEnv, refs: &'a ContractRefs<'a>) -> Self {
+        Self { env, refs }
+    }
+}
 
-    fn my_long_balance(&self) -> Result<U256, Error> {
+impl Balances for WcsprBalances<'_> {
+    fn my_cspr_balance(&self) -> Result<U256, BotError> {
         let me = self.env.caller();
-        Ok(self.refs.long()?.balance_of(&me))
+        Ok(self.env.balance_of(&me).to_u256().map_err(|_| {
+            BotError::Invariant("Failed to convert cspr balance to u256".to_string())
+        })?)
     }
 
-    fn my_short_balance(&self) -> Result<U256, Error> {
+    fn my_wcspr_balance(&self) -> Result<U256, BotError> {
         let me = self.env.caller();
-        Ok(self.refs.short()?.balance_of(&me))
+        self.refs.wcspr()?.try_balance_of(&me).during("balance_of")
+    }
+
+    fn my_long_balance(&self) -> Result<U256, BotError> {
+        Err(wcspr_only("my_long_balance"))
+    }
+
+    fn my_short_balance(&self) -> Result<U256, BotError> {
+        Err(wcspr_only("my_short_balance"))
     }
 }
 
 pub struct AssetManager<'a> {
     balances: &'a dyn Balances,
     token_manager: &'a dyn TokenManager,
+    config: &'a BotConfig,
+    limits: Option<&'a RiskLimits>,
+    /// Share of the wCSPR balance this market trades with, in basis points.
+    treasury_bps: u64,
+    /// The wCSPR this market may still spend, if it shares the balance with
+    /// other markets: its share at the start, less what it spent since and
+    /// plus what it earned back.
+    budget: Option<Cell<U256>>,
 }
 
 impl<'a> AssetManager<'a> {
-    pub fn new(balances: &'a dyn Balances, token_manager: &'a dyn TokenManager) -> Self {
+    pub fn new(
+        balances: &'a dyn Balances,
+        token_manager: &'a dyn TokenManager,
+        config: &'a BotConfig,
+    ) -> Self {
         Self {
             balances,
             token_manager,
+            config,
+            limits: None,
+            treasury_bps: 10_000,
+            budget: None,
         }
     }
 
+    /// Counts market deposits and unwraps against `limits`, refusing any that
+    /// would break them.
+    pub fn with_limits(mut self, limits: &'a RiskLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Trades with `bps` basis points of the wCSPR balance and top-up, the
+    /// share of a treasury several markets trade from. The share of the
+    /// current balance becomes the market's budget, which its own spends and
+    /// refills move from then on, so no market can spend another's share.
+    pub fn with_treasury_share(mut self, bps: u64) -> Result<Self, BotError> {
+        self.treasury_bps = bps;
+        if bps < 10_000 {
+            let budget = self.treasury_share(self.balances.my_wcspr_balance()?);
+            self.budget = Some(Cell::new(budget));
+        }
+        Ok(self)
+    }
+
+    /// Swaps along `path`, first topping up the input token to cover the
+    /// order's maximum input.
     pub fn swap(
         &self,
         path: Path,
-        amount_in: U256,
-        amount_out: U256,
+        order: SwapOrder,
         recipient: Address,
-    ) -> Result<Vec<U256>, Error> {
-        self.ensure_funds(path, amount_in)?;
-        let result = self
-            .token_manager
-            .swap(path, amount_in, amount_out, recipient)?;
+    ) -> Result<Vec<U256>, BotError> {
+        self.ensure_funds(path, order.amount_in)?;
+        let result = self.token_manager.swap(path, order, recipient)?;
+        self.track_swap(path, &result);
         Ok(result)
     }
 
-    pub fn manage_asset_levels(
+    /// Swaps along `path` with tokens an earlier action of the same plan
+    /// produced, e.g. freshly minted ones, without topping anything up.
+    pub fn swap_held(
         &self,
-        price_data: &PriceData,
+        path: Path,
+        order: SwapOrder,
         recipient: Address,
-    ) -> Result<(), Error> {
+    ) -> Result<Vec<U256>, BotError> {
+        let result = self.token_manager.swap(path, order, recipient)?;
+        self.track_swap(path, &result);
+        Ok(result)
+    }
+
+    /// The balances a strategy can plan with, and the size of a single top-up
+    /// in `ensure_funds`.
+    pub fn inventory(&self) -> Result<Inventory, BotError> {
+        Ok(Inventory {
+            wcspr: self.wcspr_balance()?,
+            long: self.balances.my_long_balance()?,
+            short: self.balances.my_short_balance()?,
+            top_up: self.top_up_amount(),
+        })
+    }
+
+    fn treasury_share(&self, amount: U256) -> U256 {
+        amount * self.treasury_bps / 10_000u64
+    }
+
+    /// The wCSPR this market may spend: its budget, as far as the shared
+    /// balance still covers it.
+    fn wcspr_balance(&self) -> Result<U256, BotError> {
+        let balance = self.balances.my_wcspr_balance()?;
+        Ok(match &self.budget {
+            Some(budget) => budget.get().min(balance),
+            None => balance,
+        })
+    }
+
+    /// Counts `amount` wCSPR the market spent against its budget, if any.
+    fn spend(&self, amount: U256) {
+        if let Some(budget) = &self.budget {
+            budget.set(budget.get().saturating_sub(amount));
+        }
+    }
+
+    /// Adds `amount` wCSPR the market received to its budget, if any.
+    fn refill(&self, amount: U256) {
+        if let Some(budget) = &self.budget {
+            budget.set(budget.get().saturating_add(amount));
+        }
+    }
+
+    /// Moves the budget by the wCSPR a swap along `path` spent or received,
+    /// given the amounts of every hop.
+    fn track_swap(&self, path: Path, amounts: &[U256]) {
+        if path.token_in() == Some(Token::Wcspr) {
+            self.spend(amounts.first().copied().unwrap_or_default());
+        }
+        if path.token_out() == Some(Token::Wcspr) {
+            self.refill(amounts.last().copied().unwrap_or_default());
+        }
+    }
+
+    /// The market's share of a single top-up.
+    fn top_up_amount(&self) -> U256 {
+        self.treasury_share(self.config.inventory.top_up_amount.into())
+    }
+
+    /// Deposits `amount` wCSPR into the market for `position` tokens, wrapping
+    /// CSPR first if the market's wCSPR budget falls short.
+    pub fn mint(&self, position: Position, amount: U256) -> Result<(), BotError> {
+        self.check_limits(amount)?;
+        if self.wcspr_balance()? < amount {
+            tracing::warn!(
+                "Not enough wcspr to mint {:?} tokens, wrapping cspr",
+                position
+            );
+            self.wrap_cspr()?;
+        }
+        match position {
+            Position::Long => self.token_manager.buy_longs(amount)?,
+            Position::Short => self.token_manager.buy_shorts(amount)?,
+        }
+        self.spend(amount);
+        self.record_deposit(amount);
+        Ok(())
+    }
+
+    /// Withdraws `amount` `position` tokens from the market for wCSPR, or all
+    /// the bot holds if a swap delivered fewer than planned. Returns the amount
+    /// withdrawn.
+    pub fn redeem(&self, position: Position, amount: U256) -> Result<U256, BotError> {
+        // What the market pays out depends on its price at execution, so the
+        // budget takes the change in the balance
+        let wcspr_before = match self.budget {
+            Some(_) => Some(self.balances.my_wcspr_balance()?),
+            None => None,
+        };
+        let amount = self.withdraw(position, amount)?;
+        if let Some(wcspr_before) = wcspr_before {
+            let wcspr_after = self.balances.my_wcspr_balance()?;
+            self.refill(wcspr_after.saturating_sub(wcspr_before));
+        }
+        Ok(amount)
+    }
+
+    fn withdraw(&self, position: Position, amount: U256) -> Result<U256, BotError> {
+        match position {
+            Position::Long => {
+                let amount = amount.min(self.balances.my_long_balance()?);
+                self.token_manager.redeem_longs(amount)?;
+                Ok(amount)
+            }
+            Position::Short => {
+                let amount = amount.min(self.balances.my_short_balance()?);
+                self.token_manager.redeem_shorts(amount)?;
+                Ok(amount)
+            }
+        }
+    }
+
+    /// Unwraps wCSPR when the CSPR balance that pays every market's gas runs
+    /// low. The balance is shared, so this runs once per check for the whole
+    /// account rather than once per market.
+    pub fn manage_cspr_level(&self) -> Result<(), BotError> {
+        let inventory = &self.config.inventory;
+        let unwrap_amount = inventory.unwrap_amount;
         let cspr_balance = self.balances.my_cspr_balance()?;
-        if cspr_balance < MIN_CSPR_BALANCE.into() {
+        if cspr_balance < inventory.min_cspr_balance.into() {
             tracing::warn!(
                 "CSPR balance low ({:.2} CSPR), unwrapping {:.2} wCSPR",
                 humanize_balance(cspr_balance),
-                humanize_balance(UNWRAP_AMOUNT.into()),
+                humanize_balance(unwrap_amount.into()),
             );
-            self.token_manager.unwrap_wcspr(UNWRAP_AMOUNT.into())?;
-            return Ok(());
+            self.check_limits(unwrap_amount.into())?;
+            self.token_manager.unwrap_wcspr(unwrap_amount.into())?;
+            self.record_unwrap(unwrap_amount.into());
         }
+        Ok(())
+    }
 
-        let wcspr_balance = self.balances.my_wcspr_balance()?;
-        if wcspr_balance < MIN_WCSPR_BALANCE.into() {
+    /// Sells the market's positions for wCSPR when its wCSPR budget falls
+    /// below its share of the minimum.
+    pub fn manage_asset_levels(
+        &self,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        recipient: Address,
+    ) -> Result<(), BotError> {
+        let inventory = &self.config.inventory;
+        let unwrap_amount = self.treasury_share(inventory.unwrap_amount.into());
+        let wcspr_balance = self.wcspr_balance()?;
+        if wcspr_balance < self.treasury_share(inventory.min_wcspr_balance.into()) {
             tracing::warn!(
                 "wCSPR balance low ({:.2} CSPR), selling positions for wCSPR",
                 humanize_balance(wcspr_balance),
             );
             let long_balance = self.balances.my_long_balance()?;
             let short_balance = self.balances.my_short_balance()?;
-            let long_cspr_value = long_balance.as_u64() as f64 * price_data.long_price;
-            let short_cspr_value = short_balance.as_u64() as f64 * price_data.short_price;
+            let long_cspr_value = price_data.long_price.quote(long_balance);
+            let short_cspr_value = price_data.short_price.quote(short_balance);
 
-            if long_cspr_value >= short_cspr_value {
+            let path = if long_cspr_value >= short_cspr_value {
                 tracing::info!("Selling longs for wCSPR");
-                // amount_in_max: how many longs needed to receive UNWRAP_AMOUNT wCSPR,
-                // with 5% slippage tolerance
-                let amount_in = (UNWRAP_AMOUNT as f64 / price_data.long_price * 1.05) as u64;
-                self.token_manager.swap(
-                    Path::LongWcspr,
-                    U256::from(amount_in),
-                    UNWRAP_AMOUNT.into(),
-                    recipient,
-                )?;
+                Path::LongWcspr
             } else {
                 tracing::info!("Selling shorts for wCSPR");
-                let amount_in = (UNWRAP_AMOUNT as f64 / price_data.short_price * 1.05) as u64;
-                self.token_manager.swap(
-                    Path::ShortWcspr,
-                    U256::from(amount_in),
-                    UNWRAP_AMOUNT.into(),
-                    recipient,
-                )?;
+                Path::ShortWcspr
+            };
+            // How many tokens it takes to receive unwrap_amount wCSPR from the
+            // pool, after its fee and price impact
+            let amount_in = sizing::quote_in(
+                path,
+                unwrap_amount,
+                graph,
+                self.config.strategy.pair_fee_bps,
+            );
+            if amount_in.is_zero() {
+                return Err(BotError::StaleData(format!(
+                    "{} pool cannot pay out {:.2} wCSPR",
+                    path,
+                    humanize_balance(unwrap_amount)
+                )));
             }
+            let order = slippage::bound(amount_in, unwrap_amount, &self.config.slippage);
+            let result = self.token_manager.swap(path, order, recipient)?;
+            self.track_swap(path, &result);
         }
 
         Ok(())
     }
 
-    pub fn print_balances(&self) -> Result<(), Error> {
+    pub fn print_balances(&self) -> Result<(), BotError> {
         log_humanized("CSPR balance", self.balances.my_cspr_balance()?);
         log_humanized("WCSPR balance", self.balances.my_wcspr_balance()?);
         log_humanized("Long balance", self.balances.my_long_balance()?);
@@ -306,52 +620,54 @@ impl<'a> AssetManager<'a> {
         Ok(())
     }
 
-    fn ensure_funds(&self, path: Path, amount_in: U256) -> Result<(), Error> {
-        match path {
-            Path::LongWcsprShort | Path::LongWcspr => self.top_up_longs_if_required(amount_in)?,
-            Path::ShortWcsprLong | Path::ShortWcspr => self.top_up_shorts_if_required(amount_in)?,
-            Path::WcsprLong | Path::WcsprShort => self.top_up_wcspr_if_required(amount_in)?,
-            Path::Empty => panic!("Empty path is not supported"),
+    fn ensure_funds(&self, path: Path, amount_in: U256) -> Result<(), BotError> {
+        match path.token_in() {
+            Some(Token::Long) => self.top_up_longs_if_required(amount_in)?,
+            Some(Token::Short) => self.top_up_shorts_if_required(amount_in)?,
+            Some(Token::Wcspr) => self.top_up_wcspr_if_required(amount_in)?,
+            Some(token @ Token::Other(_)) => {
+                return Err(BotError::Invariant(format!(
+                    "No top-up for {}, which the bot does not hold",
+                    token
+                )))
+            }
+            None => {
+                return Err(BotError::Invariant(
+                    "Empty path is not supported".to_string(),
+                ))
+            }
         }
         tracing::info!("Funds for swap ready!");
         Ok(())
     }
 
-    fn top_up_longs_if_required(&self, required_balance: U256) -> Result<(), Error> {
+    fn top_up_longs_if_required(&self, required_balance: U256) -> Result<(), BotError> {
         let long_balance = self.balances.my_long_balance()?;
         log_humanized("Required balance", required_balance);
         log_humanized("LONG balance", long_balance);
         if long_balance < required_balance {
             tracing::warn!("Not enough longs, topping up");
-            if self.balances.my_wcspr_balance()? < TOP_UP_AMOUNT.into() {
-                tracing::warn!("Not enough wcspr to top up longs, wrapping cspr");
-                self.wrap_cspr()?;
-            }
-            self.token_manager.buy_longs()?;
+            self.mint(Position::Long, self.top_up_amount())?;
             log_humanized("New LONG balance", self.balances.my_long_balance()?);
         }
         Ok(())
     }
 
-    fn top_up_shorts_if_required(&self, required_balance: U256) -> Result<(), Error> {
+    fn top_up_shorts_if_required(&self, required_balance: U256) -> Result<(), BotError> {
         let short_balance = self.balances.my_short_balance()?;
         log_humanized("Required balance", required_balance);
         log_humanized("SHORT balance", short_balance);
         if short_balance < required_balance {
             tracing::warn!("Not enough shorts, topping up");
-            if self.balances.my_wcspr_balance()? < TOP_UP_AMOUNT.into() {
-                tracing::warn!("Not enough wcspr to top up shorts, wrapping cspr");
-                self.wrap_cspr()?;
-            }
-            self.token_manager.buy_shorts()?;
+            self.mint(Position::Short, self.top_up_amount())?;
             log_humanized("New SHORT balance", self.balances.my_short_balance()?);
         }
 
         Ok(())
     }
 
-    fn top_up_wcspr_if_required(&self, required_balance: U256) -> Result<(), Error> {
-        let wcspr_balance = self.balances.my_wcspr_balance()?;
+    fn top_up_wcspr_if_required(&self, required_balance: U256) -> Result<(), BotError> {
+        let wcspr_balance = self.wcspr_balance()?;
         log_humanized("Required WCSPR balance", required_balance);
         log_humanized("Current WCSPR balance", wcspr_balance);
         if wcspr_balance < required_balance {
@@ -363,20 +679,102 @@ impl<'a> AssetManager<'a> {
         Ok(())
     }
 
-    fn wrap_cspr(&self) -> Result<(), Error> {
+    /// Checks a market deposit or unwrap of `amount` wCSPR against the risk
+    /// limits, if any.
+    fn check_limits(&self, amount: U256) -> Result<(), BotError> {
+        let Some(limits) = self.limits else {
+            return Ok(());
+        };
+        limits
+            .check_deposit(Utc::now(), amount)
+            .map_err(BotError::LimitReached)
+    }
+
+    /// Counts a market deposit of `amount` wCSPR against the risk limits, if any.
+    /// At fair value the minted tokens are worth the deposit less the market
+    /// fee, so the deposit loses the fee and its transaction.
+    fn record_deposit(&self, amount: U256) {
+        let Some(limits) = self.limits else {
+            return;
+        };
+        let strategy = &self.config.strategy;
+        let gain = Gain::between(
+            amount - sizing::market_fee(amount, strategy),
+            amount.saturating_add(strategy.market_tx_cost()),
+        );
+        if let Err(e) = limits.record(Utc::now(), amount, gain) {
+            tracing::error!(
+                "Failed to record the deposit against the risk limits: {:?}",
+                e
+            );
+        }
+    }
+
+    /// Counts an unwrap of `amount` wCSPR against the risk limits, if any.
+    /// Unwrapping keeps the value and loses its transaction.
+    fn record_unwrap(&self, amount: U256) {
+        let Some(limits) = self.limits else {
+            return;
+        };
+        let gain = Gain::between(
+            amount,
+            amount.saturating_add(self.config.gas.default_call.into()),
+        );
+        if let Err(e) = limits.record(Utc::now(), amount, gain) {
+            tracing::error!(
+                "Failed to record the unwrap against the risk limits: {:?}",
+                e
+            );
+        }
+    }
+
+    fn wrap_cspr(&self) -> Result<(), BotError> {
         let cspr_balance = self.balances.my_cspr_balance()?;
-        if cspr_balance.as_u64() < TOP_UP_AMOUNT {
-            return Err(Error::OdraError {
-                message: "Not enough cspr to wrap".to_string(),
+        let top_up_amount = self.top_up_amount();
+        if cspr_balance < top_up_amount {
+            return Err(BotError::InsufficientFunds {
+                token: "CSPR",
+                required: top_up_amount,
+                available: cspr_balance,
             });
         }
-        self.token_manager.wrap_cspr()?;
+        self.token_manager.wrap_cspr(top_up_amount)?;
+        self.refill(top_up_amount);
         Ok(())
     }
 }
 
+/// Block time (in ms) after which a swap sent now must not execute: `ttl` past
+/// the later of the last block time and the wall clock.
+fn swap_deadline(block_time: u64, ttl: Duration) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_millis() as u64)
+        .unwrap_or_default();
+    block_time.max(now).saturating_add(ttl.as_millis() as u64)
+}
+
+/// Router entry points the bot swaps through.
+const SWAP_EXACT_IN: &str = "swap_exact_tokens_for_tokens";
+const SWAP_EXACT_OUT: &str = "swap_tokens_for_exact_tokens";
+
+/// Error code the router reverts with when a swap reaches it past its deadline.
+const ROUTER_EXPIRED_CODE: u16 = 1;
+
+/// Classifies a failed swap sent with `deadline`: only the router's own
+/// deadline revert means the swap expired, any other failure keeps its class.
+fn swap_error(error: BotError, deadline: u64) -> BotError {
+    match error {
+        BotError::Reverted {
+            call: SWAP_EXACT_IN | SWAP_EXACT_OUT,
+            code: ROUTER_EXPIRED_CODE,
+        } => BotError::DeadlineExpired { deadline },
+        error => error,
+    }
+}
+
 fn humanize_balance(balance: U256) -> f64 {
-    balance.as_u64() as f64 / 1_000_000_000.0f64
+    motes_to_cspr(balance)
 }
 
 fn log_humanized(label: &str, balance: U256) {
@@ -387,32 +785,61 @@ fn log_humanized(label: &str, balance: U256) {
 mod tests {
 
     use super::*;
+    use crate::bot::{data::PoolReserves, limits::LimitBreach};
     use odra_test::env;
 
-    fn setup_test_env() -> (HostEnv, MockBalances, MockTokenManager) {
+    const TOP_UP_AMOUNT: u64 = 2_000_000_000_000;
+    const MIN_CSPR_BALANCE: u64 = 100_000_000_000;
+    const MIN_WCSPR_BALANCE: u64 = 1_500_000_000_000;
+    const UNWRAP_AMOUNT: u64 = 1_500_000_000_000;
+
+    fn test_config() -> BotConfig {
+        let mut config = BotConfig::default();
+        config.inventory.top_up_amount = TOP_UP_AMOUNT;
+        config.inventory.min_cspr_balance = MIN_CSPR_BALANCE;
+        config.inventory.min_wcspr_balance = MIN_WCSPR_BALANCE;
+        config.inventory.unwrap_amount = UNWRAP_AMOUNT;
+        config.slippage.tolerance_bps = 500;
+        config
+    }
+
+    fn exact_out(amount_in: U256, amount_out: U256) -> SwapOrder {
+        SwapOrder {
+            mode: SwapMode::ExactOut,
+            amount_in,
+            amount_out,
+        }
+    }
+
+    fn setup_test_env() -> (HostEnv, MockBalances, MockTokenManager, BotConfig) {
         let env = env();
         let refs = MockBalances::new();
         let token_manager = MockTokenManager::new();
-        (env, refs, token_manager)
+        (env, refs, token_manager, test_config())
     }
 
     // ========== Empty Path Tests ==========
 
     #[test]
-    #[should_panic(expected = "Empty path is not supported")]
-    fn test_swap_for_empty_path_panics() {
+    fn test_swap_for_empty_path_is_an_invariant_error() {
         let env = env();
         let refs = MockBalances::new();
         let token_manager = MockTokenManager::new();
-        let asset_manager = AssetManager::new(&refs, &token_manager);
-        let _ = asset_manager.swap(Path::Empty, U256::from(100), U256::from(100), env.caller());
+        let config = test_config();
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        let result = asset_manager.swap(
+            Path::Empty,
+            exact_out(U256::from(100), U256::from(100)),
+            env.caller(),
+        );
+        assert!(matches!(result, Err(BotError::Invariant(_))));
     }
 
     // ========== Multi-Hop Path Tests ==========
 
     #[test]
     fn test_swap_long_wcspr_short_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_long_balance()
             .times(1)
@@ -420,13 +847,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100), U256::from(50)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100), U256::from(50)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::LongWcsprShort,
-            U256::from(100),
-            U256::from(50),
+            exact_out(U256::from(100), U256::from(50)),
             env.caller(),
         );
 
@@ -436,7 +862,7 @@ mod tests {
 
     #[test]
     fn test_swap_short_wcspr_long_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_short_balance()
             .times(1)
@@ -444,13 +870,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100), U256::from(75)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100), U256::from(75)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::ShortWcsprLong,
-            U256::from(100),
-            U256::from(75),
+            exact_out(U256::from(100), U256::from(75)),
             env.caller(),
         );
 
@@ -462,7 +887,7 @@ mod tests {
 
     #[test]
     fn test_swap_long_wcspr_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_long_balance()
             .times(1)
@@ -470,13 +895,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(200)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(200)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::LongWcspr,
-            U256::from(200),
-            U256::from(180),
+            exact_out(U256::from(200), U256::from(180)),
             env.caller(),
         );
 
@@ -485,7 +909,7 @@ mod tests {
 
     #[test]
     fn test_swap_long_wcspr_with_zero_amount() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_long_balance()
             .times(1)
@@ -493,10 +917,14 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::zero()]));
+            .return_once(|_, _, _| Ok(vec![U256::zero()]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
-        let result = asset_manager.swap(Path::LongWcspr, U256::zero(), U256::zero(), env.caller());
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        let result = asset_manager.swap(
+            Path::LongWcspr,
+            exact_out(U256::zero(), U256::zero()),
+            env.caller(),
+        );
 
         assert!(result.is_ok());
     }
@@ -505,7 +933,7 @@ mod tests {
 
     #[test]
     fn test_swap_short_wcspr_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_short_balance()
             .times(1)
@@ -513,13 +941,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(300)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(300)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::ShortWcspr,
-            U256::from(300),
-            U256::from(270),
+            exact_out(U256::from(300), U256::from(270)),
             env.caller(),
         );
 
@@ -528,7 +955,7 @@ mod tests {
 
     #[test]
     fn test_swap_short_wcspr_with_large_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         // Use a large but not MAX value to avoid overflow in humanize_balance
         let large_balance = U256::from(100_000_000_000_000u64);
@@ -538,13 +965,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(1000)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(1000)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::ShortWcspr,
-            U256::from(1000),
-            U256::from(900),
+            exact_out(U256::from(1000), U256::from(900)),
             env.caller(),
         );
 
@@ -555,7 +981,7 @@ mod tests {
 
     #[test]
     fn test_swap_wcspr_long_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_wcspr_balance()
             .times(1)
@@ -563,13 +989,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(150)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(150)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::WcsprLong,
-            U256::from(150),
-            U256::from(140),
+            exact_out(U256::from(150), U256::from(140)),
             env.caller(),
         );
 
@@ -580,7 +1005,7 @@ mod tests {
 
     #[test]
     fn test_swap_wcspr_short_with_sufficient_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_wcspr_balance()
             .times(1)
@@ -588,13 +1013,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(250)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(250)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::WcsprShort,
-            U256::from(250),
-            U256::from(230),
+            exact_out(U256::from(250), U256::from(230)),
             env.caller(),
         );
 
@@ -603,7 +1027,7 @@ mod tests {
 
     #[test]
     fn test_swap_wcspr_short_with_insufficient_cspr_for_wrap() {
-        let (env, mut refs, token_manager) = setup_test_env();
+        let (env, mut refs, token_manager, config) = setup_test_env();
 
         refs.expect_my_wcspr_balance()
             .times(1)
@@ -612,26 +1036,24 @@ mod tests {
         refs.expect_my_cspr_balance()
             .times(1)
             .return_once(|| Ok(U256::from(TOP_UP_AMOUNT - 1)));
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::WcsprShort,
-            U256::from(250),
-            U256::from(230),
+            exact_out(U256::from(250), U256::from(230)),
             env.caller(),
         );
 
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("Not enough cspr to wrap"));
+        assert!(matches!(
+            result,
+            Err(BotError::InsufficientFunds { token: "CSPR", .. })
+        ));
     }
 
     // ========== Edge Cases ==========
 
     #[test]
     fn test_swap_with_exact_required_balance() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_long_balance()
             .times(1)
@@ -639,13 +1061,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::LongWcspr,
-            U256::from(100),
-            U256::from(90),
+            exact_out(U256::from(100), U256::from(90)),
             env.caller(),
         );
 
@@ -654,7 +1075,7 @@ mod tests {
 
     #[test]
     fn test_swap_with_large_amounts() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         let large_amount = U256::from(1_000_000_000_000_000u64);
 
@@ -664,13 +1085,12 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(1_000_000_000_000u64)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(1_000_000_000_000u64)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::WcsprLong,
-            large_amount,
-            U256::from(900_000_000_000_000u64),
+            exact_out(large_amount, U256::from(900_000_000_000_000u64)),
             env.caller(),
         );
 
@@ -681,7 +1101,7 @@ mod tests {
 
     #[test]
     fn test_top_up_long_balance_when_insufficient() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         // First call: insufficient Long balance
         refs.expect_my_long_balance()
@@ -700,28 +1120,81 @@ mod tests {
 
         token_manager
             .expect_buy_longs()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
             .times(1)
-            .return_once(|| Ok(()));
+            .return_once(|_| Ok(()));
 
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::LongWcspr,
-            U256::from(100),
-            U256::from(90),
+            exact_out(U256::from(100), U256::from(90)),
             env.caller(),
         );
 
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_top_up_refused_over_risk_limits() {
+        let (env, mut refs, token_manager, mut config) = setup_test_env();
+        config.limits.max_hourly_notional = TOP_UP_AMOUNT - 1;
+        config.limits.state_path = std::env::temp_dir()
+            .join(format!("asset-manager-limits-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let limits = RiskLimits::open(&config.limits, true).unwrap();
+
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(50)));
+
+        // Neither wraps, buys nor swaps
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config).with_limits(&limits);
+        let result = asset_manager.swap(
+            Path::LongWcspr,
+            exact_out(U256::from(100), U256::from(90)),
+            env.caller(),
+        );
+
+        assert!(matches!(result, Err(BotError::LimitReached(_))));
+    }
+
+    #[test]
+    fn test_deposits_count_their_loss_against_the_risk_limits() {
+        let (_, mut refs, mut token_manager, mut config) = setup_test_env();
+        // A single deposit loses its transaction, reaching the daily loss limit
+        config.limits.max_daily_loss = config.strategy.market_tx_cost().as_u64();
+        config.limits.state_path = std::env::temp_dir()
+            .join(format!("asset-manager-loss-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let limits = RiskLimits::open(&config.limits, true).unwrap();
+        let amount = U256::from(TOP_UP_AMOUNT);
+
+        refs.expect_my_wcspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(TOP_UP_AMOUNT)));
+        token_manager
+            .expect_buy_longs()
+            .times(1)
+            .return_once(|_| Ok(()));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config).with_limits(&limits);
+        assert!(asset_manager.mint(Position::Long, amount).is_ok());
+        assert!(matches!(
+            asset_manager.mint(Position::Long, amount),
+            Err(BotError::LimitReached(LimitBreach::DailyLoss { .. }))
+        ));
+    }
+
     #[test]
     fn test_top_up_short_balance_when_insufficient() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         // First call: insufficient Short balance
         refs.expect_my_short_balance()
@@ -740,19 +1213,19 @@ mod tests {
 
         token_manager
             .expect_buy_shorts()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
             .times(1)
-            .return_once(|| Ok(()));
+            .return_once(|_| Ok(()));
 
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::ShortWcspr,
-            U256::from(100),
-            U256::from(90),
+            exact_out(U256::from(100), U256::from(90)),
             env.caller(),
         );
 
@@ -761,7 +1234,7 @@ mod tests {
 
     #[test]
     fn test_top_up_wcspr_balance_when_insufficient() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         // First call: insufficient WCSPR balance
         refs.expect_my_wcspr_balance()
@@ -780,19 +1253,19 @@ mod tests {
 
         token_manager
             .expect_wrap_cspr()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
             .times(1)
-            .return_once(|| Ok(()));
+            .return_once(|_| Ok(()));
 
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(150)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(150)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::WcsprLong,
-            U256::from(150),
-            U256::from(140),
+            exact_out(U256::from(150), U256::from(140)),
             env.caller(),
         );
 
@@ -801,7 +1274,7 @@ mod tests {
 
     #[test]
     fn test_cascading_top_up_insufficient_wcspr_triggers_cspr_wrap() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
         // First call: insufficient Long balance
         refs.expect_my_long_balance()
@@ -826,24 +1299,25 @@ mod tests {
         // Cascading operations: wrap CSPR first, then buy longs
         token_manager
             .expect_wrap_cspr()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
             .times(1)
-            .return_once(|| Ok(()));
+            .return_once(|_| Ok(()));
 
         token_manager
             .expect_buy_longs()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
             .times(1)
-            .return_once(|| Ok(()));
+            .return_once(|_| Ok(()));
 
         token_manager
             .expect_swap()
             .times(1)
-            .return_once(|_, _, _, _| Ok(vec![U256::from(100)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(100)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.swap(
             Path::LongWcspr,
-            U256::from(100),
-            U256::from(90),
+            exact_out(U256::from(100), U256::from(90)),
             env.caller(),
         );
 
@@ -854,7 +1328,7 @@ mod tests {
 
     #[test]
     fn test_print_balances_success() {
-        let (_, mut refs, token_manager) = setup_test_env();
+        let (_, mut refs, token_manager, config) = setup_test_env();
 
         refs.expect_my_cspr_balance()
             .times(1)
@@ -869,7 +1343,7 @@ mod tests {
             .times(1)
             .return_once(|| Ok(U256::from(4_000_000_000_000u64)));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let result = asset_manager.print_balances();
 
         assert!(result.is_ok());
@@ -881,18 +1355,234 @@ mod tests {
         assert_eq!(humanize_balance(U256::from(2_500_000_000u64)), 2.5);
         assert_eq!(humanize_balance(U256::from(1_234_567_890u64)), 1.23456789);
         assert_eq!(humanize_balance(U256::zero()), 0.0);
+        assert!(humanize_balance(U256::MAX) > 0.0);
+    }
+
+    // ========== Inventory, Mint and Redeem Tests ==========
+
+    #[test]
+    fn test_inventory_reads_balances_and_top_up() {
+        let (_, mut refs, token_manager, config) = setup_test_env();
+
+        refs.expect_my_wcspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(100)));
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(200)));
+        refs.expect_my_short_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(300)));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        assert_eq!(
+            asset_manager.inventory().unwrap(),
+            Inventory {
+                wcspr: U256::from(100),
+                long: U256::from(200),
+                short: U256::from(300),
+                top_up: U256::from(TOP_UP_AMOUNT),
+            }
+        );
+    }
+
+    #[test]
+    fn test_inventory_plans_with_the_treasury_share() {
+        let (_, mut refs, token_manager, config) = setup_test_env();
+
+        refs.expect_my_wcspr_balance()
+            .times(2)
+            .returning(|| Ok(U256::from(1_000)));
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(200)));
+        refs.expect_my_short_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(300)));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config)
+            .with_treasury_share(2_500)
+            .unwrap();
+        assert_eq!(
+            asset_manager.inventory().unwrap(),
+            Inventory {
+                wcspr: U256::from(250),
+                long: U256::from(200),
+                short: U256::from(300),
+                top_up: U256::from(TOP_UP_AMOUNT / 4),
+            }
+        );
+    }
+
+    #[test]
+    fn test_mint_wraps_cspr_when_wcspr_short() {
+        let (_, mut refs, mut token_manager, config) = setup_test_env();
+        let amount = U256::from(500_000_000_000u64);
+
+        refs.expect_my_wcspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(100)));
+        refs.expect_my_cspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(TOP_UP_AMOUNT * 2)));
+        token_manager
+            .expect_wrap_cspr()
+            .withf(|amount| *amount == U256::from(TOP_UP_AMOUNT))
+            .times(1)
+            .return_once(|_| Ok(()));
+        token_manager
+            .expect_buy_shorts()
+            .withf(move |minted| *minted == amount)
+            .times(1)
+            .return_once(|_| Ok(()));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        assert!(asset_manager.mint(Position::Short, amount).is_ok());
+    }
+
+    #[test]
+    fn test_mint_then_sell_deposits_once() {
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
+        let amount = U256::from(500_000_000_000u64);
+        let minted = U256::from(400_000_000_000u64);
+
+        refs.expect_my_wcspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(TOP_UP_AMOUNT)));
+        token_manager
+            .expect_buy_longs()
+            .withf(move |deposit| *deposit == amount)
+            .times(1)
+            .return_once(|_| Ok(()));
+        token_manager
+            .expect_swap()
+            .withf(move |path, order, _| {
+                *path == Path::LongWcspr
+                    && order.mode == SwapMode::ExactIn
+                    && order.amount_in == minted
+            })
+            .times(1)
+            .return_once(move |_, order, _| Ok(vec![order.amount_in, order.amount_out]));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        assert!(asset_manager.mint(Position::Long, amount).is_ok());
+        // The order's slippage headroom must not trigger a top-up of its own
+        let order = slippage::bound_exact_in(minted, amount, &config.slippage);
+        assert!(asset_manager
+            .swap_held(Path::LongWcspr, order, env.caller())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_redeem_is_capped_by_the_balance() {
+        let (_, mut refs, mut token_manager, config) = setup_test_env();
+        let amount = U256::from(500_000_000_000u64);
+        let short_balance = U256::from(400_000_000_000u64);
+
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(move || Ok(amount + amount));
+        refs.expect_my_short_balance()
+            .times(1)
+            .return_once(move || Ok(short_balance));
+        token_manager
+            .expect_redeem_longs()
+            .withf(move |redeemed| *redeemed == amount)
+            .times(1)
+            .return_once(|_| Ok(()));
+        token_manager
+            .expect_redeem_shorts()
+            .withf(move |redeemed| *redeemed == short_balance)
+            .times(1)
+            .return_once(|_| Ok(()));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        assert_eq!(
+            asset_manager.redeem(Position::Long, amount).unwrap(),
+            amount
+        );
+        assert_eq!(
+            asset_manager.redeem(Position::Short, amount).unwrap(),
+            short_balance
+        );
+    }
+
+    #[test]
+    fn test_swap_deadline_is_ttl_past_the_later_clock() {
+        let ttl = Duration::from_secs(120);
+        let block_time = u64::MAX / 2;
+        assert_eq!(swap_deadline(block_time, ttl), block_time + 120_000);
+
+        let before = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let deadline = swap_deadline(0, ttl);
+        let after = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        assert!((before + 120_000..=after + 120_000).contains(&deadline));
+    }
+
+    #[test]
+    fn test_only_the_router_deadline_revert_expires_a_swap() {
+        let expired = swap_error(
+            BotError::Reverted {
+                call: SWAP_EXACT_IN,
+                code: ROUTER_EXPIRED_CODE,
+            },
+            1_000,
+        );
+        assert!(matches!(
+            expired,
+            BotError::DeadlineExpired { deadline: 1_000 }
+        ));
+
+        // The deadline has long passed, but the router rejected the swap for
+        // another reason, e.g. the output fell below its minimum
+        let reverted = swap_error(
+            BotError::Reverted {
+                call: SWAP_EXACT_OUT,
+                code: 7,
+            },
+            0,
+        );
+        assert!(matches!(reverted, BotError::Reverted { code: 7, .. }));
+        // The same code from another contract is not the router's deadline
+        let reverted = swap_error(
+            BotError::Reverted {
+                call: "transfer_from",
+                code: ROUTER_EXPIRED_CODE,
+            },
+            0,
+        );
+        assert!(matches!(reverted, BotError::Reverted { .. }));
+        let unknown = swap_error(BotError::OutcomeUnknown { call: "swap" }, 0);
+        assert!(matches!(unknown, BotError::OutcomeUnknown { .. }));
     }
 
     // ========== manage_asset_levels Tests ==========
 
     fn make_price_data(long_price: f64, short_price: f64) -> PriceData {
         // wcspr_price in USD; fair prices equal DEX prices (no arb opportunity needed here)
-        PriceData::new(long_price, short_price, 0.04, long_price, short_price)
+        PriceData::from_f64(long_price, short_price, 0.04, long_price, short_price)
+    }
+
+    /// Pools at the given prices, 1_000_000 wCSPR deep on each side.
+    fn make_graph(long_price: f64, short_price: f64) -> RouteGraph {
+        let wcspr = 1_000_000_000_000_000u64;
+        RouteGraph::from(&PoolReserves {
+            long: U256::from((wcspr as f64 / long_price) as u64),
+            wcspr_long: U256::from(wcspr),
+            wcspr_short: U256::from(wcspr),
+            short: U256::from((wcspr as f64 / short_price) as u64),
+        })
     }
 
     #[test]
-    fn test_manage_asset_levels_unwraps_when_cspr_low() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+    fn test_manage_cspr_level_unwraps_when_cspr_low() {
+        let (_, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_cspr_balance()
             .times(1)
@@ -903,21 +1593,49 @@ mod tests {
             .times(1)
             .withf(|&amount| amount == U256::from(UNWRAP_AMOUNT))
             .return_once(|_| Ok(()));
+        refs.expect_my_wcspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(UNWRAP_AMOUNT)));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
-        let price_data = make_price_data(0.75, 0.75);
-        assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
-            .is_ok());
+        // The whole unwrap amount, whatever the market's share: the CSPR
+        // balance pays every market's gas
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config)
+            .with_treasury_share(2_500)
+            .unwrap();
+        assert!(asset_manager.manage_cspr_level().is_ok());
     }
 
     #[test]
-    fn test_manage_asset_levels_sells_longs_when_wcspr_low_and_longs_more_valuable() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+    fn test_unwraps_count_against_the_risk_limits() {
+        let (_, mut refs, mut token_manager, mut config) = setup_test_env();
+        // Room for a single unwrap in the hour
+        config.limits.max_hourly_notional = UNWRAP_AMOUNT;
+        config.limits.state_path = std::env::temp_dir()
+            .join(format!("asset-manager-unwrap-{}.json", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let limits = RiskLimits::open(&config.limits, true).unwrap();
 
         refs.expect_my_cspr_balance()
+            .times(2)
+            .returning(|| Ok(U256::zero()));
+        token_manager
+            .expect_unwrap_wcspr()
             .times(1)
-            .return_once(|| Ok(U256::from(MIN_CSPR_BALANCE)));
+            .return_once(|_| Ok(()));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config).with_limits(&limits);
+        assert!(asset_manager.manage_cspr_level().is_ok());
+        assert!(matches!(
+            asset_manager.manage_cspr_level(),
+            Err(BotError::LimitReached(LimitBreach::HourlyNotional { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_manage_asset_levels_sells_longs_when_wcspr_low_and_longs_more_valuable() {
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
+
         refs.expect_my_wcspr_balance()
             .times(1)
             .return_once(|| Ok(U256::from(MIN_WCSPR_BALANCE - 1)));
@@ -933,25 +1651,23 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .withf(|path, _, amount_out, _| {
-                *path == Path::LongWcspr && *amount_out == U256::from(UNWRAP_AMOUNT)
+            .withf(|path, order, _| {
+                *path == Path::LongWcspr && order.amount_out == U256::from(UNWRAP_AMOUNT)
             })
-            .return_once(|_, _, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let price_data = make_price_data(0.75, 0.75);
+        let graph = make_graph(0.75, 0.75);
         assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
+            .manage_asset_levels(&price_data, &graph, env.caller())
             .is_ok());
     }
 
     #[test]
     fn test_manage_asset_levels_sells_shorts_when_wcspr_low_and_shorts_more_valuable() {
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
-        refs.expect_my_cspr_balance()
-            .times(1)
-            .return_once(|| Ok(U256::from(MIN_CSPR_BALANCE)));
         refs.expect_my_wcspr_balance()
             .times(1)
             .return_once(|| Ok(U256::from(MIN_WCSPR_BALANCE - 1)));
@@ -967,26 +1683,25 @@ mod tests {
         token_manager
             .expect_swap()
             .times(1)
-            .withf(|path, _, amount_out, _| {
-                *path == Path::ShortWcspr && *amount_out == U256::from(UNWRAP_AMOUNT)
+            .withf(|path, order, _| {
+                *path == Path::ShortWcspr && order.amount_out == U256::from(UNWRAP_AMOUNT)
             })
-            .return_once(|_, _, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let price_data = make_price_data(0.75, 0.75);
+        let graph = make_graph(0.75, 0.75);
         assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
+            .manage_asset_levels(&price_data, &graph, env.caller())
             .is_ok());
     }
 
     #[test]
     fn test_manage_asset_levels_uses_price_to_determine_amount_in() {
-        // With long_price = 0.5, to get 1500 wCSPR we need 1500/0.5 * 1.05 = 3150 longs
-        let (env, mut refs, mut token_manager) = setup_test_env();
+        // With long_price = 0.5, to get 1500 wCSPR we need more than 1500/0.5 = 3000
+        // longs: 3013.55 after the pool fee and price impact
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
 
-        refs.expect_my_cspr_balance()
-            .times(1)
-            .return_once(|| Ok(U256::from(MIN_CSPR_BALANCE)));
         refs.expect_my_wcspr_balance()
             .times(1)
             .return_once(|| Ok(U256::from(MIN_WCSPR_BALANCE - 1)));
@@ -997,44 +1712,44 @@ mod tests {
             .times(1)
             .return_once(|| Ok(U256::from(1_000_000_000_000u64)));
 
-        let expected_amount_in = (UNWRAP_AMOUNT as f64 / 0.5f64 * 1.05) as u64;
+        // Plus 5% slippage, rounded up
+        let expected_amount_in = 3_164_224_772_466u64;
         token_manager
             .expect_swap()
             .times(1)
-            .withf(move |path, amount_in, _, _| {
-                *path == Path::LongWcspr && *amount_in == U256::from(expected_amount_in)
+            .withf(move |path, order, _| {
+                *path == Path::LongWcspr && order.amount_in == U256::from(expected_amount_in)
             })
-            .return_once(|_, _, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
+            .return_once(|_, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT)]));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let price_data = make_price_data(0.5, 0.75);
+        let graph = make_graph(0.5, 0.75);
         assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
+            .manage_asset_levels(&price_data, &graph, env.caller())
             .is_ok());
     }
 
     #[test]
     fn test_manage_asset_levels_does_nothing_when_balances_sufficient() {
-        let (env, mut refs, token_manager) = setup_test_env();
+        let (env, mut refs, token_manager, config) = setup_test_env();
 
-        refs.expect_my_cspr_balance()
-            .times(1)
-            .return_once(|| Ok(U256::from(MIN_CSPR_BALANCE)));
         refs.expect_my_wcspr_balance()
             .times(1)
             .return_once(|| Ok(U256::from(MIN_WCSPR_BALANCE)));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
         let price_data = make_price_data(0.75, 0.75);
+        let graph = make_graph(0.75, 0.75);
         assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
+            .manage_asset_levels(&price_data, &graph, env.caller())
             .is_ok());
     }
 
     #[test]
-    fn test_manage_asset_levels_prefers_unwrap_over_sell_when_cspr_critically_low() {
-        // When CSPR is low, should unwrap wCSPR and NOT proceed to check wCSPR level
-        let (env, mut refs, mut token_manager) = setup_test_env();
+    fn test_manage_cspr_level_unwraps_without_selling_positions() {
+        // When CSPR is low, should unwrap wCSPR and NOT check the wCSPR level
+        let (_, mut refs, mut token_manager, config) = setup_test_env();
 
         refs.expect_my_cspr_balance()
             .times(1)
@@ -1045,10 +1760,130 @@ mod tests {
             .times(1)
             .return_once(|_| Ok(()));
 
-        let asset_manager = AssetManager::new(&refs, &token_manager);
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config);
+        assert!(asset_manager.manage_cspr_level().is_ok());
+    }
+
+    #[test]
+    fn test_manage_asset_levels_sells_the_treasury_share() {
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
+
+        // A quarter of the balance is below a quarter of the minimum
+        refs.expect_my_wcspr_balance()
+            .times(2)
+            .returning(|| Ok(U256::from(MIN_WCSPR_BALANCE - 1)));
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(5_000_000_000_000u64)));
+        refs.expect_my_short_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(1_000_000_000_000u64)));
+
+        token_manager
+            .expect_swap()
+            .times(1)
+            .withf(|path, order, _| {
+                *path == Path::LongWcspr && order.amount_out == U256::from(UNWRAP_AMOUNT / 4)
+            })
+            .return_once(|_, _, _| Ok(vec![U256::from(UNWRAP_AMOUNT / 4)]));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config)
+            .with_treasury_share(2_500)
+            .unwrap();
         let price_data = make_price_data(0.75, 0.75);
+        let graph = make_graph(0.75, 0.75);
         assert!(asset_manager
-            .manage_asset_levels(&price_data, env.caller())
+            .manage_asset_levels(&price_data, &graph, env.caller())
             .is_ok());
     }
+
+    #[test]
+    fn test_top_ups_use_the_treasury_share() {
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
+        let top_up = U256::from(TOP_UP_AMOUNT / 4);
+
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(50)));
+        // A quarter of it falls short of a quarter of a top-up
+        refs.expect_my_wcspr_balance()
+            .times(2)
+            .returning(|| Ok(U256::from(TOP_UP_AMOUNT / 2)));
+        refs.expect_my_cspr_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(TOP_UP_AMOUNT / 4)));
+        refs.expect_my_long_balance()
+            .times(1)
+            .return_once(|| Ok(U256::from(TOP_UP_AMOUNT / 4 + 50)));
+
+        token_manager
+            .expect_wrap_cspr()
+            .withf(move |amount| *amount == top_up)
+            .times(1)
+            .return_once(|_| Ok(()));
+        token_manager
+            .expect_buy_longs()
+            .withf(move |amount| *amount == top_up)
+            .times(1)
+            .return_once(|_| Ok(()));
+        token_manager
+            .expect_swap()
+            .times(1)
+            .return_once(|_, _, _| Ok(vec![U256::from(100)]));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config)
+            .with_treasury_share(2_500)
+            .unwrap();
+        let result = asset_manager.swap(
+            Path::LongWcspr,
+            exact_out(U256::from(100), U256::from(90)),
+            env.caller(),
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_the_treasury_share_is_a_budget_of_its_own() {
+        let (env, mut refs, mut token_manager, config) = setup_test_env();
+
+        // Other markets leave the shared balance where it was
+        refs.expect_my_wcspr_balance()
+            .returning(|| Ok(U256::from(1_000)));
+        refs.expect_my_long_balance()
+            .returning(|| Ok(U256::from(200)));
+        refs.expect_my_short_balance()
+            .returning(|| Ok(U256::from(300)));
+        token_manager
+            .expect_swap()
+            .withf(|path, _, _| *path == Path::WcsprLong)
+            .times(1)
+            .return_once(|_, _, _| Ok(vec![U256::from(200), U256::from(150)]));
+        token_manager
+            .expect_swap()
+            .withf(|path, _, _| *path == Path::LongWcspr)
+            .times(1)
+            .return_once(|_, _, _| Ok(vec![U256::from(100), U256::from(50)]));
+
+        let asset_manager = AssetManager::new(&refs, &token_manager, &config)
+            .with_treasury_share(5_000)
+            .unwrap();
+        asset_manager
+            .swap_held(
+                Path::WcsprLong,
+                exact_out(U256::from(200), U256::from(150)),
+                env.caller(),
+            )
+            .unwrap();
+        asset_manager
+            .swap_held(
+                Path::LongWcspr,
+                exact_out(U256::from(100), U256::from(50)),
+                env.caller(),
+            )
+            .unwrap();
+
+        // Half of 1_000, less the 200 spent, plus the 50 earned back
+        assert_eq!(asset_manager.inventory().unwrap().wcspr, U256::from(350));
+    }
 }
@@ -0,0 +1,356 @@
+use chrono::{DateTime, Utc};
+use odra::casper_types::U256;
+use odra::host::HostEnv;
+use odra::schema::casper_contract_schema::NamedCLType;
+use odra_cli::{
+    scenario::{Args, Error, Scenario, ScenarioMetadata},
+    DeployedContractsContainer,
+};
+use serde::Serialize;
+
+use crate::bot::{
+    config::{BotConfig, StrategyConfig},
+    graph::RouteGraph,
+    path::Path,
+    pnl::ReportFormat,
+    price::{motes_to_cspr, Gain},
+    serde_u256, sizing,
+    snapshot::{read_snapshots, MarketSnapshot},
+    strategy::{Action, DeviationStrategy, Inventory, Strategy},
+    utils::PriceCalculator,
+};
+
+/// A simulated swap: decided on one snapshot, executed on a later one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BacktestTrade {
+    pub decided_at: DateTime<Utc>,
+    pub executed_at: DateTime<Utc>,
+    pub path: Path,
+    #[serde(with = "serde_u256::decimal")]
+    pub amount_in: U256,
+    #[serde(with = "serde_u256::decimal")]
+    pub amount_out: U256,
+    pub expected_gain: Gain,
+    pub gain: Gain,
+    /// Cumulative gain after this trade.
+    pub equity: Gain,
+}
+
+/// Outcome of replaying a snapshot series.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BacktestResult {
+    pub snapshots: usize,
+    /// Snapshots on which `Path::candidates` found a mispricing.
+    pub opportunities: usize,
+    pub trades: Vec<BacktestTrade>,
+    pub pnl: Gain,
+    /// Largest drop of equity from a previous peak, in motes.
+    #[serde(with = "serde_u256::decimal")]
+    pub max_drawdown: U256,
+}
+
+impl BacktestResult {
+    pub fn wins(&self) -> usize {
+        self.trades
+            .iter()
+            .filter(|trade| trade.gain > Gain::zero())
+            .count()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String, Error> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text()),
+            ReportFormat::Csv => Ok(self.render_csv()),
+            ReportFormat::Json => {
+                serde_json::to_string_pretty(self).map_err(|e| Error::OdraError {
+                    message: format!("Failed to render backtest: {}", e),
+                })
+            }
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let win_rate = if self.trades.is_empty() {
+            0.0
+        } else {
+            self.wins() as f64 / self.trades.len() as f64 * 100.0
+        };
+        [
+            format!(
+                "Snapshots: {}  Opportunities: {}  Trades: {}  Win rate: {:.2}%",
+                self.snapshots,
+                self.opportunities,
+                self.trades.len(),
+                win_rate
+            ),
+            format!("PnL: {:.4} CSPR", self.pnl.to_cspr()),
+            format!("Max drawdown: {:.4} CSPR", motes_to_cspr(self.max_drawdown)),
+        ]
+        .join("\n")
+    }
+
+    /// One row per simulated trade.
+    fn render_csv(&self) -> String {
+        let mut lines = vec![
+            "decided_at,executed_at,path,amount_in,amount_out,expected_gain_cspr,gain_cspr,equity_cspr"
+                .to_string(),
+        ];
+        for trade in &self.trades {
+            lines.push(format!(
+                "{},{},{:?},{},{},{:.9},{:.9},{:.9}",
+                trade.decided_at.to_rfc3339(),
+                trade.executed_at.to_rfc3339(),
+                trade.path,
+                trade.amount_in,
+                trade.amount_out,
+                trade.expected_gain.to_cspr(),
+                trade.gain.to_cspr(),
+                trade.equity.to_cspr()
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Replays `snapshots` through the live decision code: the deviation strategy
+/// and the gain check. A trade decided on snapshot `i` is executed `latency`
+/// snapshots later, against those reserves and valued at those fair prices, to
+/// model the delay until our transaction lands.
+///
+/// Inventory is assumed to be available up to `max_notional`, and our own
+/// trades are not carried over into later snapshots (the recorded reserves
+/// already reflect what the market did).
+pub fn run(
+    snapshots: &[MarketSnapshot],
+    strategy: &StrategyConfig,
+    max_notional: u64,
+    latency: usize,
+) -> BacktestResult {
+    let mut result = BacktestResult {
+        snapshots: snapshots.len(),
+        opportunities: 0,
+        trades: vec![],
+        pnl: Gain::zero(),
+        max_drawdown: U256::zero(),
+    };
+    let mut peak = Gain::zero();
+    let deviation = DeviationStrategy::new(strategy, max_notional);
+    // Only the max notional caps the trade size
+    let inventory = Inventory {
+        wcspr: U256::MAX,
+        long: U256::MAX,
+        short: U256::MAX,
+        top_up: U256::zero(),
+    };
+
+    for (index, decided) in snapshots.iter().enumerate() {
+        let price_data = PriceCalculator::price_data(&decided.reserves, &decided.market);
+        let graph = RouteGraph::from(&decided.reserves);
+        if Path::candidates(&graph, &price_data, strategy).is_empty() {
+            continue;
+        }
+        result.opportunities += 1;
+
+        for plan in deviation.propose(&price_data, &graph, &inventory) {
+            // Deviation plans are single swaps
+            let [Action::Swap { path, amount_in }] = plan.actions[..] else {
+                continue;
+            };
+            let expected_gain = plan.expected_gain;
+            if expected_gain < strategy.min_gain() {
+                continue;
+            }
+            let Some(executed) = snapshots.get(index + latency) else {
+                continue;
+            };
+
+            let executed_price_data =
+                PriceCalculator::price_data(&executed.reserves, &executed.market);
+            let executed_graph = RouteGraph::from(&executed.reserves);
+            let amount_out = sizing::quote(path, amount_in, &executed_graph, strategy.pair_fee_bps);
+            let gain = PriceCalculator::calc_gains_in_cspr(
+                amount_in,
+                amount_out,
+                &executed_price_data,
+                path,
+                strategy,
+            );
+            result.pnl = result.pnl + gain;
+            peak = peak.max(result.pnl);
+            result.max_drawdown = result.max_drawdown.max((peak - result.pnl).magnitude());
+            result.trades.push(BacktestTrade {
+                decided_at: decided.timestamp,
+                executed_at: executed.timestamp,
+                path,
+                amount_in,
+                amount_out,
+                expected_gain,
+                gain,
+                equity: result.pnl,
+            });
+        }
+    }
+    result
+}
+
+pub struct Backtest;
+
+impl ScenarioMetadata for Backtest {
+    const NAME: &'static str = "Backtest";
+    const DESCRIPTION: &'static str =
+        "Replays recorded pool and market snapshots through the strategy and reports PnL.";
+}
+
+impl Scenario for Backtest {
+    fn args(&self) -> Vec<odra_cli::CommandArg> {
+        vec![
+            odra_cli::CommandArg::new(
+                "fixture",
+                "Snapshot file to replay (CSV, or JSONL if named *.jsonl).",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "config",
+                "Path to the bot config TOML file whose strategy is tested.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "latency",
+                "Snapshots between deciding and executing a trade. Defaults to 1.",
+                NamedCLType::U32,
+            ),
+            odra_cli::CommandArg::new(
+                "format",
+                "Output format: text, csv (one row per trade) or json. Defaults to text.",
+                NamedCLType::String,
+            ),
+        ]
+    }
+
+    fn run(
+        &self,
+        _env: &HostEnv,
+        _container: &DeployedContractsContainer,
+        args: Args,
+    ) -> Result<(), Error> {
+        let fixture: String = args.get_single("fixture")?;
+        let config_path: Option<String> = args.get_single("config").ok();
+        let config = BotConfig::load(config_path.as_deref())?;
+        let latency = args.get_single::<u32>("latency").unwrap_or(1);
+        let format: ReportFormat = args
+            .get_single::<String>("format")
+            .map(|format| format.parse())
+            .unwrap_or(Ok(ReportFormat::Text))?;
+
+        let snapshots = read_snapshots(&fixture)?;
+        tracing::info!("Replaying {} snapshots from {}", snapshots.len(), fixture);
+        let result = run(
+            &snapshots,
+            &config.strategy,
+            config.limits.max_trade_notional,
+            latency as usize,
+        );
+        println!("{}", result.render(format)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::data::{MarketReading, PoolReserves};
+    use crate::bot::price::MOTES_PER_CSPR;
+
+    use super::*;
+
+    const MAX_NOTIONAL: u64 = 10_000 * MOTES_PER_CSPR;
+
+    fn cspr(amount: u64) -> U256 {
+        U256::from(amount) * U256::from(MOTES_PER_CSPR)
+    }
+
+    /// Fair LONG and SHORT prices of 1 CSPR, with LONG at `percent` of fair on the DEX.
+    fn snapshot(second: u32, percent: u64) -> MarketSnapshot {
+        let long = cspr(1_000_000);
+        MarketSnapshot {
+            timestamp: DateTime::parse_from_rfc3339(&format!("2026-03-01T00:00:{:02}Z", second))
+                .unwrap()
+                .with_timezone(&Utc),
+            reserves: PoolReserves {
+                long,
+                wcspr_long: long * percent / 100,
+                wcspr_short: cspr(1_000_000),
+                short: cspr(1_000_000),
+            },
+            market: MarketReading {
+                long_liquidity: cspr(1_000),
+                long_total_supply: cspr(1_000),
+                short_liquidity: cspr(1_000),
+                short_total_supply: cspr(1_000),
+                price: U256::from(2_000u64),
+            },
+            oracle_price: None,
+        }
+    }
+
+    #[test]
+    fn test_no_trades_without_mispricing() {
+        let snapshots = vec![snapshot(0, 100), snapshot(1, 101)];
+        let result = run(&snapshots, &StrategyConfig::default(), MAX_NOTIONAL, 0);
+        assert_eq!(result.opportunities, 0);
+        assert!(result.trades.is_empty());
+        assert_eq!(result.pnl, Gain::zero());
+    }
+
+    #[test]
+    fn test_same_snapshot_execution_matches_expected_gain() {
+        let snapshots = vec![snapshot(0, 110), snapshot(1, 100)];
+        let result = run(&snapshots, &StrategyConfig::default(), MAX_NOTIONAL, 0);
+        assert_eq!(result.opportunities, 1);
+        assert_eq!(result.trades.len(), 1);
+        let trade = &result.trades[0];
+        assert_eq!(trade.path, Path::LongWcspr);
+        assert_eq!(trade.gain, trade.expected_gain);
+        assert!(trade.gain > StrategyConfig::default().min_gain());
+        assert_eq!(result.pnl, trade.gain);
+        assert!(result.max_drawdown.is_zero());
+    }
+
+    #[test]
+    fn test_latency_executes_on_later_snapshot_and_tracks_drawdown() {
+        // Mispriced at 0 and 2, but the pool is back at fair value when we land.
+        let snapshots = vec![
+            snapshot(0, 110),
+            snapshot(1, 100),
+            snapshot(2, 110),
+            snapshot(3, 100),
+        ];
+        let result = run(&snapshots, &StrategyConfig::default(), MAX_NOTIONAL, 1);
+        assert_eq!(result.opportunities, 2);
+        assert_eq!(result.trades.len(), 2);
+        assert!(result.trades.iter().all(|trade| trade.gain.is_negative()));
+        assert_eq!(result.trades[0].executed_at, snapshots[1].timestamp);
+        assert_eq!(result.max_drawdown, result.pnl.magnitude());
+        assert_eq!(result.wins(), 0);
+
+        // The last opportunity has no later snapshot to execute on.
+        let result = run(&snapshots[..3], &StrategyConfig::default(), MAX_NOTIONAL, 1);
+        assert_eq!(result.opportunities, 2);
+        assert_eq!(result.trades.len(), 1);
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let snapshots = vec![snapshot(0, 110)];
+        let result = run(&snapshots, &StrategyConfig::default(), MAX_NOTIONAL, 0);
+        let csv = result.render(ReportFormat::Csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(
+            lines[1].starts_with("2026-03-01T00:00:00+00:00,2026-03-01T00:00:00+00:00,LongWcspr,")
+        );
+        assert!(result
+            .render(ReportFormat::Text)
+            .unwrap()
+            .contains("Trades: 1"));
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display};
+use std::time::Instant;
+
+use crate::bot::{config::BreakerConfig, price::Gain};
+
+/// Why the circuit breaker tripped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TripReason {
+    ConsecutiveFailures(u32),
+    RealisedLoss(Gain),
+    ErrorRate(f64),
+}
+
+impl Display for TripReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TripReason::ConsecutiveFailures(count) => {
+                write!(f, "{} failed or losing trades in a row", count)
+            }
+            TripReason::RealisedLoss(gain) => {
+                write!(
+                    f,
+                    "realised {:.4} CSPR within the loss window",
+                    gain.to_cspr()
+                )
+            }
+            TripReason::ErrorRate(rate) => {
+                write!(
+                    f,
+                    "{:.0}% of checks failed within the error window",
+                    rate * 100.0
+                )
+            }
+        }
+    }
+}
+
+/// Suspends trading after repeated failures, losses or errors. While tripped
+/// the bot only monitors prices, until the cool-down elapses or the operator
+/// creates the reset file.
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    consecutive_failures: u32,
+    /// Realised gains of executed trades, oldest first.
+    gains: VecDeque<(Instant, Gain)>,
+    /// Whether each check failed, oldest first.
+    checks: VecDeque<(Instant, bool)>,
+    tripped: Option<(Instant, TripReason)>,
+    trips: u64,
+}
+
+impl CircuitBreaker {
+    /// A closed breaker. A reset file left over from an earlier run is removed,
+    /// so it can't close a future trip by accident.
+    pub fn new(config: &BreakerConfig) -> Self {
+        let _ = std::fs::remove_file(&config.reset_file);
+        Self {
+            config: config.clone(),
+            consecutive_failures: 0,
+            gains: VecDeque::new(),
+            checks: VecDeque::new(),
+            tripped: None,
+            trips: 0,
+        }
+    }
+
+    /// Records an executed trade and what it actually gained.
+    pub fn record_trade(&mut self, now: Instant, gain: Gain) {
+        if gain.is_negative() {
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+        self.gains.push_back((now, gain));
+        self.evaluate(now);
+    }
+
+    /// Records a trade that failed to execute.
+    pub fn record_failed_trade(&mut self, now: Instant) {
+        self.consecutive_failures += 1;
+        self.evaluate(now);
+    }
+
+    /// Records whether a check failed, for the error rate.
+    pub fn record_check(&mut self, now: Instant, failed: bool) {
+        self.checks.push_back((now, failed));
+        self.evaluate(now);
+    }
+
+    /// Whether trading is suspended. Closes the breaker once its cool-down has
+    /// elapsed or the reset file was created.
+    pub fn is_open(&mut self, now: Instant) -> bool {
+        let Some((since, _)) = self.tripped else {
+            return false;
+        };
+        if std::fs::remove_file(&self.config.reset_file).is_ok() {
+            tracing::warn!("Circuit breaker reset by operator, resuming trading");
+            self.close();
+            return false;
+        }
+        if now.duration_since(since) >= self.config.cooldown() {
+            tracing::info!("Circuit breaker cool-down elapsed, resuming trading");
+            self.close();
+            return false;
+        }
+        true
+    }
+
+    /// How many times the breaker tripped since the bot started.
+    pub fn trips(&self) -> u64 {
+        self.trips
+    }
+
+    fn evaluate(&mut self, now: Instant) {
+        self.prune(now);
+        if self.tripped.is_some() {
+            return;
+        }
+        if let Some(reason) = self.trip_reason() {
+            tracing::error!(
+                "Circuit breaker tripped: {}. Monitoring only for {:?}, or until {} is created",
+                reason,
+                self.config.cooldown(),
+                self.config.reset_file
+            );
+            self.tripped = Some((now, reason));
+            self.trips += 1;
+        }
+    }
+
+    fn trip_reason(&self) -> Option<TripReason> {
+        if self.consecutive_failures >= self.config.max_consecutive_failures {
+            return Some(TripReason::ConsecutiveFailures(self.consecutive_failures));
+        }
+
+        let net = self
+            .gains
+            .iter()
+            .fold(Gain::zero(), |net, (_, gain)| net + *gain);
+        if net <= -self.config.max_loss() {
+            return Some(TripReason::RealisedLoss(net));
+        }
+
+        let checks = self.checks.len();
+        if checks > 0 && checks >= self.config.min_checks as usize {
+            let failed = self.checks.iter().filter(|(_, failed)| *failed).count();
+            let rate = failed as f64 / checks as f64;
+            if rate >= self.config.max_error_rate {
+                return Some(TripReason::ErrorRate(rate));
+            }
+        }
+        None
+    }
+
+    /// Drops gains and checks that fell out of their rolling windows.
+    fn prune(&mut self, now: Instant) {
+        let loss_window = self.config.loss_window();
+        while let Some((at, _)) = self.gains.front() {
+            if now.duration_since(*at) <= loss_window {
+                break;
+            }
+            self.gains.pop_front();
+        }
+        let error_window = self.config.error_window();
+        while let Some((at, _)) = self.checks.front() {
+            if now.duration_since(*at) <= error_window {
+                break;
+            }
+            self.checks.pop_front();
+        }
+    }
+
+    /// Starts over, so the losses and errors that tripped the breaker can't
+    /// trip it again straight away.
+    fn close(&mut self) {
+        self.tripped = None;
+        self.consecutive_failures = 0;
+        self.gains.clear();
+        self.checks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn config(name: &str) -> BreakerConfig {
+        let reset_file =
+            std::env::temp_dir().join(format!("breaker-{}-{}.reset", std::process::id(), name));
+        BreakerConfig {
+            max_consecutive_failures: 3,
+            max_loss_cspr: 10.0,
+            loss_window_secs: 60,
+            max_error_rate: 0.5,
+            error_window_secs: 60,
+            min_checks: 4,
+            cooldown_secs: 300,
+            reset_file: reset_file.to_string_lossy().to_string(),
+        }
+    }
+
+    fn secs(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn test_trips_after_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new(&config("consecutive"));
+        let start = Instant::now();
+        breaker.record_failed_trade(start);
+        breaker.record_trade(start, Gain::from_cspr(-1.0));
+        breaker.record_trade(start, Gain::from_cspr(2.0));
+        breaker.record_failed_trade(start);
+        breaker.record_failed_trade(start);
+        assert!(!breaker.is_open(start));
+
+        breaker.record_trade(start, Gain::from_cspr(-1.0));
+        assert!(breaker.is_open(start));
+        assert_eq!(
+            breaker.tripped.map(|(_, reason)| reason),
+            Some(TripReason::ConsecutiveFailures(3))
+        );
+        assert_eq!(breaker.trips(), 1);
+    }
+
+    #[test]
+    fn test_trips_on_realised_loss_within_window() {
+        let mut breaker = CircuitBreaker::new(&config("loss"));
+        let start = Instant::now();
+        breaker.record_trade(start, Gain::from_cspr(-6.0));
+        breaker.record_trade(secs(start, 10), Gain::from_cspr(1.0));
+        // The first loss has left the window by now
+        breaker.record_trade(secs(start, 70), Gain::from_cspr(-6.0));
+        assert!(!breaker.is_open(secs(start, 70)));
+
+        breaker.record_trade(secs(start, 80), Gain::from_cspr(-5.0));
+        assert!(breaker.is_open(secs(start, 80)));
+        assert_eq!(
+            breaker.tripped.map(|(_, reason)| reason),
+            Some(TripReason::RealisedLoss(Gain::from_cspr(-11.0)))
+        );
+    }
+
+    #[test]
+    fn test_error_rate_needs_min_checks() {
+        let mut breaker = CircuitBreaker::new(&config("errors"));
+        let start = Instant::now();
+        breaker.record_check(start, true);
+        breaker.record_check(start, true);
+        breaker.record_check(start, true);
+        assert!(!breaker.is_open(start));
+
+        breaker.record_check(start, false);
+        assert!(breaker.is_open(start));
+        assert_eq!(
+            breaker.tripped.map(|(_, reason)| reason),
+            Some(TripReason::ErrorRate(0.75))
+        );
+    }
+
+    #[test]
+    fn test_closes_after_cooldown() {
+        let mut breaker = CircuitBreaker::new(&config("cooldown"));
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failed_trade(start);
+        }
+        assert!(breaker.is_open(secs(start, 299)));
+        assert!(!breaker.is_open(secs(start, 300)));
+
+        // Failures from before the trip don't count towards the next one
+        breaker.record_failed_trade(secs(start, 301));
+        assert!(!breaker.is_open(secs(start, 301)));
+    }
+
+    #[test]
+    fn test_operator_reset_closes_breaker() {
+        let config = config("reset");
+        std::fs::write(&config.reset_file, "").unwrap();
+        let mut breaker = CircuitBreaker::new(&config);
+        // A leftover reset file does not survive the start
+        assert!(!std::path::Path::new(&config.reset_file).exists());
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failed_trade(start);
+        }
+        assert!(breaker.is_open(start));
+        std::fs::write(&config.reset_file, "").unwrap();
+        assert!(!breaker.is_open(secs(start, 1)));
+        assert!(!std::path::Path::new(&config.reset_file).exists());
+    }
+}
@@ -0,0 +1,1233 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+use serde::Deserialize;
+
+use crate::{
+    bot::{
+        path::{Path, MAX_HOPS},
+        price::{cspr_to_motes, Gain},
+        slippage::SwapMode,
+    },
+    contracts::MarketNames,
+    CD_LONG_ID, CD_SHORT_ID, MARKET_ID,
+};
+
+/// Runtime configuration of the bot, loaded from a TOML file.
+/// Every field has a default, so the file only needs to list overrides.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BotConfig {
+    pub tick: TickConfig,
+    pub strategy: StrategyConfig,
+    pub slippage: SlippageConfig,
+    pub deadline: DeadlineConfig,
+    pub inventory: InventoryConfig,
+    pub gas: GasConfig,
+    pub events: EventsConfig,
+    pub journal: JournalConfig,
+    pub recorder: RecorderConfig,
+    pub errors: ErrorsConfig,
+    pub retry: RetryConfig,
+    pub breaker: BreakerConfig,
+    pub limits: LimitsConfig,
+    pub oracle: OracleConfig,
+    pub pools: PoolsConfig,
+    /// Markets traded in this process. Empty means the default market only.
+    pub markets: Vec<MarketConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TickConfig {
+    /// Seconds between two price checks.
+    pub interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyConfig {
+    /// Minimum deviation from fair price (in percent) to consider a path.
+    pub diff_threshold: f64,
+    /// Minimum net gain (in CSPR) required to execute a swap.
+    pub min_gain_cspr: f64,
+    /// Average cost (in CSPR) of a single-hop swap transaction.
+    pub single_hop_tx_cost_cspr: f64,
+    /// Average cost (in CSPR) of a multi-hop swap transaction.
+    pub multi_hop_tx_cost_cspr: f64,
+    /// Average cost (in CSPR) of a market deposit or withdrawal transaction.
+    pub market_tx_cost_cspr: f64,
+    /// Swap fee charged by casper-trade pairs, in basis points.
+    pub pair_fee_bps: u64,
+    /// Fee the market keeps from deposits and withdrawals, in basis points.
+    pub market_fee_bps: u64,
+    /// Most hops of a route the strategies consider, up to `MAX_HOPS`.
+    pub max_hops: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlippageConfig {
+    /// How far a swap may move against its quote, in basis points.
+    /// Applies to arbitrage and inventory swaps alike.
+    pub tolerance_bps: u64,
+    /// `exact_out` fixes the amount received and caps the input;
+    /// `exact_in` fixes the input and requires a minimum output.
+    pub mode: SwapMode,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadlineConfig {
+    /// How long a swap stays valid after it is sent, in seconds.
+    pub ttl_secs: u64,
+    /// How many times an opportunity is re-quoted after its swap expired.
+    pub max_requotes: u32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InventoryConfig {
+    /// Amount wrapped or deposited into the market on top-ups, in motes.
+    pub top_up_amount: u64,
+    /// CSPR balance below which wCSPR is unwrapped, in motes.
+    pub min_cspr_balance: u64,
+    /// wCSPR balance below which positions are sold, in motes.
+    pub min_wcspr_balance: u64,
+    /// Amount unwrapped (or bought back) when a balance is low, in motes.
+    pub unwrap_amount: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GasConfig {
+    /// Gas for approvals, wrapping and market deposits, in motes.
+    pub default_call: u64,
+    /// Gas for a single-hop router swap, in motes.
+    pub single_hop_swap: u64,
+    /// Gas for a multi-hop router swap, in motes.
+    pub multi_hop_swap: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventsConfig {
+    /// Node SSE endpoint, e.g. `http://node:9999/events`. Timer-only if unset.
+    pub sse_url: Option<String>,
+    /// First reconnect delay after the stream drops, in seconds.
+    pub reconnect_initial_secs: u64,
+    /// Upper bound on the reconnect delay, in seconds.
+    pub reconnect_max_secs: u64,
+    /// Window in which bursts of events are coalesced into one check, in milliseconds.
+    pub debounce_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JournalConfig {
+    /// JSONL file every evaluated opportunity and swap is appended to.
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    /// Directory the market data recorder writes its CSV files to.
+    pub dir: String,
+    /// Seconds between two recorded snapshots.
+    pub interval_secs: u64,
+    /// Size after which a new file is started, in bytes. Files also rotate daily.
+    pub max_file_bytes: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorsConfig {
+    /// First pause after a transport failure or missing funds, in seconds.
+    pub backoff_initial_secs: u64,
+    /// Upper bound on that pause, in seconds.
+    pub backoff_max_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Attempts per node read or write, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, in milliseconds. Doubles on every further retry.
+    pub initial_delay_ms: u64,
+    /// Upper bound on the delay between two attempts, in milliseconds.
+    pub max_delay_ms: u64,
+    /// Random spread applied to every delay, as a fraction of it (0.0 to 1.0).
+    pub jitter: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BreakerConfig {
+    /// Failed or losing trades in a row that trip the breaker.
+    pub max_consecutive_failures: u32,
+    /// Net realised loss (in CSPR) within the loss window that trips the breaker.
+    pub max_loss_cspr: f64,
+    /// Rolling window the realised loss is summed over, in seconds.
+    pub loss_window_secs: u64,
+    /// Share of failed checks (0.0 to 1.0) within the error window that trips the breaker.
+    pub max_error_rate: f64,
+    /// Rolling window the error rate is measured over, in seconds.
+    pub error_window_secs: u64,
+    /// Checks needed within the error window before the error rate counts.
+    pub min_checks: u32,
+    /// How long a tripped breaker keeps the bot monitoring only, in seconds.
+    pub cooldown_secs: u64,
+    /// Creating this file closes a tripped breaker straight away.
+    pub reset_file: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// Largest fair value of a single trade, in motes. Strategies size their
+    /// trades up to it.
+    pub max_trade_notional: u64,
+    /// Largest value traded or deposited into the market within the last hour, in motes.
+    pub max_hourly_notional: u64,
+    /// Largest value traded or deposited into the market within the last day, in motes.
+    pub max_daily_notional: u64,
+    /// Largest net realised loss within the last day, in motes.
+    pub max_daily_loss: u64,
+    /// File the last day of trades and deposits is kept in, so restarts keep the budget.
+    pub state_path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OracleConfig {
+    /// Styks price feed the market's CSPR/USD price is checked against, and
+    /// whose TWAP the recorder writes next to the market price.
+    pub price_feed_id: String,
+    /// Largest difference between the market price and the feed's TWAP, in basis points.
+    pub max_deviation_bps: u64,
+    /// Oldest feed update the bot still trades on, in seconds.
+    pub max_staleness_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolsConfig {
+    /// Smallest reserve of either token in a casper-trade pool the bot trades against.
+    pub min_reserve: u64,
+    /// Oldest last update of a pool the bot still trades against, in seconds.
+    pub max_pair_age_secs: u64,
+    /// Largest move of a pool price since the previous check, beyond the move
+    /// of the market's fair price, in basis points.
+    pub max_jump_bps: u64,
+}
+
+/// A Casper Delta market, found in the contracts file by the names of its
+/// contracts. It trades through every pair of the contracts file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketConfig {
+    /// Name of the market in logs and in its journal and limits files, made of
+    /// letters, digits, `_` and `-`.
+    pub name: String,
+    pub market: String,
+    pub long: String,
+    pub short: String,
+    /// Share of the wCSPR treasury the market trades with, in basis points.
+    pub treasury_bps: u64,
+    /// Risk limits of the market alone. `[limits]` if omitted.
+    pub limits: Option<LimitsConfig>,
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        Self { interval_secs: 180 }
+    }
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        Self {
+            diff_threshold: 2.5,
+            min_gain_cspr: 1.0,
+            single_hop_tx_cost_cspr: 7.0,
+            multi_hop_tx_cost_cspr: 12.5,
+            market_tx_cost_cspr: 3.5,
+            pair_fee_bps: 30,
+            market_fee_bps: 0,
+            max_hops: 2,
+        }
+    }
+}
+
+impl Default for SlippageConfig {
+    fn default() -> Self {
+        Self {
+            tolerance_bps: 100,
+            mode: SwapMode::ExactOut,
+        }
+    }
+}
+
+impl Default for DeadlineConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 120,
+            max_requotes: 1,
+        }
+    }
+}
+
+impl Default for InventoryConfig {
+    fn default() -> Self {
+        Self {
+            top_up_amount: 2_000_000_000_000,     // 2_000 CSPR
+            min_cspr_balance: 100_000_000_000,    // 100 CSPR
+            min_wcspr_balance: 1_500_000_000_000, // 1_500 CSPR
+            unwrap_amount: 1_500_000_000_000,     // 1_500 CSPR
+        }
+    }
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            default_call: 4_000_000_000,    // 4 CSPR
+            single_hop_swap: 8_000_000_000, // 8 CSPR
+            multi_hop_swap: 13_000_000_000, // 13 CSPR
+        }
+    }
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            sse_url: None,
+            reconnect_initial_secs: 1,
+            reconnect_max_secs: 60,
+            debounce_ms: 500,
+        }
+    }
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            path: "trade-journal.jsonl".to_string(),
+        }
+    }
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            dir: "market-data".to_string(),
+            interval_secs: 30,
+            max_file_bytes: 10_000_000, // 10 MB
+        }
+    }
+}
+
+impl Default for ErrorsConfig {
+    fn default() -> Self {
+        Self {
+            backoff_initial_secs: 10,
+            backoff_max_secs: 600,
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 250,
+            max_delay_ms: 4_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: 3,
+            max_loss_cspr: 50.0,
+            loss_window_secs: 3_600,
+            max_error_rate: 0.5,
+            error_window_secs: 1_800,
+            min_checks: 6,
+            cooldown_secs: 3_600,
+            reset_file: "breaker.reset".to_string(),
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_trade_notional: 10_000_000_000_000,  // 10_000 CSPR
+            max_hourly_notional: 50_000_000_000_000, // 50_000 CSPR
+            max_daily_notional: 250_000_000_000_000, // 250_000 CSPR
+            max_daily_loss: 200_000_000_000,         // 200 CSPR
+            state_path: "risk-limits.json".to_string(),
+        }
+    }
+}
+
+impl Default for MarketConfig {
+    fn default() -> Self {
+        Self {
+            name: "main".to_string(),
+            market: MARKET_ID.to_string(),
+            long: CD_LONG_ID.to_string(),
+            short: CD_SHORT_ID.to_string(),
+            treasury_bps: 10_000,
+            limits: None,
+        }
+    }
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        Self {
+            price_feed_id: "CSPRUSD".to_string(),
+            max_deviation_bps: 200,
+            max_staleness_secs: 900,
+        }
+    }
+}
+
+impl Default for PoolsConfig {
+    fn default() -> Self {
+        Self {
+            min_reserve: 100_000_000_000, // 100 tokens
+            max_pair_age_secs: 86_400,
+            max_jump_bps: 1_000,
+        }
+    }
+}
+
+impl TickConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+impl EventsConfig {
+    pub fn reconnect_initial(&self) -> Duration {
+        Duration::from_secs(self.reconnect_initial_secs)
+    }
+
+    pub fn reconnect_max(&self) -> Duration {
+        Duration::from_secs(self.reconnect_max_secs)
+    }
+
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce_ms)
+    }
+}
+
+impl DeadlineConfig {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+impl RecorderConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+impl ErrorsConfig {
+    pub fn backoff_initial(&self) -> Duration {
+        Duration::from_secs(self.backoff_initial_secs)
+    }
+
+    pub fn backoff_max(&self) -> Duration {
+        Duration::from_secs(self.backoff_max_secs)
+    }
+}
+
+impl RetryConfig {
+    pub fn initial_delay(&self) -> Duration {
+        Duration::from_millis(self.initial_delay_ms)
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_ms)
+    }
+}
+
+impl BreakerConfig {
+    pub fn max_loss(&self) -> Gain {
+        Gain::from_cspr(self.max_loss_cspr)
+    }
+
+    pub fn loss_window(&self) -> Duration {
+        Duration::from_secs(self.loss_window_secs)
+    }
+
+    pub fn error_window(&self) -> Duration {
+        Duration::from_secs(self.error_window_secs)
+    }
+
+    pub fn cooldown(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
+}
+
+impl OracleConfig {
+    pub fn max_staleness(&self) -> Duration {
+        Duration::from_secs(self.max_staleness_secs)
+    }
+}
+
+impl PoolsConfig {
+    pub fn max_pair_age(&self) -> Duration {
+        Duration::from_secs(self.max_pair_age_secs)
+    }
+}
+
+impl StrategyConfig {
+    /// The deviation threshold in basis points.
+    pub fn diff_threshold_bps(&self) -> u64 {
+        (self.diff_threshold * 100.0f64).round() as u64
+    }
+
+    pub fn min_gain(&self) -> Gain {
+        Gain::from_cspr(self.min_gain_cspr)
+    }
+
+    /// Average transaction cost of a swap along `path`, in motes. Every hop
+    /// past the second adds as much as the second did.
+    pub fn tx_cost(&self, path: Path) -> U256 {
+        if path.is_multi_hop() {
+            let per_hop = (self.multi_hop_tx_cost_cspr - self.single_hop_tx_cost_cspr).max(0.0);
+            let extra_hops = (path.hops() - 2) as f64;
+            cspr_to_motes(self.multi_hop_tx_cost_cspr + per_hop * extra_hops)
+        } else {
+            cspr_to_motes(self.single_hop_tx_cost_cspr)
+        }
+    }
+
+    /// Average transaction cost of a market deposit or withdrawal, in motes.
+    pub fn market_tx_cost(&self) -> U256 {
+        cspr_to_motes(self.market_tx_cost_cspr)
+    }
+}
+
+impl GasConfig {
+    /// Every hop past the second adds as much as the second did.
+    pub fn swap(&self, path: Path) -> u64 {
+        if path.is_multi_hop() {
+            let per_hop = self.multi_hop_swap.saturating_sub(self.single_hop_swap);
+            self.multi_hop_swap + per_hop * (path.hops() as u64 - 2)
+        } else {
+            self.single_hop_swap
+        }
+    }
+}
+
+impl MarketConfig {
+    pub fn names(&self) -> MarketNames {
+        MarketNames {
+            market: self.market.clone(),
+            long: self.long.clone(),
+            short: self.short.clone(),
+        }
+    }
+}
+
+impl BotConfig {
+    /// The markets to trade: those listed, or the default one if none are.
+    pub fn markets(&self) -> Vec<MarketConfig> {
+        if self.markets.is_empty() {
+            vec![MarketConfig::default()]
+        } else {
+            self.markets.clone()
+        }
+    }
+
+    /// The trade journal of `market`. Each market of several gets its own,
+    /// named after it, e.g. `trade-journal-btc.jsonl`.
+    pub fn market_journal_path(&self, market: &MarketConfig) -> String {
+        self.market_path(&self.journal.path, market)
+    }
+
+    /// The risk limits of `market`: its own, or `[limits]` with a state file
+    /// of its own if it shares them with other markets.
+    pub fn market_limits(&self, market: &MarketConfig) -> LimitsConfig {
+        match &market.limits {
+            Some(limits) => limits.clone(),
+            None => LimitsConfig {
+                state_path: self.market_path(&self.limits.state_path, market),
+                ..self.limits.clone()
+            },
+        }
+    }
+
+    /// The circuit breaker of `market`: `[breaker]` with a reset file of its
+    /// own if there are several markets, so each is reset on its own.
+    pub fn market_breaker(&self, market: &MarketConfig) -> BreakerConfig {
+        BreakerConfig {
+            reset_file: self.market_path(&self.breaker.reset_file, market),
+            ..self.breaker.clone()
+        }
+    }
+
+    /// The risk limits of the treasury, which unwraps wCSPR for every market:
+    /// `[limits]` with a state file of its own.
+    pub fn treasury_limits(&self) -> LimitsConfig {
+        LimitsConfig {
+            state_path: suffixed_path(&self.limits.state_path, "treasury"),
+            ..self.limits.clone()
+        }
+    }
+
+    /// `path` with the market's name appended to the file stem, if there are
+    /// several markets.
+    fn market_path(&self, path: &str, market: &MarketConfig) -> String {
+        if self.markets.len() <= 1 {
+            return path.to_string();
+        }
+        suffixed_path(path, &market.name)
+    }
+
+    /// Loads the config from `path` (or the defaults if no path is given),
+    /// applies `BOT_*` environment overrides (including those from `.env`)
+    /// and validates the result.
+    pub fn load(path: Option<&str>) -> Result<Self, Error> {
+        let mut config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        dotenv::dotenv().ok();
+        config.apply_env_overrides()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn from_file(path: &str) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path).map_err(|e| Error::OdraError {
+            message: format!("Failed to read config file {}: {}", path, e),
+        })?;
+        Self::from_toml(&content)
+    }
+
+    fn from_toml(content: &str) -> Result<Self, Error> {
+        toml::from_str(content).map_err(|e| Error::OdraError {
+            message: format!("Failed to parse config: {}", e),
+        })
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<(), Error> {
+        env_override("BOT_TICK_INTERVAL_SECS", &mut self.tick.interval_secs)?;
+
+        let strategy = &mut self.strategy;
+        env_override("BOT_DIFF_THRESHOLD", &mut strategy.diff_threshold)?;
+        env_override("BOT_MIN_GAIN_CSPR", &mut strategy.min_gain_cspr)?;
+        env_override(
+            "BOT_SINGLE_HOP_TX_COST_CSPR",
+            &mut strategy.single_hop_tx_cost_cspr,
+        )?;
+        env_override(
+            "BOT_MULTI_HOP_TX_COST_CSPR",
+            &mut strategy.multi_hop_tx_cost_cspr,
+        )?;
+        env_override("BOT_MARKET_TX_COST_CSPR", &mut strategy.market_tx_cost_cspr)?;
+        env_override("BOT_PAIR_FEE_BPS", &mut strategy.pair_fee_bps)?;
+        env_override("BOT_MARKET_FEE_BPS", &mut strategy.market_fee_bps)?;
+        env_override("BOT_MAX_HOPS", &mut strategy.max_hops)?;
+
+        env_override("BOT_SLIPPAGE_BPS", &mut self.slippage.tolerance_bps)?;
+        env_override("BOT_SWAP_MODE", &mut self.slippage.mode)?;
+        env_override("BOT_DEADLINE_TTL_SECS", &mut self.deadline.ttl_secs)?;
+        env_override("BOT_MAX_REQUOTES", &mut self.deadline.max_requotes)?;
+
+        let inventory = &mut self.inventory;
+        env_override("BOT_TOP_UP_AMOUNT", &mut inventory.top_up_amount)?;
+        env_override("BOT_MIN_CSPR_BALANCE", &mut inventory.min_cspr_balance)?;
+        env_override("BOT_MIN_WCSPR_BALANCE", &mut inventory.min_wcspr_balance)?;
+        env_override("BOT_UNWRAP_AMOUNT", &mut inventory.unwrap_amount)?;
+
+        let gas = &mut self.gas;
+        env_override("BOT_GAS_DEFAULT_CALL", &mut gas.default_call)?;
+        env_override("BOT_GAS_SINGLE_HOP_SWAP", &mut gas.single_hop_swap)?;
+        env_override("BOT_GAS_MULTI_HOP_SWAP", &mut gas.multi_hop_swap)?;
+
+        let events = &mut self.events;
+        if let Ok(url) = std::env::var("BOT_SSE_URL") {
+            events.sse_url = Some(url.trim().to_string()).filter(|url| !url.is_empty());
+        }
+        env_override(
+            "BOT_RECONNECT_INITIAL_SECS",
+            &mut events.reconnect_initial_secs,
+        )?;
+        env_override("BOT_RECONNECT_MAX_SECS", &mut events.reconnect_max_secs)?;
+        env_override("BOT_DEBOUNCE_MS", &mut events.debounce_ms)?;
+
+        env_override("BOT_JOURNAL_PATH", &mut self.journal.path)?;
+
+        let recorder = &mut self.recorder;
+        env_override("BOT_RECORDER_DIR", &mut recorder.dir)?;
+        env_override("BOT_RECORDER_INTERVAL_SECS", &mut recorder.interval_secs)?;
+        env_override("BOT_RECORDER_MAX_FILE_BYTES", &mut recorder.max_file_bytes)?;
+
+        let errors = &mut self.errors;
+        env_override(
+            "BOT_ERROR_BACKOFF_INITIAL_SECS",
+            &mut errors.backoff_initial_secs,
+        )?;
+        env_override("BOT_ERROR_BACKOFF_MAX_SECS", &mut errors.backoff_max_secs)?;
+
+        let retry = &mut self.retry;
+        env_override("BOT_RETRY_MAX_ATTEMPTS", &mut retry.max_attempts)?;
+        env_override("BOT_RETRY_INITIAL_DELAY_MS", &mut retry.initial_delay_ms)?;
+        env_override("BOT_RETRY_MAX_DELAY_MS", &mut retry.max_delay_ms)?;
+        env_override("BOT_RETRY_JITTER", &mut retry.jitter)?;
+
+        let breaker = &mut self.breaker;
+        env_override(
+            "BOT_BREAKER_MAX_CONSECUTIVE_FAILURES",
+            &mut breaker.max_consecutive_failures,
+        )?;
+        env_override("BOT_BREAKER_MAX_LOSS_CSPR", &mut breaker.max_loss_cspr)?;
+        env_override(
+            "BOT_BREAKER_LOSS_WINDOW_SECS",
+            &mut breaker.loss_window_secs,
+        )?;
+        env_override("BOT_BREAKER_MAX_ERROR_RATE", &mut breaker.max_error_rate)?;
+        env_override(
+            "BOT_BREAKER_ERROR_WINDOW_SECS",
+            &mut breaker.error_window_secs,
+        )?;
+        env_override("BOT_BREAKER_MIN_CHECKS", &mut breaker.min_checks)?;
+        env_override("BOT_BREAKER_COOLDOWN_SECS", &mut breaker.cooldown_secs)?;
+        env_override("BOT_BREAKER_RESET_FILE", &mut breaker.reset_file)?;
+
+        let limits = &mut self.limits;
+        env_override("BOT_MAX_TRADE_NOTIONAL", &mut limits.max_trade_notional)?;
+        env_override("BOT_MAX_HOURLY_NOTIONAL", &mut limits.max_hourly_notional)?;
+        env_override("BOT_MAX_DAILY_NOTIONAL", &mut limits.max_daily_notional)?;
+        env_override("BOT_MAX_DAILY_LOSS", &mut limits.max_daily_loss)?;
+        env_override("BOT_LIMITS_STATE_PATH", &mut limits.state_path)?;
+
+        let oracle = &mut self.oracle;
+        env_override("BOT_ORACLE_PRICE_FEED_ID", &mut oracle.price_feed_id)?;
+        env_override(
+            "BOT_ORACLE_MAX_DEVIATION_BPS",
+            &mut oracle.max_deviation_bps,
+        )?;
+        env_override(
+            "BOT_ORACLE_MAX_STALENESS_SECS",
+            &mut oracle.max_staleness_secs,
+        )?;
+
+        let pools = &mut self.pools;
+        env_override("BOT_POOLS_MIN_RESERVE", &mut pools.min_reserve)?;
+        env_override("BOT_POOLS_MAX_PAIR_AGE_SECS", &mut pools.max_pair_age_secs)?;
+        env_override("BOT_POOLS_MAX_JUMP_BPS", &mut pools.max_jump_bps)?;
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let strategy = &self.strategy;
+        ensure(
+            self.tick.interval_secs > 0,
+            "tick.interval_secs must be positive",
+        )?;
+        ensure(
+            strategy.diff_threshold.is_finite() && strategy.diff_threshold >= 0.0,
+            "strategy.diff_threshold must be a non-negative number",
+        )?;
+        ensure(
+            strategy.min_gain_cspr.is_finite(),
+            "strategy.min_gain_cspr must be a number",
+        )?;
+        ensure(
+            strategy.single_hop_tx_cost_cspr.is_finite() && strategy.single_hop_tx_cost_cspr >= 0.0,
+            "strategy.single_hop_tx_cost_cspr must be a non-negative number",
+        )?;
+        ensure(
+            strategy.multi_hop_tx_cost_cspr.is_finite() && strategy.multi_hop_tx_cost_cspr >= 0.0,
+            "strategy.multi_hop_tx_cost_cspr must be a non-negative number",
+        )?;
+        ensure(
+            strategy.market_tx_cost_cspr.is_finite() && strategy.market_tx_cost_cspr >= 0.0,
+            "strategy.market_tx_cost_cspr must be a non-negative number",
+        )?;
+        ensure(
+            strategy.pair_fee_bps < 10_000,
+            "strategy.pair_fee_bps must be below 10000",
+        )?;
+        ensure(
+            strategy.market_fee_bps < 10_000,
+            "strategy.market_fee_bps must be below 10000",
+        )?;
+        ensure(
+            (1..=MAX_HOPS).contains(&strategy.max_hops),
+            &format!("strategy.max_hops must be between 1 and {}", MAX_HOPS),
+        )?;
+
+        ensure(
+            self.slippage.tolerance_bps < 10_000,
+            "slippage.tolerance_bps must be below 10000",
+        )?;
+        ensure(
+            self.deadline.ttl_secs > 0,
+            "deadline.ttl_secs must be positive",
+        )?;
+
+        let inventory = &self.inventory;
+        ensure(
+            inventory.top_up_amount > 0,
+            "inventory.top_up_amount must be positive",
+        )?;
+        ensure(
+            inventory.unwrap_amount > 0,
+            "inventory.unwrap_amount must be positive",
+        )?;
+
+        let gas = &self.gas;
+        ensure(
+            gas.default_call > 0 && gas.single_hop_swap > 0 && gas.multi_hop_swap > 0,
+            "gas limits must be positive",
+        )?;
+
+        let events = &self.events;
+        ensure(
+            events.reconnect_initial_secs > 0
+                && events.reconnect_initial_secs <= events.reconnect_max_secs,
+            "events.reconnect_initial_secs must be positive and at most events.reconnect_max_secs",
+        )?;
+        ensure(
+            !self.journal.path.trim().is_empty(),
+            "journal.path must not be empty",
+        )?;
+
+        let recorder = &self.recorder;
+        ensure(
+            !recorder.dir.trim().is_empty(),
+            "recorder.dir must not be empty",
+        )?;
+        ensure(
+            recorder.interval_secs > 0,
+            "recorder.interval_secs must be positive",
+        )?;
+        ensure(
+            recorder.max_file_bytes > 0,
+            "recorder.max_file_bytes must be positive",
+        )?;
+
+        let errors = &self.errors;
+        ensure(
+            errors.backoff_initial_secs > 0
+                && errors.backoff_initial_secs <= errors.backoff_max_secs,
+            "errors.backoff_initial_secs must be positive and at most errors.backoff_max_secs",
+        )?;
+
+        let retry = &self.retry;
+        ensure(
+            retry.max_attempts > 0,
+            "retry.max_attempts must be positive",
+        )?;
+        ensure(
+            retry.initial_delay_ms <= retry.max_delay_ms,
+            "retry.initial_delay_ms must be at most retry.max_delay_ms",
+        )?;
+        ensure(
+            (0.0..=1.0).contains(&retry.jitter),
+            "retry.jitter must be between 0.0 and 1.0",
+        )?;
+
+        let breaker = &self.breaker;
+        ensure(
+            breaker.max_consecutive_failures > 0,
+            "breaker.max_consecutive_failures must be positive",
+        )?;
+        ensure(
+            breaker.max_loss_cspr.is_finite() && breaker.max_loss_cspr > 0.0,
+            "breaker.max_loss_cspr must be a positive number",
+        )?;
+        ensure(
+            breaker.max_error_rate > 0.0 && breaker.max_error_rate <= 1.0,
+            "breaker.max_error_rate must be above 0.0 and at most 1.0",
+        )?;
+        ensure(
+            breaker.loss_window_secs > 0
+                && breaker.error_window_secs > 0
+                && breaker.cooldown_secs > 0,
+            "breaker windows and cooldown must be positive",
+        )?;
+        ensure(
+            !breaker.reset_file.trim().is_empty(),
+            "breaker.reset_file must not be empty",
+        )?;
+
+        validate_limits(&self.limits, "limits")?;
+
+        let oracle = &self.oracle;
+        ensure(
+            !oracle.price_feed_id.trim().is_empty(),
+            "oracle.price_feed_id must not be empty",
+        )?;
+        ensure(
+            oracle.max_deviation_bps > 0 && oracle.max_deviation_bps < 10_000,
+            "oracle.max_deviation_bps must be between 1 and 9999",
+        )?;
+        ensure(
+            oracle.max_staleness_secs > 0,
+            "oracle.max_staleness_secs must be positive",
+        )?;
+
+        let pools = &self.pools;
+        ensure(pools.min_reserve > 0, "pools.min_reserve must be positive")?;
+        ensure(
+            pools.max_pair_age_secs > 0,
+            "pools.max_pair_age_secs must be positive",
+        )?;
+        ensure(
+            pools.max_jump_bps > 0,
+            "pools.max_jump_bps must be positive",
+        )?;
+
+        let markets = self.markets();
+        for (index, market) in markets.iter().enumerate() {
+            ensure(
+                !market.name.trim().is_empty()
+                    && !markets[..index]
+                        .iter()
+                        .any(|other| other.name == market.name),
+                "markets.name must be set and unique",
+            )?;
+            // Names end up in file names and log fields
+            ensure(
+                market
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                "markets.name must only contain letters, digits, '_' and '-'",
+            )?;
+            ensure(
+                !market.market.trim().is_empty()
+                    && !market.long.trim().is_empty()
+                    && !market.short.trim().is_empty(),
+                "markets.market, markets.long and markets.short must not be empty",
+            )?;
+            ensure(
+                market.treasury_bps > 0,
+                "markets.treasury_bps must be positive",
+            )?;
+            if let Some(limits) = &market.limits {
+                validate_limits(limits, "markets.limits")?;
+            }
+        }
+        ensure(
+            markets
+                .iter()
+                .map(|market| market.treasury_bps)
+                .sum::<u64>()
+                <= 10_000,
+            "markets.treasury_bps must add up to at most 10000",
+        )?;
+        let mut state_paths = markets
+            .iter()
+            .map(|market| self.market_limits(market).state_path)
+            .collect::<Vec<_>>();
+        state_paths.push(self.treasury_limits().state_path);
+        state_paths.sort();
+        state_paths.dedup();
+        ensure(
+            state_paths.len() == markets.len() + 1,
+            "markets.limits.state_path must differ between markets and the treasury",
+        )?;
+        Ok(())
+    }
+}
+
+fn validate_limits(limits: &LimitsConfig, section: &str) -> Result<(), Error> {
+    ensure(
+        limits.max_trade_notional > 0
+            && limits.max_trade_notional <= limits.max_hourly_notional
+            && limits.max_hourly_notional <= limits.max_daily_notional,
+        &format!(
+            "{}.max_*_notional must be positive and grow from trade to hourly to daily",
+            section
+        ),
+    )?;
+    ensure(
+        !limits.state_path.trim().is_empty(),
+        &format!("{}.state_path must not be empty", section),
+    )
+}
+
+/// `path` with `-<suffix>` appended to the file stem.
+fn suffixed_path(path: &str, suffix: &str) -> String {
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, suffix),
+    };
+    path.with_file_name(file_name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn env_override<T: FromStr>(name: &str, target: &mut T) -> Result<(), Error> {
+    if let Ok(value) = std::env::var(name) {
+        *target = value.trim().parse().map_err(|_| Error::OdraError {
+            message: format!("Invalid value for {}: {}", name, value),
+        })?;
+    }
+    Ok(())
+}
+
+fn ensure(condition: bool, message: &str) -> Result<(), Error> {
+    if condition {
+        Ok(())
+    } else {
+        Err(Error::OdraError {
+            message: format!("Invalid config: {}", message),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        assert!(BotConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_partial_file_keeps_defaults() {
+        let config = BotConfig::from_toml(
+            r#"
+            [strategy]
+            diff_threshold = 4.0
+
+            [inventory]
+            top_up_amount = 500000000000
+
+            [slippage]
+            mode = "exact_in"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.strategy.diff_threshold, 4.0);
+        assert_eq!(config.strategy.min_gain_cspr, 1.0);
+        assert_eq!(config.inventory.top_up_amount, 500_000_000_000);
+        assert_eq!(config.inventory.min_cspr_balance, 100_000_000_000);
+        assert_eq!(config.tick.interval_secs, 180);
+        assert_eq!(config.events.sse_url, None);
+        assert_eq!(config.slippage.mode, SwapMode::ExactIn);
+        assert_eq!(config.slippage.tolerance_bps, 100);
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let result = BotConfig::from_toml(
+            r#"
+            [strategy]
+            diff_treshold = 4.0
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_values() {
+        let mut config = BotConfig::default();
+        config.strategy.pair_fee_bps = 10_000;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.tick.interval_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.strategy.max_hops = MAX_HOPS + 1;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.strategy.diff_threshold = -1.0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.events.reconnect_initial_secs = 120;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.recorder.max_file_bytes = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.slippage.tolerance_bps = 10_000;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.deadline.ttl_secs = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.errors.backoff_max_secs = 1;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.retry.max_attempts = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.retry.jitter = 1.5;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.breaker.max_error_rate = 0.0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.limits.max_hourly_notional = config.limits.max_daily_notional + 1;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.oracle.max_deviation_bps = 0;
+        assert!(config.validate().is_err());
+
+        let mut config = BotConfig::default();
+        config.pools.max_pair_age_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_env_override() {
+        let mut value = 1u64;
+        std::env::set_var("BOT_TEST_ENV_OVERRIDE", "42");
+        assert!(env_override("BOT_TEST_ENV_OVERRIDE", &mut value).is_ok());
+        assert_eq!(value, 42);
+
+        std::env::set_var("BOT_TEST_ENV_OVERRIDE", "not a number");
+        assert!(env_override("BOT_TEST_ENV_OVERRIDE", &mut value).is_err());
+        std::env::remove_var("BOT_TEST_ENV_OVERRIDE");
+    }
+
+    #[test]
+    fn test_diff_threshold_bps() {
+        let config = BotConfig::default();
+        assert_eq!(config.strategy.diff_threshold_bps(), 250);
+        assert_eq!(config.strategy.min_gain(), Gain::from_cspr(1.0));
+    }
+
+    #[test]
+    fn test_tx_cost_and_gas_depend_on_hops() {
+        let config = BotConfig::default();
+        assert_eq!(
+            config.strategy.tx_cost(Path::LongWcsprShort),
+            U256::from(12_500_000_000u64)
+        );
+        assert_eq!(
+            config.strategy.tx_cost(Path::LongWcspr),
+            U256::from(7_000_000_000u64)
+        );
+        assert_eq!(config.gas.swap(Path::ShortWcsprLong), 13_000_000_000);
+        assert_eq!(config.gas.swap(Path::WcsprShort), 8_000_000_000);
+
+        let cycle = "WcsprLongShortWcspr".parse().unwrap();
+        assert_eq!(
+            config.strategy.tx_cost(cycle),
+            U256::from(18_000_000_000u64)
+        );
+        assert_eq!(config.gas.swap(cycle), 18_000_000_000);
+    }
+
+    #[test]
+    fn test_markets_get_their_own_files_and_limits() {
+        let config = BotConfig::default();
+        let main = &config.markets()[0];
+        assert_eq!(main.names(), MarketNames::default());
+        assert_eq!(config.market_journal_path(main), "trade-journal.jsonl");
+        assert_eq!(config.market_limits(main).state_path, "risk-limits.json");
+        assert_eq!(config.market_breaker(main).reset_file, "breaker.reset");
+        assert_eq!(
+            config.treasury_limits().state_path,
+            "risk-limits-treasury.json"
+        );
+
+        let config = BotConfig::from_toml(
+            r#"
+            [[markets]]
+            name = "cspr"
+            treasury_bps = 6_000
+
+            [[markets]]
+            name = "btc"
+            market = "Market BTC"
+            long = "CD_LONG_BTC"
+            short = "CD_SHORT_BTC"
+            treasury_bps = 4_000
+
+            [markets.limits]
+            max_trade_notional = 1_000
+            state_path = "btc-limits.json"
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+        let markets = config.markets();
+        assert_eq!(markets[1].names().long, "CD_LONG_BTC");
+        assert_eq!(
+            config.market_journal_path(&markets[0]),
+            "trade-journal-cspr.jsonl"
+        );
+        assert_eq!(
+            config.market_limits(&markets[0]).state_path,
+            "risk-limits-cspr.json"
+        );
+        assert_eq!(
+            config.market_breaker(&markets[1]).reset_file,
+            "breaker-btc.reset"
+        );
+        let btc_limits = config.market_limits(&markets[1]);
+        assert_eq!(btc_limits.max_trade_notional, 1_000);
+        assert_eq!(btc_limits.state_path, "btc-limits.json");
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_markets() {
+        let market = |name: &str, treasury_bps| MarketConfig {
+            name: name.to_string(),
+            treasury_bps,
+            ..MarketConfig::default()
+        };
+        let invalid = [
+            // Duplicate names
+            vec![market("a", 5_000), market("a", 5_000)],
+            vec![market("../btc", 5_000)],
+            vec![market("btc usd", 5_000)],
+            // More than the whole treasury
+            vec![market("a", 6_000), market("b", 5_000)],
+            vec![market("a", 0)],
+            vec![MarketConfig {
+                limits: Some(LimitsConfig {
+                    max_trade_notional: 0,
+                    ..LimitsConfig::default()
+                }),
+                ..market("a", 10_000)
+            }],
+        ];
+        for markets in invalid {
+            let config = BotConfig {
+                markets,
+                ..BotConfig::default()
+            };
+            assert!(config.validate().is_err());
+        }
+    }
+}
@@ -2,37 +2,54 @@ use std::fmt::Display;
 
 use odra::casper_types::U256;
 
-use crate::bot::path::Path;
+use crate::bot::{
+    path::Token,
+    price::{motes_to_cspr, Price, MOTES_PER_CSPR},
+};
 
-const DECIMAL_PLACES: u32 = 9;
+/// Raw reserves of both casper-trade pools, as returned by `get_reserves`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolReserves {
+    pub long: U256,
+    pub wcspr_long: U256,
+    pub wcspr_short: U256,
+    pub short: U256,
+}
+
+/// The market state fields fair prices are derived from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarketReading {
+    pub long_liquidity: U256,
+    pub long_total_supply: U256,
+    pub short_liquidity: U256,
+    pub short_total_supply: U256,
+    /// CSPR price in USD, with `WCSPR_PRICE_PRECISION` decimals.
+    pub price: U256,
+}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PriceData {
-    pub long_price: f64,
-    pub short_price: f64,
-    pub wcspr_price: f64,
-    pub long_fair_price: f64,
-    pub short_fair_price: f64,
-    pub long_diff: f64,
-    pub short_diff: f64,
-    pub longs_for_one_usd: u64,
-    pub shorts_for_one_usd: u64,
-    pub wcspr_for_one_usd: u64,
+    pub long_price: Price,
+    pub short_price: Price,
+    pub wcspr_price: Price,
+    pub long_fair_price: Price,
+    pub short_fair_price: Price,
+    pub longs_for_one_usd: U256,
+    pub shorts_for_one_usd: U256,
+    pub wcspr_for_one_usd: U256,
 }
 
 impl PriceData {
     pub fn new(
-        long_price: f64,
-        short_price: f64,
-        wcspr_price: f64,
-        long_fair_price: f64,
-        short_fair_price: f64,
+        long_price: Price,
+        short_price: Price,
+        wcspr_price: Price,
+        long_fair_price: Price,
+        short_fair_price: Price,
     ) -> Self {
-        let long_diff = (long_price / long_fair_price) * 100.0f64 - 100.0f64;
-        let short_diff = (short_price / short_fair_price) * 100.0f64 - 100.0f64;
-        let longs_for_one_usd = (1.0f64 / wcspr_price / long_fair_price) as u64;
-        let shorts_for_one_usd = (1.0f64 / wcspr_price / short_fair_price) as u64;
-        let wcspr_for_one_usd = (1.0f64 / wcspr_price) as u64;
+        let wcspr_for_one_usd = wcspr_price.base_for(U256::from(MOTES_PER_CSPR));
+        let longs_for_one_usd = long_fair_price.base_for(wcspr_for_one_usd);
+        let shorts_for_one_usd = short_fair_price.base_for(wcspr_for_one_usd);
 
         Self {
             long_price,
@@ -40,58 +57,74 @@ impl PriceData {
             wcspr_price,
             long_fair_price,
             short_fair_price,
-            long_diff,
-            short_diff,
             longs_for_one_usd,
             shorts_for_one_usd,
             wcspr_for_one_usd,
         }
     }
 
-    fn shorts_amount_per_usd(&self) -> U256 {
-        U256::from(self.shorts_for_one_usd * 10u64.pow(DECIMAL_PLACES))
+    #[cfg(test)]
+    pub fn from_f64(
+        long_price: f64,
+        short_price: f64,
+        wcspr_price: f64,
+        long_fair_price: f64,
+        short_fair_price: f64,
+    ) -> Self {
+        Self::new(
+            Price::from_f64(long_price),
+            Price::from_f64(short_price),
+            Price::from_f64(wcspr_price),
+            Price::from_f64(long_fair_price),
+            Price::from_f64(short_fair_price),
+        )
+    }
+
+    /// Fair price of `token` in CSPR, zero for tokens of no market the bot reads.
+    pub fn fair_price(&self, token: Token) -> Price {
+        match token {
+            Token::Wcspr => Price::new(U256::one(), U256::one()),
+            Token::Long => self.long_fair_price,
+            Token::Short => self.short_fair_price,
+            Token::Other(_) => Price::zero(),
+        }
     }
 
-    fn longs_amount_per_usd(&self) -> U256 {
-        U256::from(self.longs_for_one_usd * 10u64.pow(DECIMAL_PLACES))
+    /// Fair value of `amount` of `token`, in motes.
+    pub fn fair_value(&self, token: Token, amount: U256) -> U256 {
+        self.fair_price(token).quote(amount)
     }
 
-    fn wcspr_amount_per_usd(&self) -> U256 {
-        U256::from(self.wcspr_for_one_usd * 10u64.pow(DECIMAL_PLACES))
+    /// Percentage deviation of the LONG DEX price from fair value, for logging.
+    pub fn long_diff(&self) -> f64 {
+        self.long_price.deviation_pct(&self.long_fair_price)
     }
 
-    pub fn amount_per_one_usd(&self, path: Path) -> U256 {
-        match path {
-            Path::LongWcsprShort => self.longs_amount_per_usd(),
-            Path::ShortWcsprLong => self.shorts_amount_per_usd(),
-            Path::LongWcspr => self.longs_amount_per_usd(),
-            Path::ShortWcspr => self.shorts_amount_per_usd(),
-            Path::WcsprLong => self.wcspr_amount_per_usd(),
-            Path::WcsprShort => self.wcspr_amount_per_usd(),
-            Path::Empty => U256::zero(),
-        }
+    /// Percentage deviation of the SHORT DEX price from fair value, for logging.
+    pub fn short_diff(&self) -> f64 {
+        self.short_price.deviation_pct(&self.short_fair_price)
     }
 }
 
 impl PriceData {
     pub fn log(&self) {
         tracing::info!(
-            long_price = self.long_price,
-            short_price = self.short_price,
-            wcspr_price = self.wcspr_price,
-            long_fair_price = self.long_fair_price,
-            short_fair_price = self.short_fair_price,
+            long_price = self.long_price.to_f64(),
+            short_price = self.short_price.to_f64(),
+            wcspr_price = self.wcspr_price.to_f64(),
+            long_fair_price = self.long_fair_price.to_f64(),
+            short_fair_price = self.short_fair_price.to_f64(),
             "DEX prices (CSPR)"
         );
         tracing::info!(
-            long_diff = format!("{:+.2}%", self.long_diff),
-            short_diff = format!("{:+.2}%", self.short_diff),
+            long_diff = format!("{:+.2}%", self.long_diff()),
+            short_diff = format!("{:+.2}%", self.short_diff()),
             "Price deviations from fair value"
         );
         tracing::info!(
-            longs_per_usd = self.longs_for_one_usd,
-            shorts_per_usd = self.shorts_for_one_usd,
-            wcspr_per_usd = self.wcspr_for_one_usd,
+            longs_per_usd = motes_to_cspr(self.longs_for_one_usd),
+            shorts_per_usd = motes_to_cspr(self.shorts_for_one_usd),
+            wcspr_per_usd = motes_to_cspr(self.wcspr_for_one_usd),
             "Token amounts per USD"
         );
     }
@@ -100,10 +133,10 @@ impl PriceData {
 impl Display for PriceData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "========================")?;
-        writeln!(f, "Long:  {:.6} CSPR  (fair {:.6}, diff {:+.2}%)", self.long_price, self.long_fair_price, self.long_diff)?;
-        writeln!(f, "Short: {:.6} CSPR  (fair {:.6}, diff {:+.2}%)", self.short_price, self.short_fair_price, self.short_diff)?;
-        writeln!(f, "WCSPR: {:.6} USD", self.wcspr_price)?;
-        writeln!(f, "Per USD — Long: {}  Short: {}  WCSPR: {}", self.longs_for_one_usd, self.shorts_for_one_usd, self.wcspr_for_one_usd)?;
+        writeln!(f, "Long:  {:.6} CSPR  (fair {:.6}, diff {:+.2}%)", self.long_price.to_f64(), self.long_fair_price.to_f64(), self.long_diff())?;
+        writeln!(f, "Short: {:.6} CSPR  (fair {:.6}, diff {:+.2}%)", self.short_price.to_f64(), self.short_fair_price.to_f64(), self.short_diff())?;
+        writeln!(f, "WCSPR: {:.6} USD", self.wcspr_price.to_f64())?;
+        writeln!(f, "Per USD — Long: {:.2}  Short: {:.2}  WCSPR: {:.2}", motes_to_cspr(self.longs_for_one_usd), motes_to_cspr(self.shorts_for_one_usd), motes_to_cspr(self.wcspr_for_one_usd))?;
         writeln!(f, "========================")
     }
 }
@@ -0,0 +1,291 @@
+use casper_delta_contracts::{
+    market::{Market, MarketInitArgs},
+    position_token::{PositionToken, PositionTokenInitArgs},
+    wrapped_native::WrappedNativeToken,
+};
+use casper_trade_contracts::{
+    factory::{Factory, FactoryInitArgs},
+    pair::PairHostRef,
+    pair_factory::PairFactory,
+    router::{Router, RouterInitArgs},
+};
+use odra::casper_types::{U256, U512};
+use odra::host::{Deployer, HostEnv, HostRef, NoArgs};
+use odra::prelude::{Address, Addressable};
+use styks_contracts::styks_price_feed::{StyksPriceFeed, StyksPriceFeedConfig};
+
+use crate::bot::{
+    asset_manager::{AssetManager, Balances, RealBalances, RealTokenManager, TokenManager},
+    config::BotConfig,
+    engine::{BotEngine, MarketContext},
+    events::{BotEvent, ShutdownSignal},
+    journal::{Outcome, TradeJournal, TradeRecord},
+    limits::RiskLimits,
+    path::Path,
+    price::{Price, MOTES_PER_CSPR},
+    strategy::DeviationStrategy,
+    utils::PriceCalculator,
+};
+use crate::contracts::{ContractRefs, DeployedContracts};
+use crate::{LP_LONG_WCSPR_ID, LP_WCSPR_SHORT_ID};
+
+const FEED_ID: &str = "CSPRUSD";
+/// CSPR/USD with 5 decimal places, as the market expects.
+const CSPR_USD: u64 = 2_000;
+const DEADLINE: u64 = u64::MAX;
+
+fn cspr(amount: u64) -> U256 {
+    U256::from(amount) * U256::from(MOTES_PER_CSPR)
+}
+
+/// A full casper-delta + casper-trade deployment on the Odra MockVM.
+struct Harness {
+    env: HostEnv,
+    contracts: DeployedContracts,
+    bot: Address,
+}
+
+impl Harness {
+    /// Deploys every contract the bot talks to. Account 0 administers the
+    /// contracts and provides liquidity, account 1 runs the bot.
+    fn deploy() -> Self {
+        let env = odra_test::env();
+        let admin = env.get_account(0);
+        let bot = env.get_account(1);
+        env.set_caller(admin);
+
+        let mut price_feed = StyksPriceFeed::deploy(&env, NoArgs);
+        price_feed.set_config(StyksPriceFeedConfig {
+            heartbeat_interval: 60,
+            heartbeat_tolerance: 30,
+            twap_window: 1,
+            twap_tolerance: 0,
+            price_feed_ids: vec![FEED_ID.to_string()],
+        });
+        price_feed.add_to_feed(vec![(FEED_ID.to_string(), CSPR_USD)]);
+
+        let wcspr = WrappedNativeToken::deploy(&env, NoArgs);
+        let position_token = |name: &str| {
+            PositionToken::deploy(
+                &env,
+                PositionTokenInitArgs {
+                    name: name.to_string(),
+                    symbol: name.to_string(),
+                    decimals: 9,
+                },
+            )
+        };
+        let mut long = position_token("CD_LONG");
+        let mut short = position_token("CD_SHORT");
+        let market = Market::deploy(
+            &env,
+            MarketInitArgs {
+                long_token: long.address(),
+                short_token: short.address(),
+                wcspr_token: wcspr.address(),
+                price_feed: price_feed.address(),
+                price_feed_id: FEED_ID.to_string(),
+            },
+        );
+        long.set_minter(market.address());
+        short.set_minter(market.address());
+
+        let pair_factory = PairFactory::deploy(&env, NoArgs);
+        let mut factory = Factory::deploy(
+            &env,
+            FactoryInitArgs {
+                fee_to_setter: admin,
+                pair_factory: pair_factory.address(),
+            },
+        );
+        let router = Router::deploy(
+            &env,
+            RouterInitArgs {
+                factory: factory.address(),
+                wcspr: wcspr.address(),
+            },
+        );
+        let mut pair = |token_a: Address, token_b: Address| {
+            let address = factory.create_pair(token_a, token_b);
+            PairHostRef::new(address, env.clone())
+        };
+        let pairs = vec![
+            (
+                LP_LONG_WCSPR_ID.to_string(),
+                pair(long.address(), wcspr.address()),
+            ),
+            (
+                LP_WCSPR_SHORT_ID.to_string(),
+                pair(wcspr.address(), short.address()),
+            ),
+        ];
+
+        Self {
+            env: env.clone(),
+            contracts: DeployedContracts {
+                router,
+                pairs,
+                market,
+                price_feed,
+                wcspr,
+                long,
+                short,
+            },
+            bot,
+        }
+    }
+
+    /// Mints `depth_cspr` worth of LONG and SHORT through the market and pools
+    /// them against wCSPR, pricing LONG at `long_percent` and SHORT at
+    /// `short_percent` of fair value.
+    fn seed_liquidity(&self, depth_cspr: u64, long_percent: u64, short_percent: u64) {
+        let depth = cspr(depth_cspr);
+        let contracts = &self.contracts;
+        let mut wcspr = contracts.wcspr.clone();
+        let mut market = contracts.market.clone();
+        let mut long = contracts.long.clone();
+        let mut short = contracts.short.clone();
+        let mut router = contracts.router.clone();
+        let me = self.env.get_account(0);
+        self.env.set_caller(me);
+
+        wcspr
+            .with_tokens(U512::from(depth_cspr * 4) * U512::from(MOTES_PER_CSPR))
+            .deposit();
+        wcspr.approve(&market.address(), &U256::MAX);
+        market.deposit_long(depth);
+        market.deposit_short(depth);
+        wcspr.approve(&router.address(), &U256::MAX);
+        long.approve(&router.address(), &U256::MAX);
+        short.approve(&router.address(), &U256::MAX);
+
+        let (long_fair, short_fair, _) = self.fair_prices();
+        let long_amount = long.balance_of(&me);
+        let short_amount = short.balance_of(&me);
+        let long_wcspr = long_fair.quote(long_amount) * long_percent / 100;
+        let short_wcspr = short_fair.quote(short_amount) * short_percent / 100;
+        router.add_liquidity(
+            long.address(),
+            wcspr.address(),
+            long_amount,
+            long_wcspr,
+            U256::zero(),
+            U256::zero(),
+            me,
+            DEADLINE,
+        );
+        router.add_liquidity(
+            wcspr.address(),
+            short.address(),
+            short_wcspr,
+            short_amount,
+            U256::zero(),
+            U256::zero(),
+            me,
+            DEADLINE,
+        );
+    }
+
+    fn refs(&self) -> ContractRefs<'_> {
+        ContractRefs::deployed(&self.env, self.contracts.clone())
+    }
+
+    fn fair_prices(&self) -> (Price, Price, Price) {
+        let refs = self.refs();
+        PriceCalculator::new(&refs).fair_prices().unwrap()
+    }
+
+    /// How far the LONG pool price is from fair value, in basis points.
+    fn long_deviation_bps(&self) -> u64 {
+        let refs = self.refs();
+        let calc = PriceCalculator::new(&refs);
+        let reserves = calc.pool_reserves().unwrap();
+        let (long_price, _) = PriceCalculator::casper_trade_prices(&reserves);
+        let (long_fair, _, _) = calc.fair_prices().unwrap();
+        (long_price.deviation_pct(&long_fair).abs() * 100.0) as u64
+    }
+
+    /// The bot's CSPR, wCSPR and positions, valued at fair prices in motes.
+    fn bot_value(&self, balances: &RealBalances) -> U256 {
+        let (long_fair, short_fair, _) = self.fair_prices();
+        balances.my_cspr_balance().unwrap()
+            + balances.my_wcspr_balance().unwrap()
+            + long_fair.quote(balances.my_long_balance().unwrap())
+            + short_fair.quote(balances.my_short_balance().unwrap())
+    }
+
+    /// Runs the real engine for `ticks` timer ticks as the bot account.
+    /// Returns its journal and its value before and after.
+    fn run_bot(&self, ticks: usize) -> (Vec<TradeRecord>, U256, U256) {
+        self.env.set_caller(self.bot);
+        let refs = self.refs();
+        let mut config = BotConfig::default();
+        let dir = std::env::temp_dir().join(format!(
+            "bot-e2e-{}-{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let journal_path = dir.join("journal.jsonl").to_string_lossy().to_string();
+        let journal = TradeJournal::open(&journal_path, false).unwrap();
+        config.limits.state_path = dir.join("risk-limits.json").to_string_lossy().to_string();
+        let limits = RiskLimits::open(&config.limits, false).unwrap();
+        let token_manager = RealTokenManager::new(&self.env, &refs, &config);
+        let balances = RealBalances::new(&self.env, &refs);
+        let asset_manager =
+            AssetManager::new(&balances, &token_manager, &config).with_limits(&limits);
+        token_manager.approve_markets().unwrap();
+        let initial_value = self.bot_value(&balances);
+
+        let engine = BotEngine::new(
+            PriceCalculator::new(&refs),
+            Box::new(DeviationStrategy::new(
+                &config.strategy,
+                config.limits.max_trade_notional,
+            )),
+            asset_manager,
+            MarketContext {
+                contracts: &refs,
+                journal: &journal,
+                limits: &limits,
+                breaker: config.breaker.clone(),
+            },
+            self.bot,
+            &config,
+            ShutdownSignal::new(),
+        );
+        for _ in 0..ticks {
+            assert!(engine.handle_event(&BotEvent::TimerTick).unwrap());
+        }
+        let records = TradeJournal::read(&journal_path).unwrap();
+        (records, initial_value, self.bot_value(&balances))
+    }
+}
+
+#[test]
+fn test_bot_arbitrages_skewed_pool_back_to_fair_price() {
+    let harness = Harness::deploy();
+    harness.seed_liquidity(20_000, 110, 100);
+    assert!(harness.long_deviation_bps() >= 900);
+
+    let (records, initial_value, final_value) = harness.run_bot(5);
+    assert!(records
+        .iter()
+        .any(|record| record.path == Path::LongWcspr && record.outcome == Outcome::Executed));
+    let deviation = harness.long_deviation_bps();
+    assert!(
+        deviation < BotConfig::default().strategy.diff_threshold_bps(),
+        "LONG is still {} bps from fair value",
+        deviation
+    );
+    assert!(final_value > initial_value);
+}
+
+#[test]
+fn test_bot_leaves_fairly_priced_pools_alone() {
+    let harness = Harness::deploy();
+    harness.seed_liquidity(20_000, 100, 100);
+
+    let (records, _, _) = harness.run_bot(1);
+    assert!(records.is_empty());
+}
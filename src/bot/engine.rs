@@ -1,44 +1,108 @@
+use std::cell::RefCell;
+use std::time::Instant;
+
+use chrono::Utc;
 use odra::casper_types::U256;
 use odra::prelude::Address;
-use odra_cli::scenario::Error;
 use tracing::instrument;
 
 use crate::bot::asset_manager::AssetManager;
-use crate::bot::data::PriceData;
-use crate::bot::events::BotEvent;
+use crate::bot::breaker::CircuitBreaker;
+use crate::bot::config::{BotConfig, BreakerConfig};
+use crate::bot::data::{PoolReserves, PriceData};
+use crate::bot::error::{BotError, CallResult};
+use crate::bot::events::{BotEvent, ShutdownSignal};
+use crate::bot::graph::RouteGraph;
+use crate::bot::journal::{MarketCall, Outcome, TradeAmounts, TradeJournal, TradeRecord};
+use crate::bot::limits::RiskLimits;
 use crate::bot::path::Path;
+use crate::bot::pool_guard::PoolGuard;
+use crate::bot::price::{motes_to_cspr, Gain};
+use crate::bot::sizing;
+use crate::bot::slippage::{self, SwapOrder};
+use crate::bot::strategy::{Action, Plan, Position, Strategy};
 use crate::bot::utils::PriceCalculator;
 use crate::contracts::ContractRefs;
 
 /// The core bot logic, decoupled from the event loop.
 pub struct BotEngine<'a> {
     calc: PriceCalculator<'a>,
+    strategy: Box<dyn Strategy + 'a>,
     asset_manager: AssetManager<'a>,
     contracts: &'a ContractRefs<'a>,
     caller: Address,
+    config: &'a BotConfig,
+    shutdown: ShutdownSignal,
+    journal: &'a TradeJournal,
+    limits: &'a RiskLimits,
+    breaker: RefCell<CircuitBreaker>,
+    pool_guard: PoolGuard,
+    summary: RefCell<RunSummary>,
+}
+
+/// The pieces of one market an engine trades on and keeps to itself.
+pub struct MarketContext<'a> {
+    pub contracts: &'a ContractRefs<'a>,
+    pub journal: &'a TradeJournal,
+    pub limits: &'a RiskLimits,
+    pub breaker: BreakerConfig,
+}
+
+/// Totals reported when the bot stops.
+#[derive(Debug)]
+struct RunSummary {
+    started: Instant,
+    checks: u64,
+    errors: u64,
+    trades: u64,
+    gain: Gain,
 }
 
 impl<'a> BotEngine<'a> {
     pub fn new(
         calc: PriceCalculator<'a>,
+        strategy: Box<dyn Strategy + 'a>,
         asset_manager: AssetManager<'a>,
-        contracts: &'a ContractRefs<'a>,
+        market: MarketContext<'a>,
         caller: Address,
+        config: &'a BotConfig,
+        shutdown: ShutdownSignal,
     ) -> Self {
         Self {
             calc,
+            strategy,
             asset_manager,
-            contracts,
+            contracts: market.contracts,
             caller,
+            config,
+            shutdown,
+            journal: market.journal,
+            limits: market.limits,
+            breaker: RefCell::new(CircuitBreaker::new(&market.breaker)),
+            pool_guard: PoolGuard::new(&config.pools).with_max_hops(config.strategy.max_hops),
+            summary: RefCell::new(RunSummary {
+                started: Instant::now(),
+                checks: 0,
+                errors: 0,
+                trades: 0,
+                gain: Gain::zero(),
+            }),
         }
     }
 
     /// Handle a single event. Returns `Ok(true)` to continue, `Ok(false)` to stop.
     #[instrument(skip(self))]
-    pub fn handle_event(&self, event: &BotEvent) -> Result<bool, Error> {
+    pub fn handle_event(&self, event: &BotEvent) -> Result<bool, BotError> {
         match event {
             BotEvent::TimerTick | BotEvent::TradeExecuted { .. } | BotEvent::PriceChanged { .. } => {
-                self.check_and_trade()?;
+                self.summary.borrow_mut().checks += 1;
+                let result = self.check_and_trade();
+                self.breaker
+                    .borrow_mut()
+                    .record_check(Instant::now(), result.is_err());
+                result.inspect_err(|_| {
+                    self.summary.borrow_mut().errors += 1;
+                })?;
                 Ok(true)
             }
             BotEvent::Shutdown => {
@@ -48,94 +112,526 @@ impl<'a> BotEngine<'a> {
         }
     }
 
-    /// Fetch prices, find arbitrage path, execute swap if profitable.
-    fn check_and_trade(&self) -> Result<(), Error> {
-        let price_data = self.get_price_data()?;
+    /// Print final balances and what the bot did since it started.
+    pub fn finish(&self) -> Result<(), BotError> {
+        self.asset_manager.print_balances()?;
+        let summary = self.summary.borrow();
+        tracing::info!(
+            uptime_secs = summary.started.elapsed().as_secs(),
+            checks = summary.checks,
+            errors = summary.errors,
+            trades = summary.trades,
+            gain_cspr = summary.gain.to_cspr(),
+            breaker_trips = self.breaker.borrow().trips(),
+            "Bot stopped"
+        );
+        Ok(())
+    }
+
+    /// Fetch prices, let the strategy propose plans and execute them.
+    /// Nothing is traded while the market's CSPR price disagrees with the Styks feed,
+    /// or while a pool looks drained, stale or manipulated.
+    /// A swap that expired before it executed is re-quoted against fresh prices.
+    fn check_and_trade(&self) -> Result<(), BotError> {
+        let max_requotes = self.config.deadline.max_requotes;
+        let mut requotes = 0;
+        while self.check_once()? == Some(Outcome::DeadlineExpired) {
+            if requotes == max_requotes {
+                tracing::warn!("Swap deadline expired, giving up until the next check");
+                break;
+            }
+            requotes += 1;
+            tracing::warn!(
+                "Swap deadline expired, re-quoting ({}/{})",
+                requotes,
+                max_requotes
+            );
+        }
+        Ok(())
+    }
+
+    /// One pass of `check_and_trade`. Returns `DeadlineExpired` if any swap
+    /// expired, and otherwise the outcome of the last action executed, if any.
+    fn check_once(&self) -> Result<Option<Outcome>, BotError> {
+        if self.shutdown.is_triggered() {
+            tracing::info!("Shutdown requested, skipping check");
+            return Ok(None);
+        }
+        let pools = self.calc.pool_reading()?;
+        let graph = pools.graph();
+        let reserves = PriceCalculator::market_reserves(&graph)?;
+        let price_data = self.get_price_data(&reserves)?;
         price_data.log();
+        // Trade only through the pools the guard kept
+        let (graph, pool_check) = match self.pool_guard.check(&pools, &price_data) {
+            Ok(guarded) => (guarded, Ok(())),
+            Err(anomaly) => (graph, Err(anomaly)),
+        };
+
+        let blocked = if self.breaker.borrow_mut().is_open(Instant::now()) {
+            Some("Circuit breaker open".to_string())
+        } else {
+            let oracle = &self.config.oracle;
+            let reading = self.calc.oracle_reading(&oracle.price_feed_id)?;
+            match (reading.check(&price_data.wcspr_price, oracle), pool_check) {
+                (Err(mismatch), _) => Some(format!("Oracle cross-check failed: {}", mismatch)),
+                (_, Err(anomaly)) => Some(format!("Pool sanity check failed: {}", anomaly)),
+                _ => None,
+            }
+        };
+        if let Some(reason) = blocked {
+            let plans = self.propose(&price_data, &graph)?;
+            tracing::warn!("{}, monitoring only (proposed: {:?})", reason, plans);
+            return Ok(None);
+        }
 
         self.asset_manager
-            .manage_asset_levels(&price_data, self.caller)?;
+            .manage_asset_levels(&price_data, &graph, self.caller)?;
 
-        let path = Path::from(&price_data);
-        tracing::info!("Swap path: {:?}", path);
-        if path == Path::Empty {
+        let plans = self.propose(&price_data, &graph)?;
+        if plans.is_empty() {
             tracing::info!("No arbitrage path found");
-            return Ok(());
+            return Ok(None);
         }
+        let mut outcomes = vec![];
+        for plan in plans {
+            if self.breaker.borrow_mut().is_open(Instant::now()) {
+                tracing::warn!("Circuit breaker opened, dropping the remaining plans");
+                break;
+            }
+            if let Some(outcome) = self.check_later_swaps(&plan, &price_data) {
+                outcomes.push(outcome);
+                continue;
+            }
+            let market_call = plan.market_call();
+            // A plan's later actions spend what earlier ones produced, so stop
+            // at the first that didn't execute
+            for (index, action) in plan.actions.into_iter().enumerate() {
+                let outcome = self.execute(action, &price_data, index > 0, market_call)?;
+                outcomes.push(outcome);
+                if outcome != Outcome::Executed {
+                    break;
+                }
+            }
+        }
+        if outcomes.contains(&Outcome::DeadlineExpired) {
+            return Ok(Some(Outcome::DeadlineExpired));
+        }
+        Ok(outcomes.last().copied())
+    }
 
-        let amounts = self.get_swap_amounts(&price_data, path);
-        if let Ok([amount_in, .., amount_out]) = amounts.as_deref() {
-            let gain =
-                PriceCalculator::calc_gains_in_cspr(*amount_in, *amount_out, &price_data, path);
-            tracing::info!("Gain: {:<10.4} CSPR", gain);
-            if gain < 1.0f64 {
-                tracing::info!("No arbitrage path found");
-                return Ok(());
+    fn propose(&self, price_data: &PriceData, graph: &RouteGraph) -> Result<Vec<Plan>, BotError> {
+        let inventory = self.asset_manager.inventory()?;
+        let plans = self.strategy.propose(price_data, graph, &inventory);
+        tracing::info!("Proposed by {}: {:?}", self.strategy.name(), plans);
+        Ok(plans)
+    }
+
+    /// Quotes the swaps that follow a market call in `plan`, so no deposit is
+    /// made for tokens that could no longer be sold at a gain. Returns the
+    /// outcome of the first swap that, together with the market call, doesn't
+    /// clear the minimum gain, journaled like any other skipped trade.
+    fn check_later_swaps(&self, plan: &Plan, price_data: &PriceData) -> Option<Outcome> {
+        let market_call = plan.market_call();
+        let later_actions = plan
+            .actions
+            .iter()
+            .skip_while(|action| matches!(action, Action::Swap { .. }));
+        for action in later_actions {
+            let Action::Swap { path, amount_in } = *action else {
+                continue;
+            };
+            let mut amounts = TradeAmounts::default();
+            let quote =
+                self.quote_swap(path, amount_in, price_data, true, market_call, &mut amounts);
+            if let Err(outcome) = quote {
+                tracing::info!("Skipping {:?}, its swap no longer pays off", plan.actions);
+                self.record(TradeRecord::new(path, outcome, price_data, amounts));
+                return Some(outcome);
+            }
+        }
+        None
+    }
+
+    /// Executes `action`; `produced` if an earlier action of its plan produced
+    /// the tokens it spends. A swap has to make up for the plan's `market_call`.
+    fn execute(
+        &self,
+        action: Action,
+        price_data: &PriceData,
+        produced: bool,
+        market_call: Option<Action>,
+    ) -> Result<Outcome, BotError> {
+        match action {
+            Action::Swap { path, amount_in } => {
+                self.journaled_trade(path, amount_in, price_data, produced, market_call)
+            }
+            Action::Mint { position, amount } => self.journaled(
+                position.buy_path(),
+                Some(MarketCall::Mint),
+                price_data,
+                |amounts| self.mint(position, amount, price_data, amounts),
+            ),
+            Action::Redeem { position, amount } => self.journaled(
+                position.sell_path(),
+                Some(MarketCall::Redeem),
+                price_data,
+                |amounts| self.redeem(position, amount, price_data, amounts),
+            ),
+        }
+    }
+
+    /// Trades along `path`, recording the outcome in the journal and the circuit breaker.
+    fn journaled_trade(
+        &self,
+        path: Path,
+        amount_in: U256,
+        price_data: &PriceData,
+        produced: bool,
+        market_call: Option<Action>,
+    ) -> Result<Outcome, BotError> {
+        self.journaled(path, None, price_data, |amounts| {
+            self.trade(path, amount_in, price_data, produced, market_call, amounts)
+        })
+    }
+
+    /// Runs a swap or `market_call` standing in for `path`, recording its outcome
+    /// in the journal and the circuit breaker.
+    fn journaled(
+        &self,
+        path: Path,
+        market_call: Option<MarketCall>,
+        price_data: &PriceData,
+        run: impl FnOnce(&mut TradeAmounts) -> Result<Outcome, BotError>,
+    ) -> Result<Outcome, BotError> {
+        let mut amounts = TradeAmounts::default();
+        let result = run(&mut amounts);
+        let now = Instant::now();
+        match (&result, amounts.actual_gain) {
+            (Ok(Outcome::Executed), Some(gain)) => {
+                // Only a swap moves the pools the guard compares against
+                if market_call.is_none() {
+                    self.pool_guard.reset();
+                }
+                self.breaker.borrow_mut().record_trade(now, gain)
             }
+            (Err(_), _) => self.breaker.borrow_mut().record_failed_trade(now),
+            _ => {}
+        }
+        let (outcome, error) = match &result {
+            Ok(outcome) => (*outcome, None),
+            Err(e) => (Outcome::Failed, Some(e.to_string())),
+        };
+        let mut record = TradeRecord::new(path, outcome, price_data, amounts);
+        record.market_call = market_call;
+        record.error = error;
+        self.record(record);
+        result
+    }
+
+    fn record(&self, record: TradeRecord) {
+        if let Err(e) = self.journal.append(record) {
+            tracing::error!("Failed to write trade journal: {:?}", e);
+        }
+    }
+
+    /// Quote and execute a swap of `amount_in` along `path`, filling in `amounts` as it goes.
+    /// Tokens an earlier action `produced` are sold exactly and never topped up.
+    fn trade(
+        &self,
+        path: Path,
+        amount_in: U256,
+        price_data: &PriceData,
+        produced: bool,
+        market_call: Option<Action>,
+        amounts: &mut TradeAmounts,
+    ) -> Result<Outcome, BotError> {
+        let quote = self.quote_swap(path, amount_in, price_data, produced, market_call, amounts);
+        let order = match quote {
+            Ok(order) => order,
+            Err(outcome) => return Ok(outcome),
+        };
+        if self.shutdown.is_triggered() {
+            tracing::info!("Shutdown requested, skipping swap");
+            return Ok(Outcome::ShutdownSkipped);
+        }
 
-            let (actual_amount_in, actual_amount_out) =
-                self.swap(path, *amount_in, *amount_out)?;
-            let actual_gain = PriceCalculator::calc_gains_in_cspr(
-                actual_amount_in,
-                actual_amount_out,
-                &price_data,
-                path,
+        let notional = sizing::notional(path, order.amount_in, price_data);
+        if let Err(breach) = self.limits.check_trade(Utc::now(), notional) {
+            tracing::warn!("Risk limit reached, skipping swap: {}", breach);
+            return Ok(Outcome::RiskLimited);
+        }
+
+        amounts.gas = Some(self.config.gas.swap(path));
+        let (actual_amount_in, actual_amount_out) = match self.swap(path, order, produced) {
+            Ok(amounts) => amounts,
+            Err(BotError::DeadlineExpired { deadline }) => {
+                tracing::warn!("Swap missed its deadline {}", deadline);
+                return Ok(Outcome::DeadlineExpired);
+            }
+            Err(BotError::LimitReached(breach)) => {
+                tracing::warn!("Risk limit reached, skipping top-up: {}", breach);
+                return Ok(Outcome::RiskLimited);
+            }
+            Err(e) => return Err(e),
+        };
+        let actual_gain = PriceCalculator::calc_gains_in_cspr(
+            actual_amount_in,
+            actual_amount_out,
+            price_data,
+            path,
+            &self.config.strategy,
+        );
+        amounts.actual_amount_in = Some(actual_amount_in);
+        amounts.actual_amount_out = Some(actual_amount_out);
+        amounts.actual_gain = Some(actual_gain);
+        tracing::info!("Actual gain: {:<10.4} CSPR", actual_gain.to_cspr());
+        let notional = sizing::notional(path, actual_amount_in, price_data);
+        if let Err(e) = self.limits.record(Utc::now(), notional, actual_gain) {
+            tracing::error!(
+                "Failed to record the trade against the risk limits: {:?}",
+                e
             );
-            tracing::info!("Actual gain: {:<10.4} CSPR", actual_gain);
-        } else {
+        }
+        let mut summary = self.summary.borrow_mut();
+        summary.trades += 1;
+        summary.gain = summary.gain + actual_gain;
+        Ok(Outcome::Executed)
+    }
+
+    /// Quotes a swap of `amount_in` along `path` and bounds it by the slippage
+    /// tolerance, filling in `amounts`. Fails with the outcome to record if the
+    /// swap and its plan's `market_call` together don't clear the minimum gain,
+    /// as quoted or at its worst fill.
+    fn quote_swap(
+        &self,
+        path: Path,
+        amount_in: U256,
+        price_data: &PriceData,
+        produced: bool,
+        market_call: Option<Action>,
+        amounts: &mut TradeAmounts,
+    ) -> Result<SwapOrder, Outcome> {
+        let strategy = &self.config.strategy;
+        if amount_in.is_zero() {
+            tracing::info!("No profitable trade size found");
+            return Err(Outcome::NoProfitableSize);
+        }
+
+        let quote = self.get_swap_amounts(amount_in, path);
+        let Ok([amount_in, .., amount_out]) = quote.as_deref() else {
             tracing::info!("No valid swap amounts found");
+            return Err(Outcome::QuoteFailed);
+        };
+        let (amount_in, amount_out) = (*amount_in, *amount_out);
+        let gain =
+            PriceCalculator::calc_gains_in_cspr(amount_in, amount_out, price_data, path, strategy);
+        amounts.quoted_amount_in = Some(amount_in);
+        amounts.quoted_amount_out = Some(amount_out);
+        amounts.expected_gain = Some(gain);
+        tracing::info!("Gain: {:<10.4} CSPR", gain.to_cspr());
+        let market_gain = self.market_call_gain(market_call, amount_out, price_data);
+        if gain + market_gain < strategy.min_gain() {
+            tracing::info!("No arbitrage path found");
+            return Err(Outcome::BelowMinGain);
         }
-        Ok(())
+
+        // Re-check the gain at the worst fill the slippage bound allows
+        let order = if produced {
+            slippage::bound_exact_in(amount_in, amount_out, &self.config.slippage)
+        } else {
+            slippage::bound(amount_in, amount_out, &self.config.slippage)
+        };
+        let worst_case_gain = PriceCalculator::calc_gains_in_cspr(
+            order.amount_in,
+            order.amount_out,
+            price_data,
+            path,
+            strategy,
+        );
+        amounts.worst_case_gain = Some(worst_case_gain);
+        tracing::info!("Worst-case gain: {:<10.4} CSPR", worst_case_gain.to_cspr());
+        let market_gain = self.market_call_gain(market_call, order.amount_out, price_data);
+        if worst_case_gain + market_gain < strategy.min_gain() {
+            tracing::info!("Slippage tolerance leaves too little gain, skipping swap");
+            return Err(Outcome::WorstCaseBelowMinGain);
+        }
+        Ok(order)
     }
 
-    fn get_price_data(&self) -> Result<PriceData, Error> {
-        let (long_price, short_price) = self.calc.casper_trade_prices()?;
-        let (long_fair_price, short_fair_price, wcspr_price) = self.calc.fair_prices()?;
-        Ok(PriceData::new(
-            long_price,
-            short_price,
-            wcspr_price,
-            long_fair_price,
-            short_fair_price,
-        ))
+    /// Gain of the market call a swap's plan makes next to it, at fair prices:
+    /// the deposit that minted the tokens the swap sells, or the redemption of
+    /// the `amount_out` tokens it buys.
+    fn market_call_gain(
+        &self,
+        market_call: Option<Action>,
+        amount_out: U256,
+        price_data: &PriceData,
+    ) -> Gain {
+        let strategy = &self.config.strategy;
+        let gain = match market_call {
+            Some(Action::Mint { position, amount }) => {
+                sizing::mint_gain(position, amount, price_data, strategy)
+            }
+            Some(Action::Redeem { position, .. }) => {
+                sizing::redeem_gain(position, amount_out, price_data, strategy)
+            }
+            _ => return Gain::zero(),
+        };
+        tracing::info!("Market call gain: {:<10.4} CSPR", gain.to_cspr());
+        gain
     }
 
-    fn swap(&self, path: Path, amount_in: U256, amount_out: U256) -> Result<(U256, U256), Error> {
+    /// Deposits `amount` wCSPR for `position` tokens, filling in `amounts` as it
+    /// goes. Valued at fair prices, a mint only loses the market fee and its
+    /// transaction, which the swap selling the tokens has to make up for.
+    fn mint(
+        &self,
+        position: Position,
+        amount: U256,
+        price_data: &PriceData,
+        amounts: &mut TradeAmounts,
+    ) -> Result<Outcome, BotError> {
+        let strategy = &self.config.strategy;
+        if amount.is_zero() {
+            tracing::info!("No profitable mint size found");
+            return Ok(Outcome::NoProfitableSize);
+        }
+        let minted = sizing::minted_amount(amount, position.fair_price(price_data), strategy);
+        let gain = sizing::mint_gain(position, amount, price_data, strategy);
+        amounts.quoted_amount_in = Some(amount);
+        amounts.quoted_amount_out = Some(minted);
+        amounts.expected_gain = Some(gain);
+        amounts.market_fee = Some(sizing::market_fee(amount, strategy));
+        if self.shutdown.is_triggered() {
+            tracing::info!("Shutdown requested, skipping mint");
+            return Ok(Outcome::ShutdownSkipped);
+        }
+
+        amounts.gas = Some(self.config.gas.default_call);
+        match self.asset_manager.mint(position, amount) {
+            Ok(()) => {}
+            Err(BotError::LimitReached(breach)) => {
+                tracing::warn!("Risk limit reached, skipping mint: {}", breach);
+                return Ok(Outcome::RiskLimited);
+            }
+            Err(e) => return Err(e),
+        }
+        tracing::info!(
+            "Minted {:?} with {:.2} wCSPR",
+            position,
+            motes_to_cspr(amount)
+        );
+        amounts.actual_amount_in = Some(amount);
+        amounts.actual_amount_out = Some(minted);
+        amounts.actual_gain = Some(gain);
+        let mut summary = self.summary.borrow_mut();
+        summary.trades += 1;
+        summary.gain = summary.gain + gain;
+        Ok(Outcome::Executed)
+    }
+
+    /// Withdraws `amount` `position` tokens for wCSPR, filling in `amounts` as it
+    /// goes. Like a mint, it only loses the market fee and its transaction at
+    /// fair prices; the swap that bought the tokens made the gain.
+    fn redeem(
+        &self,
+        position: Position,
+        amount: U256,
+        price_data: &PriceData,
+        amounts: &mut TradeAmounts,
+    ) -> Result<Outcome, BotError> {
+        let strategy = &self.config.strategy;
+        if amount.is_zero() {
+            tracing::info!("Nothing to redeem");
+            return Ok(Outcome::NoProfitableSize);
+        }
+        let fair_price = position.fair_price(price_data);
+        amounts.quoted_amount_in = Some(amount);
+        amounts.quoted_amount_out = Some(sizing::redeemed_amount(amount, fair_price, strategy));
+        amounts.expected_gain = Some(sizing::redeem_gain(position, amount, price_data, strategy));
+        if self.shutdown.is_triggered() {
+            tracing::info!("Shutdown requested, skipping redeem");
+            return Ok(Outcome::ShutdownSkipped);
+        }
+
+        if let Err(breach) = self
+            .limits
+            .check_deposit(Utc::now(), fair_price.quote(amount))
+        {
+            tracing::warn!("Risk limit reached, skipping redeem: {}", breach);
+            return Ok(Outcome::RiskLimited);
+        }
+
+        amounts.gas = Some(self.config.gas.default_call);
+        let redeemed = self.asset_manager.redeem(position, amount)?;
+        let amount_out = sizing::redeemed_amount(redeemed, fair_price, strategy);
+        let gain = sizing::redeem_gain(position, redeemed, price_data, strategy);
+        let notional = fair_price.quote(redeemed);
+        if let Err(e) = self.limits.record(Utc::now(), notional, gain) {
+            tracing::error!(
+                "Failed to record the redeem against the risk limits: {:?}",
+                e
+            );
+        }
+        tracing::info!(
+            "Redeemed {:?} for {:.2} wCSPR",
+            position,
+            motes_to_cspr(amount_out)
+        );
+        amounts.actual_amount_in = Some(redeemed);
+        amounts.actual_amount_out = Some(amount_out);
+        amounts.actual_gain = Some(gain);
+        amounts.market_fee = Some(sizing::market_fee(notional, strategy));
+        let mut summary = self.summary.borrow_mut();
+        summary.trades += 1;
+        summary.gain = summary.gain + gain;
+        Ok(Outcome::Executed)
+    }
+
+    fn get_price_data(&self, reserves: &PoolReserves) -> Result<PriceData, BotError> {
+        let reading = self.calc.market_reading()?;
+        let price_data = PriceCalculator::price_data(reserves, &reading);
+        if price_data.long_price.is_zero() || price_data.short_price.is_zero() {
+            return Err(BotError::StaleData(
+                "a casper-trade pool is empty".to_string(),
+            ));
+        }
+        if price_data.long_fair_price.is_zero() || price_data.short_fair_price.is_zero() {
+            return Err(BotError::StaleData(
+                "the market has no liquidity".to_string(),
+            ));
+        }
+        Ok(price_data)
+    }
+
+    fn swap(&self, path: Path, order: SwapOrder, produced: bool) -> Result<(U256, U256), BotError> {
         tracing::info!("Preparing swap...");
-        let result = self
-            .asset_manager
-            .swap(path, amount_in, amount_out, self.caller)?;
+        let result = if produced {
+            self.asset_manager.swap_held(path, order, self.caller)?
+        } else {
+            self.asset_manager.swap(path, order, self.caller)?
+        };
         tracing::info!("Arbitrage swap completed");
         self.asset_manager.print_balances()?;
 
         if let [amount_in, .., amount_out] = result.as_slice() {
             Ok((*amount_in, *amount_out))
         } else {
-            Err(Error::OdraError {
-                message: "Invalid swap result".to_string(),
-            })
+            Err(BotError::Invariant("Invalid swap result".to_string()))
         }
     }
 
-    fn get_swap_amounts(
-        &self,
-        price_data: &PriceData,
-        path: Path,
-    ) -> Result<Vec<U256>, Error> {
-        let amount_in = price_data.amount_per_one_usd(path);
+    fn get_swap_amounts(&self, amount_in: U256, path: Path) -> Result<Vec<U256>, BotError> {
         let path = path.build(self.contracts)?;
         let amounts = self.contracts
             .router()?
             .try_get_amounts_out(amount_in, path)
-            .map_err(|e| Error::OdraError {
-                message: format!("Failed to get amounts out: {:?}", e),
-            })?;
+            .during("get_amounts_out")?;
         if let [amount_in, .., amount_out] = amounts.as_slice() {
             Ok(vec![*amount_in, *amount_out])
         } else {
-            Err(Error::OdraError {
-                message: "Invalid swap result".to_string(),
-            })
+            Err(BotError::Invariant("Invalid swap result".to_string()))
         }
     }
 
@@ -0,0 +1,246 @@
+use std::fmt::{self, Display};
+
+use odra::casper_types::U256;
+use odra::{ExecutionError, OdraError, OdraResult, VmError};
+use odra_cli::scenario::Error;
+
+use crate::bot::limits::LimitBreach;
+use crate::bot::price::motes_to_cspr;
+
+/// A failed check or trade, classified so the run loop can decide what to do next.
+#[derive(Debug)]
+pub enum BotError {
+    /// Not enough of `token` to fund a swap or top-up.
+    InsufficientFunds {
+        token: &'static str,
+        required: U256,
+        available: U256,
+    },
+    /// Contract `call` reverted with the contract's (router, pair, market...) error code.
+    Reverted { call: &'static str, code: u16 },
+    /// The swap reached the chain after its deadline (block time, in ms);
+    /// the opportunity should be re-quoted.
+    DeadlineExpired { deadline: u64 },
+    /// The node could not execute or answer the call.
+    Transport(String),
+    /// A state-changing call failed in transit and may or may not have executed.
+    OutcomeUnknown { call: &'static str },
+    /// A trade or top-up would break a hard risk limit.
+    LimitReached(LimitBreach),
+    /// On-chain data too old or inconsistent to trade on.
+    StaleData(String),
+    /// A result the bot's own logic rules out, e.g. a malformed router response.
+    Invariant(String),
+    /// Config, IO or contract lookup failures surfaced by `odra_cli`.
+    Setup(Error),
+}
+
+/// What the run loop does after an event failed to be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Handle the same event again straight away.
+    Retry,
+    /// Drop the event and wait for the next one.
+    Skip,
+    /// Wait out an increasing delay before handling more events.
+    BackOff,
+    /// Stop the bot.
+    Halt,
+}
+
+impl BotError {
+    /// Whether the same call may succeed if simply made again.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BotError::Transport(_))
+    }
+
+    pub fn policy(&self) -> ErrorPolicy {
+        match self {
+            // The next check quotes against fresh prices anyway.
+            BotError::Reverted { .. } | BotError::StaleData(_) => ErrorPolicy::Skip,
+            // The budget frees up as the limit windows roll on.
+            BotError::LimitReached(_) => ErrorPolicy::Skip,
+            BotError::DeadlineExpired { .. } => ErrorPolicy::Retry,
+            // Give the node, or whoever funds the account, time to recover.
+            BotError::Transport(_)
+            | BotError::OutcomeUnknown { .. }
+            | BotError::InsufficientFunds { .. } => ErrorPolicy::BackOff,
+            BotError::Invariant(_) | BotError::Setup(_) => ErrorPolicy::Halt,
+        }
+    }
+}
+
+impl Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::InsufficientFunds {
+                token,
+                required,
+                available,
+            } => write!(
+                f,
+                "Not enough {}: {:.2} required, {:.2} available",
+                token,
+                motes_to_cspr(*required),
+                motes_to_cspr(*available)
+            ),
+            BotError::Reverted { call, code } => {
+                write!(f, "{} reverted with error code {}", call, code)
+            }
+            BotError::DeadlineExpired { deadline } => {
+                write!(f, "Swap deadline {} expired", deadline)
+            }
+            BotError::Transport(message) => write!(f, "Node call failed: {}", message),
+            BotError::OutcomeUnknown { call } => {
+                write!(f, "Outcome of {} unknown, not resubmitting it", call)
+            }
+            BotError::LimitReached(breach) => write!(f, "Risk limit reached: {}", breach),
+            BotError::StaleData(message) => write!(f, "Stale data: {}", message),
+            BotError::Invariant(message) => write!(f, "Invariant violated: {}", message),
+            BotError::Setup(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl BotError {
+    /// Classifies the failure of contract `call`. Only VM errors the node may not
+    /// repeat are transport failures; a missing entry point, contract or argument,
+    /// or a value that does not (de)serialize, fails the same way every time.
+    pub fn from_call(call: &'static str, e: OdraError) -> Self {
+        match e {
+            OdraError::ExecutionError(ExecutionError::User(code)) => {
+                BotError::Reverted { call, code }
+            }
+            OdraError::ExecutionError(e) => BotError::Reverted {
+                call,
+                code: e.code(),
+            },
+            OdraError::VmError(
+                e @ (VmError::NoSuchMethod(_)
+                | VmError::InvalidContractAddress
+                | VmError::MissingArg),
+            ) => BotError::Setup(Error::OdraError {
+                message: format!("{} does not match the deployed contract: {:?}", call, e),
+            }),
+            // Malformed values, or a balance the bot checked before spending it.
+            OdraError::VmError(
+                e @ (VmError::Serialization
+                | VmError::Deserialization
+                | VmError::InvalidContext
+                | VmError::BalanceExceeded),
+            ) => BotError::Invariant(format!("{} failed: {:?}", call, e)),
+            OdraError::VmError(e) => BotError::Transport(format!("{} failed: {:?}", call, e)),
+        }
+    }
+}
+
+/// Names the contract call a result came from, see [`BotError::from_call`].
+pub trait CallResult<T> {
+    fn during(self, call: &'static str) -> Result<T, BotError>;
+}
+
+impl<T> CallResult<T> for OdraResult<T> {
+    fn during(self, call: &'static str) -> Result<T, BotError> {
+        self.map_err(|e| BotError::from_call(call, e))
+    }
+}
+
+impl From<Error> for BotError {
+    fn from(e: Error) -> Self {
+        BotError::Setup(e)
+    }
+}
+
+impl From<BotError> for Error {
+    fn from(e: BotError) -> Self {
+        match e {
+            BotError::Setup(e) => e,
+            e => Error::OdraError {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_odra_errors_are_classified() {
+        let error = BotError::from_call(
+            "get_reserves",
+            OdraError::ExecutionError(ExecutionError::User(42)),
+        );
+        assert!(matches!(
+            error,
+            BotError::Reverted {
+                call: "get_reserves",
+                code: 42
+            }
+        ));
+        assert_eq!(error.policy(), ErrorPolicy::Skip);
+        assert_eq!(
+            error.to_string(),
+            "get_reserves reverted with error code 42"
+        );
+
+        let error = BotError::from_call(
+            "get_reserves",
+            OdraError::VmError(VmError::Other("timeout".to_string())),
+        );
+        assert!(matches!(error, BotError::Transport(_)));
+        assert_eq!(error.policy(), ErrorPolicy::BackOff);
+    }
+
+    #[test]
+    fn test_deterministic_vm_errors_halt() {
+        let missing = OdraError::VmError(VmError::NoSuchMethod("token0".to_string()));
+        let error = BotError::from_call("token0", missing);
+        assert!(matches!(error, BotError::Setup(_)));
+        assert_eq!(error.policy(), ErrorPolicy::Halt);
+
+        let malformed = OdraError::VmError(VmError::Deserialization);
+        let error = BotError::from_call("get_amounts_out", malformed);
+        assert!(matches!(error, BotError::Invariant(_)));
+        assert_eq!(error.policy(), ErrorPolicy::Halt);
+        assert!(!error.is_transient());
+    }
+
+    #[test]
+    fn test_policy_per_class() {
+        assert_eq!(
+            BotError::DeadlineExpired { deadline: 1 }.policy(),
+            ErrorPolicy::Retry
+        );
+        assert_eq!(
+            BotError::StaleData("empty pool".to_string()).policy(),
+            ErrorPolicy::Skip
+        );
+        assert_eq!(
+            BotError::Invariant("Invalid swap result".to_string()).policy(),
+            ErrorPolicy::Halt
+        );
+        let setup = BotError::from(Error::OdraError {
+            message: "Contract not found".to_string(),
+        });
+        assert_eq!(setup.policy(), ErrorPolicy::Halt);
+    }
+
+    #[test]
+    fn test_converts_back_to_cli_error() {
+        let error: Error = BotError::InsufficientFunds {
+            token: "CSPR",
+            required: U256::from(2_000_000_000_000u64),
+            available: U256::from(1_000_000_000u64),
+        }
+        .into();
+        assert_eq!(
+            error.to_string(),
+            Error::OdraError {
+                message: "Not enough CSPR: 2000.00 required, 1.00 available".to_string()
+            }
+            .to_string()
+        );
+    }
+}
@@ -1,15 +1,22 @@
-use std::thread::sleep;
 use std::time::Duration;
 
+pub use self::composite::CompositeEventSource;
+pub use self::shutdown::{ShutdownEventSource, ShutdownSignal};
+pub use self::sse::{Backoff, SseEventSource, WatchedContract};
+
+mod composite;
+mod shutdown;
+mod sse;
+
 /// Events that can trigger the bot's price-check-and-trade cycle.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum BotEvent {
     /// Periodic timer fired — check prices and trade if profitable.
     TimerTick,
-    /// A trade was executed on the DEX (future: from SSE/WebSocket stream).
+    /// A transaction touched one of the DEX pairs (from the node event stream).
     TradeExecuted { pair: String },
-    /// Significant price movement detected on-chain (future: from node events).
+    /// A transaction touched the market, moving fair prices (from the node event stream).
     PriceChanged { token: String },
     /// Graceful shutdown requested.
     Shutdown,
@@ -22,29 +29,59 @@ pub trait EventSource {
 }
 
 /// Emits `TimerTick` events at a fixed interval.
-/// The first event is emitted immediately.
+/// The first event is emitted immediately. Stops once shutdown is requested.
 pub struct TimerEventSource {
     interval: Duration,
     first: bool,
+    shutdown: ShutdownSignal,
 }
 
 impl TimerEventSource {
-    pub fn new(interval: Duration) -> Self {
+    pub fn new(interval: Duration, shutdown: ShutdownSignal) -> Self {
         Self {
             interval,
             first: true,
+            shutdown,
         }
     }
 }
 
 impl EventSource for TimerEventSource {
     fn next_event(&mut self) -> Option<BotEvent> {
+        if self.shutdown.is_triggered() {
+            return None;
+        }
         if self.first {
             self.first = false;
             return Some(BotEvent::TimerTick);
         }
         tracing::info!("Sleeping for {} seconds...", self.interval.as_secs());
-        sleep(self.interval);
+        if self.shutdown.sleep(self.interval) {
+            return None;
+        }
         Some(BotEvent::TimerTick)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn test_timer_sleep_is_interrupted_by_shutdown() {
+        let shutdown = ShutdownSignal::new();
+        let mut timer = TimerEventSource::new(Duration::from_secs(60), shutdown.clone());
+        assert_eq!(timer.next_event(), Some(BotEvent::TimerTick));
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            shutdown.trigger();
+        });
+        let started = Instant::now();
+        assert_eq!(timer.next_event(), None);
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{BotEvent, EventSource};
+
+/// Merges several event sources into one stream.
+///
+/// Every source runs on its own thread. Events arriving within `debounce` of the
+/// first one are coalesced into a single event — the one with the highest priority —
+/// since each of them would trigger the same price check. `Shutdown` is delivered
+/// as soon as it arrives, ahead of anything pending, and ends the stream.
+pub struct CompositeEventSource {
+    receiver: Receiver<BotEvent>,
+    debounce: Duration,
+    stopped: bool,
+}
+
+impl CompositeEventSource {
+    pub fn new(sources: Vec<Box<dyn EventSource + Send>>, debounce: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        for mut source in sources {
+            let sender = sender.clone();
+            thread::spawn(move || {
+                while let Some(event) = source.next_event() {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        Self {
+            receiver,
+            debounce,
+            stopped: false,
+        }
+    }
+}
+
+impl EventSource for CompositeEventSource {
+    fn next_event(&mut self) -> Option<BotEvent> {
+        if self.stopped {
+            return None;
+        }
+        let mut selected = self.receiver.recv().ok()?;
+        let deadline = Instant::now() + self.debounce;
+        let mut coalesced = 0;
+        while selected != BotEvent::Shutdown {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Ok(event) = self.receiver.recv_timeout(timeout) else {
+                break;
+            };
+            coalesced += 1;
+            if priority(&event) > priority(&selected) {
+                selected = event;
+            }
+        }
+        if coalesced > 0 {
+            tracing::debug!("Coalesced {} events into {:?}", coalesced, selected);
+        }
+        if selected == BotEvent::Shutdown {
+            self.stopped = true;
+        }
+        Some(selected)
+    }
+}
+
+/// Higher wins when events are coalesced.
+fn priority(event: &BotEvent) -> u8 {
+    match event {
+        BotEvent::Shutdown => 3,
+        BotEvent::PriceChanged { .. } => 2,
+        BotEvent::TradeExecuted { .. } => 1,
+        BotEvent::TimerTick => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Emits each event after its delay, then ends.
+    struct ScriptedSource(VecDeque<(u64, BotEvent)>);
+
+    impl EventSource for ScriptedSource {
+        fn next_event(&mut self) -> Option<BotEvent> {
+            let (delay_ms, event) = self.0.pop_front()?;
+            thread::sleep(Duration::from_millis(delay_ms));
+            Some(event)
+        }
+    }
+
+    fn scripted(events: Vec<(u64, BotEvent)>) -> Box<dyn EventSource + Send> {
+        Box::new(ScriptedSource(events.into()))
+    }
+
+    fn trade() -> BotEvent {
+        BotEvent::TradeExecuted {
+            pair: "CD_LONG-WCSPR LP".to_string(),
+        }
+    }
+
+    fn price_changed() -> BotEvent {
+        BotEvent::PriceChanged {
+            token: "Market".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_burst_of_trades_is_debounced() {
+        let burst = (0..5).map(|_| (0, trade())).collect();
+        let mut source =
+            CompositeEventSource::new(vec![scripted(burst)], Duration::from_millis(200));
+        assert_eq!(source.next_event(), Some(trade()));
+        assert_eq!(source.next_event(), None);
+    }
+
+    #[test]
+    fn test_separate_events_are_not_merged() {
+        let events = vec![(0, trade()), (300, trade())];
+        let mut source =
+            CompositeEventSource::new(vec![scripted(events)], Duration::from_millis(50));
+        assert_eq!(source.next_event(), Some(trade()));
+        assert_eq!(source.next_event(), Some(trade()));
+        assert_eq!(source.next_event(), None);
+    }
+
+    #[test]
+    fn test_highest_priority_event_wins() {
+        let sources = vec![
+            scripted(vec![(0, BotEvent::TimerTick)]),
+            scripted(vec![(20, trade())]),
+            scripted(vec![(40, price_changed())]),
+        ];
+        let mut source = CompositeEventSource::new(sources, Duration::from_millis(300));
+        assert_eq!(source.next_event(), Some(price_changed()));
+        assert_eq!(source.next_event(), None);
+    }
+
+    #[test]
+    fn test_shutdown_is_delivered_first_and_ends_stream() {
+        let sources = vec![
+            scripted(vec![(0, trade()), (0, trade())]),
+            scripted(vec![(20, BotEvent::Shutdown)]),
+            scripted(vec![(50, BotEvent::TimerTick); 100]),
+        ];
+        let started = Instant::now();
+        let mut source = CompositeEventSource::new(sources, Duration::from_secs(10));
+        assert_eq!(source.next_event(), Some(BotEvent::Shutdown));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(source.next_event(), None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use odra_cli::scenario::Error;
+
+use super::{BotEvent, EventSource};
+
+/// A shutdown request shared between the signal handler, the event sources and the engine.
+#[derive(Clone, Default)]
+pub struct ShutdownSignal {
+    state: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Triggers this signal on SIGINT/SIGTERM. A second signal exits immediately.
+    pub fn install_handler(&self) -> Result<(), Error> {
+        let signal = self.clone();
+        ctrlc::set_handler(move || {
+            if signal.is_triggered() {
+                tracing::warn!("Second shutdown signal received, exiting immediately");
+                std::process::exit(130);
+            }
+            tracing::info!("Shutdown signal received, finishing current work...");
+            signal.trigger();
+        })
+        .map_err(|e| Error::OdraError {
+            message: format!("Failed to install signal handler: {}", e),
+        })
+    }
+
+    pub fn trigger(&self) {
+        let (triggered, condvar) = &*self.state;
+        *triggered.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        let (triggered, _) = &*self.state;
+        *triggered.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sleeps for `duration` unless triggered first. Returns whether it was triggered.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let (triggered, condvar) = &*self.state;
+        let mut triggered = triggered.lock().unwrap_or_else(|e| e.into_inner());
+        while !*triggered {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                break;
+            }
+            triggered = condvar
+                .wait_timeout(triggered, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *triggered
+    }
+
+    /// Blocks until triggered.
+    pub fn wait(&self) {
+        let (triggered, condvar) = &*self.state;
+        let mut triggered = triggered.lock().unwrap_or_else(|e| e.into_inner());
+        while !*triggered {
+            triggered = condvar.wait(triggered).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Emits a single `Shutdown` event once the signal is triggered.
+pub struct ShutdownEventSource {
+    signal: ShutdownSignal,
+    done: bool,
+}
+
+impl ShutdownEventSource {
+    pub fn new(signal: ShutdownSignal) -> Self {
+        Self {
+            signal,
+            done: false,
+        }
+    }
+}
+
+impl EventSource for ShutdownEventSource {
+    fn next_event(&mut self) -> Option<BotEvent> {
+        if self.done {
+            return None;
+        }
+        self.signal.wait();
+        self.done = true;
+        Some(BotEvent::Shutdown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_sleep_is_interrupted_by_trigger() {
+        let signal = ShutdownSignal::new();
+        let trigger = signal.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            trigger.trigger();
+        });
+        let started = Instant::now();
+        assert!(signal.sleep(Duration::from_secs(10)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_sleep_times_out_without_trigger() {
+        let signal = ShutdownSignal::new();
+        assert!(!signal.sleep(Duration::from_millis(20)));
+        assert!(!signal.is_triggered());
+    }
+
+    #[test]
+    fn test_source_emits_shutdown_once() {
+        let signal = ShutdownSignal::new();
+        let mut source = ShutdownEventSource::new(signal.clone());
+        signal.trigger();
+        assert_eq!(source.next_event(), Some(BotEvent::Shutdown));
+        assert_eq!(source.next_event(), None);
+    }
+}
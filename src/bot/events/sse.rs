@@ -0,0 +1,395 @@
+use std::error::Error;
+use std::io::{BufRead, BufReader};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use odra::prelude::Address;
+use serde_json::Value;
+
+use super::{BotEvent, EventSource};
+
+/// Node events that carry the outcome of an executed transaction.
+const PROCESSED_EVENTS: [&str; 2] = ["TransactionProcessed", "DeployProcessed"];
+
+/// A contract whose transactions should wake the bot, and the event to emit for them.
+#[derive(Debug, Clone)]
+pub struct WatchedContract {
+    hash: String,
+    event: BotEvent,
+}
+
+impl WatchedContract {
+    /// Transactions touching a DEX pair emit `TradeExecuted`.
+    pub fn pair(name: &str, address: &Address) -> Self {
+        Self::new(
+            &address_hash(address),
+            BotEvent::TradeExecuted {
+                pair: name.to_string(),
+            },
+        )
+    }
+
+    /// Transactions touching the market emit `PriceChanged`.
+    pub fn market(name: &str, address: &Address) -> Self {
+        Self::new(
+            &address_hash(address),
+            BotEvent::PriceChanged {
+                token: name.to_string(),
+            },
+        )
+    }
+
+    fn new(hash: &str, event: BotEvent) -> Self {
+        Self {
+            hash: hash.to_lowercase(),
+            event,
+        }
+    }
+}
+
+/// The hex part of a formatted address, e.g. `hash-4b15...` -> `4b15...`.
+fn address_hash(address: &Address) -> String {
+    address
+        .to_formatted_string()
+        .rsplit('-')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Exponential delay, doubling up to `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            current: initial,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+}
+
+/// Emits `TradeExecuted`/`PriceChanged` events for processed transactions that touch
+/// any of the watched contracts, read from a Casper node SSE event stream.
+///
+/// The stream is consumed on a background thread which reconnects with exponential
+/// backoff, resuming from the last seen event id.
+pub struct SseEventSource {
+    receiver: Receiver<BotEvent>,
+}
+
+impl SseEventSource {
+    pub fn new(url: String, watched: Vec<WatchedContract>, backoff: Backoff) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let mut reader = StreamReader {
+            url,
+            watched,
+            backoff,
+            last_event_id: None,
+            sender,
+            disconnected: false,
+        };
+        thread::spawn(move || reader.run());
+        Self { receiver }
+    }
+}
+
+impl EventSource for SseEventSource {
+    fn next_event(&mut self) -> Option<BotEvent> {
+        self.receiver.recv().ok()
+    }
+}
+
+struct StreamReader {
+    url: String,
+    watched: Vec<WatchedContract>,
+    backoff: Backoff,
+    last_event_id: Option<u64>,
+    sender: Sender<BotEvent>,
+    disconnected: bool,
+}
+
+impl StreamReader {
+    fn run(&mut self) {
+        loop {
+            match self.stream() {
+                Ok(()) => tracing::warn!("Node event stream closed"),
+                Err(e) => tracing::warn!("Node event stream error: {}", e),
+            }
+            if self.disconnected {
+                return;
+            }
+            let delay = self.backoff.next_delay();
+            tracing::info!(
+                "Reconnecting to node event stream in {} ms...",
+                delay.as_millis()
+            );
+            thread::sleep(delay);
+        }
+    }
+
+    fn stream(&mut self) -> Result<(), Box<dyn Error>> {
+        let url = self.stream_url();
+        let response = reqwest::blocking::Client::builder()
+            .timeout(None::<Duration>)
+            .build()?
+            .get(&url)
+            .send()?
+            .error_for_status()?;
+        tracing::info!("Connected to node event stream at {}", url);
+        self.backoff.reset();
+
+        let mut parser = SseParser::default();
+        for line in BufReader::new(response).lines() {
+            let Some(message) = parser.push_line(&line?) else {
+                continue;
+            };
+            if message.id.is_some() {
+                self.last_event_id = message.id;
+            }
+            for event in events_for(&message.data, &self.watched) {
+                if self.sender.send(event).is_err() {
+                    self.disconnected = true;
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn stream_url(&self) -> String {
+        match self.last_event_id {
+            Some(id) => {
+                let separator = if self.url.contains('?') { '&' } else { '?' };
+                format!("{}{}start_from={}", self.url, separator, id + 1)
+            }
+            None => self.url.clone(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct SseMessage {
+    id: Option<u64>,
+    data: String,
+}
+
+/// Incremental parser for the `text/event-stream` format.
+#[derive(Default)]
+struct SseParser {
+    id: Option<u64>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Feeds a single line; returns a message once its terminating blank line is seen.
+    fn push_line(&mut self, line: &str) -> Option<SseMessage> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.is_empty() {
+            if self.data.is_empty() {
+                self.id = None;
+                return None;
+            }
+            return Some(SseMessage {
+                id: self.id.take(),
+                data: std::mem::take(&mut self.data).join("\n"),
+            });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "id" => self.id = value.parse().ok(),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Bot events for a single node event: one per watched contract mentioned by a
+/// processed transaction (in its effects, messages or transfers).
+fn events_for(data: &str, watched: &[WatchedContract]) -> Vec<BotEvent> {
+    let is_processed = serde_json::from_str::<Value>(data)
+        .ok()
+        .and_then(|value| {
+            value.as_object().map(|event| {
+                PROCESSED_EVENTS
+                    .iter()
+                    .any(|kind| event.contains_key(*kind))
+            })
+        })
+        .unwrap_or(false);
+    if !is_processed {
+        return vec![];
+    }
+    let data = data.to_lowercase();
+    watched
+        .iter()
+        .filter(|contract| !contract.hash.is_empty() && data.contains(&contract.hash))
+        .map(|contract| contract.event.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use super::*;
+
+    const PAIR_HASH: &str = "0846e67a3309a78522b41bcbe08ed833f8ba472868a1da8fe5fa5cb131f94bcd";
+    const MARKET_HASH: &str = "4b15cdbc606589ebfdcdb3f8f81e2a2a0d44298e3d1faf0743d2be2d90d8cb6b";
+
+    fn watched() -> Vec<WatchedContract> {
+        vec![
+            WatchedContract::new(
+                PAIR_HASH,
+                BotEvent::TradeExecuted {
+                    pair: "CD_LONG-WCSPR LP".to_string(),
+                },
+            ),
+            WatchedContract::new(
+                MARKET_HASH,
+                BotEvent::PriceChanged {
+                    token: "Market".to_string(),
+                },
+            ),
+        ]
+    }
+
+    fn processed(hash: &str) -> String {
+        format!(
+            r#"{{"TransactionProcessed":{{"execution_result":{{"Version2":{{"effects":[{{"key":"hash-{}","kind":"Identity"}}]}}}}}}}}"#,
+            hash
+        )
+    }
+
+    /// Serves each body to one connection, then stops listening.
+    /// Request lines are reported through the returned receiver.
+    fn serve(bodies: Vec<String>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/events", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            for body in bodies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 1024];
+                let read = stream.read(&mut request).unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]).to_string();
+                let _ = requests.send(request.lines().next().unwrap_or_default().to_string());
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        (url, received)
+    }
+
+    #[test]
+    fn test_parser_joins_data_lines_and_tracks_id() {
+        let mut parser = SseParser::default();
+        assert_eq!(parser.push_line(":keepalive"), None);
+        assert_eq!(parser.push_line("id: 7"), None);
+        assert_eq!(parser.push_line("data: {\"a\":"), None);
+        assert_eq!(parser.push_line("data:1}\r"), None);
+        assert_eq!(
+            parser.push_line(""),
+            Some(SseMessage {
+                id: Some(7),
+                data: "{\"a\":\n1}".to_string(),
+            })
+        );
+        assert_eq!(parser.push_line(""), None);
+    }
+
+    #[test]
+    fn test_events_for_processed_transactions_only() {
+        let watched = watched();
+        assert_eq!(
+            events_for(&processed(PAIR_HASH), &watched),
+            vec![BotEvent::TradeExecuted {
+                pair: "CD_LONG-WCSPR LP".to_string()
+            }]
+        );
+        assert_eq!(
+            events_for(&processed(&MARKET_HASH.to_uppercase()), &watched),
+            vec![BotEvent::PriceChanged {
+                token: "Market".to_string()
+            }]
+        );
+        let accepted = format!(
+            r#"{{"TransactionAccepted":{{"target":"hash-{}"}}}}"#,
+            PAIR_HASH
+        );
+        assert!(events_for(&accepted, &watched).is_empty());
+        assert!(events_for(r#"{"ApiVersion":"2.0.0"}"#, &watched).is_empty());
+        assert!(events_for(&processed(&"ab".repeat(32)), &watched).is_empty());
+        assert!(events_for("not json", &watched).is_empty());
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_max_and_resets() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_source_reads_stream_and_resumes_after_reconnect() {
+        let first = format!(
+            "data: {{\"ApiVersion\":\"2.0.0\"}}\n\nid: 3\ndata: {}\n\n",
+            processed(PAIR_HASH)
+        );
+        let second = format!("id: 4\ndata: {}\n\n", processed(MARKET_HASH));
+        let (url, requests) = serve(vec![first, second]);
+
+        let backoff = Backoff::new(Duration::from_millis(10), Duration::from_millis(50));
+        let mut source = SseEventSource::new(url, watched(), backoff);
+
+        assert_eq!(
+            source.next_event(),
+            Some(BotEvent::TradeExecuted {
+                pair: "CD_LONG-WCSPR LP".to_string()
+            })
+        );
+        assert_eq!(
+            source.next_event(),
+            Some(BotEvent::PriceChanged {
+                token: "Market".to_string()
+            })
+        );
+        assert_eq!(requests.recv().unwrap(), "GET /events HTTP/1.1");
+        assert_eq!(
+            requests.recv().unwrap(),
+            "GET /events?start_from=4 HTTP/1.1"
+        );
+    }
+}
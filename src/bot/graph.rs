@@ -0,0 +1,285 @@
+use odra::casper_types::U256;
+
+use crate::bot::{
+    data::PoolReserves,
+    path::{Path, Token, MAX_HOPS},
+    price::u256_to_f64,
+};
+
+/// A constant-product pool between two tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pool {
+    pub tokens: (Token, Token),
+    /// Reserves of `tokens`, in the same order.
+    pub reserves: (U256, U256),
+}
+
+impl Pool {
+    /// `(reserve_in, reserve_out)` of a swap from `token_in` to `token_out`,
+    /// if the pool trades them.
+    fn reserves(&self, token_in: Token, token_out: Token) -> Option<(U256, U256)> {
+        let (reserve0, reserve1) = self.reserves;
+        match self.tokens {
+            tokens if tokens == (token_in, token_out) => Some((reserve0, reserve1)),
+            tokens if tokens == (token_out, token_in) => Some((reserve1, reserve0)),
+            _ => None,
+        }
+    }
+
+    /// The token at the other end of the pool from `token`, if it trades it.
+    fn other(&self, token: Token) -> Option<Token> {
+        match self.tokens {
+            (token0, token1) if token0 == token => Some(token1),
+            (token0, token1) if token1 == token => Some(token0),
+            _ => None,
+        }
+    }
+}
+
+/// The pools the bot can trade through, with the tokens as nodes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteGraph {
+    pools: Vec<Pool>,
+}
+
+impl RouteGraph {
+    pub fn new(pools: Vec<Pool>) -> Self {
+        let mut graph = Self::default();
+        pools.into_iter().for_each(|pool| graph.add(pool));
+        graph
+    }
+
+    /// Adds a pool, unless the graph already has one for its tokens.
+    pub fn add(&mut self, pool: Pool) {
+        let (token0, token1) = pool.tokens;
+        if token0 != token1 && self.reserves(token0, token1).is_none() {
+            self.pools.push(pool);
+        }
+    }
+
+    pub fn pools(&self) -> &[Pool] {
+        &self.pools
+    }
+
+    fn reserves(&self, token_in: Token, token_out: Token) -> Option<(U256, U256)> {
+        self.pools
+            .iter()
+            .find_map(|pool| pool.reserves(token_in, token_out))
+    }
+
+    /// `(reserve_in, reserve_out)` for every hop of `path`, or `None` if a hop
+    /// has no pool.
+    pub fn hops(&self, path: Path) -> Option<Vec<(U256, U256)>> {
+        path.pairs()
+            .map(|(token_in, token_out)| self.reserves(token_in, token_out))
+            .collect()
+    }
+
+    /// Output of `path` per unit of input at the current pool prices, before
+    /// fees. `None` if a hop has no pool or an empty one.
+    pub fn spot_rate(&self, path: Path) -> Option<f64> {
+        self.hops(path)?
+            .into_iter()
+            .try_fold(1.0, |rate, (reserve_in, reserve_out)| {
+                (!reserve_in.is_zero())
+                    .then(|| rate * u256_to_f64(reserve_out) / u256_to_f64(reserve_in))
+            })
+    }
+
+    /// Every route of one to `max_hops` hops (at most `MAX_HOPS`) that visits
+    /// no token twice, except for cycles back to the token they start from,
+    /// and trades through no pool twice.
+    pub fn routes(&self, max_hops: usize) -> Vec<Path> {
+        let max_hops = max_hops.min(MAX_HOPS);
+        let mut routes = vec![];
+        for token in self.tokens() {
+            self.extend(Path::new(&[token]), max_hops, &mut routes);
+        }
+        routes
+    }
+
+    /// The market's tokens, then every other token the pools trade.
+    fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Token::ALL.to_vec();
+        for pool in &self.pools {
+            for token in [pool.tokens.0, pool.tokens.1] {
+                if !tokens.contains(&token) {
+                    tokens.push(token);
+                }
+            }
+        }
+        tokens
+    }
+
+    /// The reserves of the LONG/wCSPR and wCSPR/SHORT pools, if the graph has both.
+    pub fn market_reserves(&self) -> Option<PoolReserves> {
+        let (long, wcspr_long) = self.reserves(Token::Long, Token::Wcspr)?;
+        let (wcspr_short, short) = self.reserves(Token::Wcspr, Token::Short)?;
+        Some(PoolReserves {
+            long,
+            wcspr_long,
+            wcspr_short,
+            short,
+        })
+    }
+
+    fn extend(&self, path: Path, max_hops: usize, routes: &mut Vec<Path>) {
+        let tokens = path.tokens();
+        let (Some(&start), Some(&last)) = (tokens.first(), tokens.last()) else {
+            return;
+        };
+        if path.hops() == max_hops {
+            return;
+        }
+        for next in self.pools.iter().filter_map(|pool| pool.other(last)) {
+            if path.shares_pool_with(Path::new(&[last, next])) {
+                continue;
+            }
+            if next == start {
+                routes.push(path.then(next));
+            } else if !tokens.contains(&next) {
+                routes.push(path.then(next));
+                self.extend(path.then(next), max_hops, routes);
+            }
+        }
+    }
+}
+
+/// The LONG/wCSPR and wCSPR/SHORT pools.
+impl From<&PoolReserves> for RouteGraph {
+    fn from(reserves: &PoolReserves) -> Self {
+        Self::new(vec![
+            Pool {
+                tokens: (Token::Long, Token::Wcspr),
+                reserves: (reserves.long, reserves.wcspr_long),
+            },
+            Pool {
+                tokens: (Token::Wcspr, Token::Short),
+                reserves: (reserves.wcspr_short, reserves.short),
+            },
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u64 = 1_000_000_000;
+
+    fn pool(tokens: (Token, Token), reserve0: u64, reserve1: u64) -> Pool {
+        Pool {
+            tokens,
+            reserves: (U256::from(reserve0) * ONE, U256::from(reserve1) * ONE),
+        }
+    }
+
+    /// The LONG/wCSPR and wCSPR/SHORT pools plus a direct LONG/SHORT one.
+    fn with_long_short() -> RouteGraph {
+        RouteGraph::new(vec![
+            pool((Token::Long, Token::Wcspr), 1_000, 1_000),
+            pool((Token::Wcspr, Token::Short), 1_000, 1_000),
+            pool((Token::Long, Token::Short), 1_000, 1_200),
+        ])
+    }
+
+    #[test]
+    fn test_routes_through_the_wcspr_pools() {
+        let reserves = PoolReserves {
+            long: U256::one(),
+            wcspr_long: U256::one(),
+            wcspr_short: U256::one(),
+            short: U256::one(),
+        };
+        let graph = RouteGraph::from(&reserves);
+        assert_eq!(
+            graph.routes(MAX_HOPS),
+            vec![
+                Path::WcsprLong,
+                Path::WcsprShort,
+                Path::LongWcspr,
+                Path::LongWcsprShort,
+                Path::ShortWcspr,
+                Path::ShortWcsprLong,
+            ]
+        );
+        assert_eq!(graph.routes(1).len(), 4);
+    }
+
+    #[test]
+    fn test_routes_through_a_new_pool() {
+        let graph = with_long_short();
+        let routes = graph.routes(2);
+        assert!(routes.contains(&"LongShort".parse().unwrap()));
+        assert!(routes.contains(&"WcsprLongShort".parse().unwrap()));
+        // Back and forth through a single pool is no route
+        assert!(!routes.contains(&"WcsprLongWcspr".parse().unwrap()));
+
+        let cycles = graph
+            .routes(3)
+            .into_iter()
+            .filter(|path| path.token_in() == path.token_out())
+            .collect::<Vec<_>>();
+        assert_eq!(cycles.len(), 6);
+        assert!(cycles.contains(&"WcsprLongShortWcspr".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_routes_through_tokens_of_other_markets() {
+        let other = Token::Other(odra_test::env().get_account(1));
+        let graph = RouteGraph::new(vec![
+            pool((Token::Long, other), 1_000, 1_000),
+            pool((other, Token::Short), 1_000, 1_000),
+        ]);
+        let routes = graph.routes(2);
+        assert!(routes.contains(&Path::new(&[Token::Long, other, Token::Short])));
+        assert!(routes.contains(&Path::new(&[other, Token::Short])));
+        assert_eq!(graph.market_reserves(), None);
+    }
+
+    #[test]
+    fn test_market_reserves_orient_the_wcspr_pools() {
+        let graph = RouteGraph::new(vec![
+            pool((Token::Short, Token::Wcspr), 300, 400),
+            pool((Token::Wcspr, Token::Long), 200, 100),
+        ]);
+        let reserves = graph.market_reserves().unwrap();
+        assert_eq!(
+            (reserves.long, reserves.wcspr_long),
+            (U256::from(100) * ONE, U256::from(200) * ONE)
+        );
+        assert_eq!(
+            (reserves.wcspr_short, reserves.short),
+            (U256::from(400) * ONE, U256::from(300) * ONE)
+        );
+    }
+
+    #[test]
+    fn test_hops_orient_the_reserves() {
+        let graph = with_long_short();
+        let path = "ShortLongWcspr".parse().unwrap();
+        assert_eq!(
+            graph.hops(path),
+            Some(vec![
+                (U256::from(1_200) * ONE, U256::from(1_000) * ONE),
+                (U256::from(1_000) * ONE, U256::from(1_000) * ONE),
+            ])
+        );
+        assert_eq!(graph.spot_rate(path), Some(1_000.0 / 1_200.0));
+
+        let without = RouteGraph::new(vec![pool((Token::Long, Token::Wcspr), 1, 1)]);
+        assert_eq!(without.hops(Path::WcsprShort), None);
+        assert_eq!(without.hops(Path::Empty), Some(vec![]));
+    }
+
+    #[test]
+    fn test_add_keeps_the_first_pool_of_a_pair() {
+        let mut graph = with_long_short();
+        graph.add(pool((Token::Short, Token::Long), 1, 1));
+        assert_eq!(graph.pools().len(), 3);
+        assert_eq!(
+            graph.pools()[2],
+            pool((Token::Long, Token::Short), 1_000, 1_200)
+        );
+    }
+}
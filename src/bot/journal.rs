@@ -0,0 +1,319 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+
+use chrono::{DateTime, Utc};
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{data::PriceData, path::Path, price::Gain, serde_u256};
+
+/// Version of the record layout. Bump it when a field is removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// What happened to an arbitrage opportunity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    /// No trade size beat the transaction costs.
+    NoProfitableSize,
+    /// The router could not quote the swap.
+    QuoteFailed,
+    /// The quoted gain was below the configured minimum.
+    BelowMinGain,
+    /// The gain at the slippage bound was below the configured minimum.
+    WorstCaseBelowMinGain,
+    /// Shutdown was requested before the swap was sent.
+    ShutdownSkipped,
+    /// The swap reached the chain after its deadline.
+    DeadlineExpired,
+    /// A hard risk limit stopped the trade or its top-up.
+    RiskLimited,
+    /// The swap, mint or redeem went through.
+    Executed,
+    /// Funding or the swap itself failed; see `error`.
+    Failed,
+}
+
+/// A deposit into or withdrawal from the market, journaled like a swap along the
+/// path it stands in for: wCSPR to the position for a mint, back for a redeem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketCall {
+    Mint,
+    Redeem,
+}
+
+/// Prices at evaluation time, in CSPR (wCSPR in USD). Lossy, for auditing only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceSnapshot {
+    pub long_price: f64,
+    pub short_price: f64,
+    pub wcspr_price: f64,
+    pub long_fair_price: f64,
+    pub short_fair_price: f64,
+}
+
+impl From<&PriceData> for PriceSnapshot {
+    fn from(data: &PriceData) -> Self {
+        Self {
+            long_price: data.long_price.to_f64(),
+            short_price: data.short_price.to_f64(),
+            wcspr_price: data.wcspr_price.to_f64(),
+            long_fair_price: data.long_fair_price.to_f64(),
+            short_fair_price: data.short_fair_price.to_f64(),
+        }
+    }
+}
+
+/// Amounts known once the evaluation got far enough. Amounts are exact, in motes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TradeAmounts {
+    #[serde(default, with = "serde_u256::option")]
+    pub quoted_amount_in: Option<U256>,
+    #[serde(default, with = "serde_u256::option")]
+    pub quoted_amount_out: Option<U256>,
+    #[serde(default, with = "serde_u256::option")]
+    pub actual_amount_in: Option<U256>,
+    #[serde(default, with = "serde_u256::option")]
+    pub actual_amount_out: Option<U256>,
+    #[serde(default)]
+    pub expected_gain: Option<Gain>,
+    /// Gain if the swap fills at its slippage bound.
+    #[serde(default)]
+    pub worst_case_gain: Option<Gain>,
+    #[serde(default)]
+    pub actual_gain: Option<Gain>,
+    /// Gas limit set for the swap, in motes.
+    #[serde(default)]
+    pub gas: Option<u64>,
+    /// Fee the market charged on a mint or redeem, in wCSPR motes.
+    #[serde(default, with = "serde_u256::option")]
+    pub market_fee: Option<U256>,
+}
+
+/// One line of the journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeRecord {
+    pub schema_version: u32,
+    pub timestamp: DateTime<Utc>,
+    pub dry_run: bool,
+    pub path: Path,
+    /// Set for mints and redeems, which don't swap along `path`.
+    #[serde(default)]
+    pub market_call: Option<MarketCall>,
+    pub outcome: Outcome,
+    pub prices: PriceSnapshot,
+    #[serde(flatten)]
+    pub amounts: TradeAmounts,
+    /// Transaction hash, when the backend reports one.
+    #[serde(default)]
+    pub tx_hash: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl TradeRecord {
+    pub fn new(
+        path: Path,
+        outcome: Outcome,
+        price_data: &PriceData,
+        amounts: TradeAmounts,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            timestamp: Utc::now(),
+            dry_run: false,
+            path,
+            market_call: None,
+            outcome,
+            prices: price_data.into(),
+            amounts,
+            tx_hash: None,
+            error: None,
+        }
+    }
+}
+
+/// Append-only JSONL journal of every evaluated opportunity and executed swap.
+pub struct TradeJournal {
+    file: File,
+    path: String,
+    dry_run: bool,
+}
+
+impl TradeJournal {
+    /// Opens (or creates) the journal at `path`.
+    /// Records are marked as dry runs if `dry_run` is set.
+    pub fn open(path: &str, dry_run: bool) -> Result<Self, Error> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| journal_error(path, e))?;
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| journal_error(path, e))?;
+        Ok(Self {
+            file,
+            path: path.to_string(),
+            dry_run,
+        })
+    }
+
+    /// Appends a record and syncs it to disk.
+    pub fn append(&self, mut record: TradeRecord) -> Result<(), Error> {
+        record.dry_run = self.dry_run;
+        let mut line = serde_json::to_string(&record).map_err(|e| journal_error(&self.path, e))?;
+        line.push('\n');
+        (&self.file)
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| journal_error(&self.path, e))
+    }
+
+    /// Reads all records. A truncated last line (e.g. after a crash) is skipped;
+    /// any other malformed line or a newer schema version is an error.
+    pub fn read(path: &str) -> Result<Vec<TradeRecord>, Error> {
+        let file = File::open(path).map_err(|e| journal_error(path, e))?;
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| journal_error(path, e))?;
+        let last = lines.len();
+        let mut records = vec![];
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = match serde_json::from_str::<TradeRecord>(line) {
+                Ok(record) => record,
+                Err(_) if index + 1 == last => {
+                    tracing::warn!("Skipping truncated last line of journal {}", path);
+                    continue;
+                }
+                Err(e) => return Err(journal_error(path, format!("line {}: {}", index + 1, e))),
+            };
+            if record.schema_version > SCHEMA_VERSION {
+                return Err(journal_error(
+                    path,
+                    format!(
+                        "line {}: unsupported schema version {}",
+                        index + 1,
+                        record.schema_version
+                    ),
+                ));
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+fn journal_error(path: &str, e: impl std::fmt::Display) -> Error {
+    Error::OdraError {
+        message: format!("Trade journal {}: {}", path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_path(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("bot-journal-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("journal.jsonl").to_string_lossy().to_string()
+    }
+
+    fn executed() -> TradeRecord {
+        let price_data = PriceData::from_f64(1.1, 0.9, 0.05, 1.0, 1.0);
+        TradeRecord::new(
+            Path::LongWcspr,
+            Outcome::Executed,
+            &price_data,
+            TradeAmounts {
+                quoted_amount_in: Some(U256::from(1_000_000_000_000u64)),
+                quoted_amount_out: Some(U256::from(1_080_000_000_000u64)),
+                actual_amount_in: Some(U256::from(1_000_000_000_000u64)),
+                actual_amount_out: Some(U256::MAX),
+                expected_gain: Some(Gain::from_cspr(73.0)),
+                worst_case_gain: Some(Gain::from_cspr(52.0)),
+                actual_gain: Some(Gain::from_cspr(-2.5)),
+                gas: Some(8_000_000_000),
+                market_fee: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_append_and_read_round_trip() {
+        let path = journal_path("round-trip");
+        let journal = TradeJournal::open(&path, true).unwrap();
+        let record = executed();
+        journal.append(record.clone()).unwrap();
+        let mut failed = TradeRecord::new(
+            Path::WcsprShort,
+            Outcome::Failed,
+            &PriceData::from_f64(1.0, 0.9, 0.05, 1.0, 1.0),
+            TradeAmounts::default(),
+        );
+        failed.error = Some("swap reverted".to_string());
+        journal.append(failed.clone()).unwrap();
+
+        let records = TradeJournal::read(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].amounts, record.amounts);
+        assert_eq!(records[0].timestamp, record.timestamp);
+        assert!(records[0].dry_run);
+        assert_eq!(records[1].outcome, Outcome::Failed);
+        assert_eq!(records[1].error.as_deref(), Some("swap reverted"));
+    }
+
+    #[test]
+    fn test_amounts_are_exact_decimal_strings() {
+        let json = serde_json::to_value(executed()).unwrap();
+        assert_eq!(json["schema_version"], SCHEMA_VERSION);
+        assert_eq!(json["path"], "LongWcspr");
+        assert_eq!(json["outcome"], "executed");
+        assert_eq!(json["quoted_amount_in"], "1000000000000");
+        assert_eq!(json["actual_amount_out"], U256::MAX.to_string());
+        assert_eq!(json["actual_gain"], "-2500000000");
+        assert_eq!(json["market_call"], serde_json::Value::Null);
+
+        let mut mint = executed();
+        mint.market_call = Some(MarketCall::Mint);
+        mint.amounts.market_fee = Some(U256::from(3_000_000_000u64));
+        let json = serde_json::to_value(mint).unwrap();
+        assert_eq!(json["market_call"], "mint");
+        assert_eq!(json["market_fee"], "3000000000");
+    }
+
+    #[test]
+    fn test_read_skips_truncated_last_line_only() {
+        let path = journal_path("truncated");
+        let journal = TradeJournal::open(&path, false).unwrap();
+        journal.append(executed()).unwrap();
+        (&journal.file)
+            .write_all(b"{\"schema_version\":1,\"times")
+            .unwrap();
+        assert_eq!(TradeJournal::read(&path).unwrap().len(), 1);
+
+        (&journal.file).write_all(b"\n").unwrap();
+        journal.append(executed()).unwrap();
+        assert!(TradeJournal::read(&path).is_err());
+    }
+
+    #[test]
+    fn test_read_rejects_newer_schema() {
+        let path = journal_path("schema");
+        let journal = TradeJournal::open(&path, false).unwrap();
+        let mut record = executed();
+        record.schema_version = SCHEMA_VERSION + 1;
+        journal.append(record).unwrap();
+        journal.append(executed()).unwrap();
+        assert!(TradeJournal::read(&path).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+use std::cell::RefCell;
+use std::fmt::{self, Display};
+use std::fs;
+
+use chrono::{DateTime, Duration, Utc};
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{
+    config::LimitsConfig,
+    price::{motes_to_cspr, Gain},
+    serde_u256,
+};
+
+/// A trade or market deposit counted against the limits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Spend {
+    at: DateTime<Utc>,
+    /// Fair value moved, in motes.
+    #[serde(with = "serde_u256::decimal")]
+    notional: U256,
+    /// Realised gain of a trade, or of a market deposit or withdrawal at fair value.
+    gain: Gain,
+}
+
+/// The limit a trade or deposit would break.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitBreach {
+    TradeNotional {
+        notional: U256,
+        limit: U256,
+    },
+    HourlyNotional {
+        used: U256,
+        notional: U256,
+        limit: U256,
+    },
+    DailyNotional {
+        used: U256,
+        notional: U256,
+        limit: U256,
+    },
+    DailyLoss {
+        loss: Gain,
+        limit: U256,
+    },
+}
+
+impl Display for LimitBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitBreach::TradeNotional { notional, limit } => write!(
+                f,
+                "trade of {:.2} CSPR exceeds the {:.2} CSPR trade limit",
+                motes_to_cspr(*notional),
+                motes_to_cspr(*limit)
+            ),
+            LimitBreach::HourlyNotional {
+                used,
+                notional,
+                limit,
+            } => write!(
+                f,
+                "{:.2} CSPR on top of {:.2} CSPR this hour exceeds the {:.2} CSPR hourly limit",
+                motes_to_cspr(*notional),
+                motes_to_cspr(*used),
+                motes_to_cspr(*limit)
+            ),
+            LimitBreach::DailyNotional {
+                used,
+                notional,
+                limit,
+            } => write!(
+                f,
+                "{:.2} CSPR on top of {:.2} CSPR today exceeds the {:.2} CSPR daily limit",
+                motes_to_cspr(*notional),
+                motes_to_cspr(*used),
+                motes_to_cspr(*limit)
+            ),
+            LimitBreach::DailyLoss { loss, limit } => write!(
+                f,
+                "realised {:.2} CSPR today, at the {:.2} CSPR daily loss limit",
+                loss.to_cspr(),
+                motes_to_cspr(*limit)
+            ),
+        }
+    }
+}
+
+/// Hard notional and loss limits over the last hour and day. Every trade and
+/// deposit is persisted, so restarting the bot does not reset the budget.
+pub struct RiskLimits {
+    config: LimitsConfig,
+    dry_run: bool,
+    spends: RefCell<Vec<Spend>>,
+}
+
+impl RiskLimits {
+    /// Loads the last day of spends from `config.state_path`, if it exists.
+    /// A dry run starts from them but never writes the file.
+    pub fn open(config: &LimitsConfig, dry_run: bool) -> Result<Self, Error> {
+        let path = &config.state_path;
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent).map_err(|e| limits_error(path, e))?;
+            }
+        }
+        let spends = match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| limits_error(path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(limits_error(path, e)),
+        };
+        Ok(Self {
+            config: config.clone(),
+            dry_run,
+            spends: RefCell::new(spends),
+        })
+    }
+
+    /// Checks a trade of `notional` motes against every limit.
+    pub fn check_trade(&self, now: DateTime<Utc>, notional: U256) -> Result<(), LimitBreach> {
+        let limit = U256::from(self.config.max_trade_notional);
+        if notional > limit {
+            return Err(LimitBreach::TradeNotional { notional, limit });
+        }
+        self.check_budget(now, notional)
+    }
+
+    /// Checks a market deposit or withdrawal of `notional` motes against the
+    /// hourly, daily and loss limits.
+    pub fn check_deposit(&self, now: DateTime<Utc>, notional: U256) -> Result<(), LimitBreach> {
+        self.check_budget(now, notional)
+    }
+
+    /// Counts a trade, deposit or withdrawal against the limits and persists it.
+    pub fn record(&self, now: DateTime<Utc>, notional: U256, gain: Gain) -> Result<(), Error> {
+        let mut spends = self.spends.borrow_mut();
+        spends.retain(|spend| now - spend.at < Duration::days(1));
+        spends.push(Spend {
+            at: now,
+            notional,
+            gain,
+        });
+        if self.dry_run {
+            return Ok(());
+        }
+        let path = &self.config.state_path;
+        let content = serde_json::to_string(&*spends).map_err(|e| limits_error(path, e))?;
+        // Write to a temporary file first, so a crash never leaves a truncated state
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, content)
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(|e| limits_error(path, e))
+    }
+
+    fn check_budget(&self, now: DateTime<Utc>, notional: U256) -> Result<(), LimitBreach> {
+        let spends = self.spends.borrow();
+        let since = |window: Duration| spends.iter().filter(move |spend| now - spend.at < window);
+
+        let loss = since(Duration::days(1)).fold(Gain::zero(), |net, spend| net + spend.gain);
+        let limit = U256::from(self.config.max_daily_loss);
+        if loss.is_negative() && loss.magnitude() >= limit {
+            return Err(LimitBreach::DailyLoss { loss, limit });
+        }
+
+        let used = since(Duration::hours(1)).fold(U256::zero(), |sum, spend| sum + spend.notional);
+        let limit = U256::from(self.config.max_hourly_notional);
+        if used + notional > limit {
+            return Err(LimitBreach::HourlyNotional {
+                used,
+                notional,
+                limit,
+            });
+        }
+
+        let used = since(Duration::days(1)).fold(U256::zero(), |sum, spend| sum + spend.notional);
+        let limit = U256::from(self.config.max_daily_notional);
+        if used + notional > limit {
+            return Err(LimitBreach::DailyNotional {
+                used,
+                notional,
+                limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn limits_error(path: &str, e: impl std::fmt::Display) -> Error {
+    Error::OdraError {
+        message: format!("Risk limits state {}: {}", path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cspr(amount: u64) -> U256 {
+        U256::from(amount) * U256::from(1_000_000_000u64)
+    }
+
+    fn config(name: &str) -> LimitsConfig {
+        let dir = std::env::temp_dir().join(format!("bot-limits-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        LimitsConfig {
+            max_trade_notional: 1_000_000_000_000,  // 1_000 CSPR
+            max_hourly_notional: 2_000_000_000_000, // 2_000 CSPR
+            max_daily_notional: 3_000_000_000_000,  // 3_000 CSPR
+            max_daily_loss: 100_000_000_000,        // 100 CSPR
+            state_path: dir.join("limits.json").to_string_lossy().to_string(),
+        }
+    }
+
+    #[test]
+    fn test_trade_notional_limit() {
+        let limits = RiskLimits::open(&config("trade"), false).unwrap();
+        let now = Utc::now();
+        assert!(limits.check_trade(now, cspr(1_000)).is_ok());
+        assert!(matches!(
+            limits.check_trade(now, cspr(1_001)),
+            Err(LimitBreach::TradeNotional { .. })
+        ));
+        // Deposits are only bound by the hourly and daily budgets
+        assert!(limits.check_deposit(now, cspr(1_500)).is_ok());
+    }
+
+    #[test]
+    fn test_hourly_and_daily_notional_roll_over() {
+        let limits = RiskLimits::open(&config("budget"), false).unwrap();
+        let start = Utc::now();
+        limits.record(start, cspr(1_000), Gain::zero()).unwrap();
+        limits.record(start, cspr(800), Gain::zero()).unwrap();
+        assert!(matches!(
+            limits.check_trade(start, cspr(500)),
+            Err(LimitBreach::HourlyNotional { .. })
+        ));
+
+        let later = start + Duration::minutes(61);
+        assert!(limits.check_trade(later, cspr(500)).is_ok());
+        limits.record(later, cspr(1_000), Gain::zero()).unwrap();
+        assert!(matches!(
+            limits.check_trade(later, cspr(500)),
+            Err(LimitBreach::DailyNotional { .. })
+        ));
+        assert!(limits
+            .check_trade(start + Duration::hours(25), cspr(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_daily_loss_blocks_trades_and_deposits() {
+        let limits = RiskLimits::open(&config("loss"), false).unwrap();
+        let now = Utc::now();
+        limits
+            .record(now, cspr(100), Gain::from_cspr(-60.0))
+            .unwrap();
+        limits
+            .record(now, cspr(100), Gain::from_cspr(10.0))
+            .unwrap();
+        assert!(limits.check_trade(now, cspr(100)).is_ok());
+
+        limits
+            .record(now, cspr(100), Gain::from_cspr(-50.0))
+            .unwrap();
+        assert!(matches!(
+            limits.check_trade(now, cspr(100)),
+            Err(LimitBreach::DailyLoss { .. })
+        ));
+        assert!(limits.check_deposit(now, cspr(100)).is_err());
+    }
+
+    #[test]
+    fn test_spends_survive_a_restart() {
+        let config = config("restart");
+        let now = Utc::now();
+        let limits = RiskLimits::open(&config, false).unwrap();
+        limits.record(now, cspr(1_900), Gain::zero()).unwrap();
+        drop(limits);
+
+        let limits = RiskLimits::open(&config, false).unwrap();
+        assert!(matches!(
+            limits.check_trade(now, cspr(200)),
+            Err(LimitBreach::HourlyNotional { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dry_run_does_not_persist() {
+        let config = config("dry-run");
+        let limits = RiskLimits::open(&config, true).unwrap();
+        limits
+            .record(Utc::now(), cspr(1_900), Gain::zero())
+            .unwrap();
+        assert!(!std::path::Path::new(&config.state_path).exists());
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use crate::bot::{config::OracleConfig, price::Price};
+
+/// CSPR/USD as reported by the Styks price feed.
+#[derive(Debug, Clone, Copy)]
+pub struct OracleReading {
+    /// TWAP of the feed, if it has one.
+    pub price: Option<Price>,
+    /// Block time of the feed's last update, in seconds.
+    pub updated_at: Option<u64>,
+    /// Block time the feed was read at, in seconds.
+    pub read_at: u64,
+}
+
+/// Why the market's CSPR price can't be trusted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OracleMismatch {
+    /// The feed has no price or was never updated.
+    Missing,
+    /// The feed was last updated too long ago.
+    Stale { age: Duration },
+    /// The market price is too far from the feed's.
+    Diverged { market: f64, oracle: f64 },
+}
+
+impl Display for OracleMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OracleMismatch::Missing => write!(f, "the Styks feed has no price"),
+            OracleMismatch::Stale { age } => {
+                write!(f, "the Styks feed was last updated {:?} ago", age)
+            }
+            OracleMismatch::Diverged { market, oracle } => write!(
+                f,
+                "market price {:.5} USD is {:.2}% off the Styks price {:.5} USD",
+                market,
+                (market - oracle) / oracle * 100.0,
+                oracle
+            ),
+        }
+    }
+}
+
+impl OracleReading {
+    /// Checks the market's CSPR price against this reading.
+    pub fn check(&self, market: &Price, config: &OracleConfig) -> Result<(), OracleMismatch> {
+        let (Some(oracle), Some(updated_at)) = (self.price, self.updated_at) else {
+            return Err(OracleMismatch::Missing);
+        };
+        if oracle.is_zero() {
+            return Err(OracleMismatch::Missing);
+        }
+        let age = Duration::from_secs(self.read_at.saturating_sub(updated_at));
+        if age > config.max_staleness() {
+            return Err(OracleMismatch::Stale { age });
+        }
+        if market.is_zero() || market.deviates_from(&oracle, config.max_deviation_bps) {
+            return Err(OracleMismatch::Diverged {
+                market: market.to_f64(),
+                oracle: oracle.to_f64(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn reading(price: f64, age_secs: u64) -> OracleReading {
+        OracleReading {
+            price: Some(Price::from_f64(price)),
+            updated_at: Some(NOW - age_secs),
+            read_at: NOW,
+        }
+    }
+
+    #[test]
+    fn test_accepts_market_price_within_tolerance() {
+        let config = OracleConfig::default();
+        let market = Price::from_f64(0.0202);
+        assert_eq!(reading(0.02, 60).check(&market, &config), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_diverging_market_price() {
+        let config = OracleConfig::default();
+        let market = Price::from_f64(0.021);
+        assert!(matches!(
+            reading(0.02, 60).check(&market, &config),
+            Err(OracleMismatch::Diverged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rejects_stale_or_missing_feed() {
+        let config = OracleConfig::default();
+        let market = Price::from_f64(0.02);
+        assert_eq!(
+            reading(0.02, 901).check(&market, &config),
+            Err(OracleMismatch::Stale {
+                age: Duration::from_secs(901)
+            })
+        );
+        let missing = OracleReading {
+            price: None,
+            updated_at: Some(NOW),
+            read_at: NOW,
+        };
+        assert_eq!(
+            missing.check(&market, &config),
+            Err(OracleMismatch::Missing)
+        );
+    }
+}
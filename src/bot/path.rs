@@ -1,87 +1,271 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
 use odra::prelude::{Address, Addressable};
 use odra_cli::scenario::Error;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    bot::{config::StrategyConfig, data::PriceData, graph::RouteGraph},
+    contracts::ContractRefs,
+};
+
+/// A token the bot trades: one of the market's, or any other token a pair in
+/// the contracts container trades, by its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Token {
+    Wcspr,
+    Long,
+    Short,
+    /// None of the market's tokens, e.g. a position token of another market.
+    Other(Address),
+}
 
-use crate::{bot::data::PriceData, contracts::ContractRefs};
+impl Token {
+    /// The market's own tokens.
+    pub const ALL: [Token; 3] = [Token::Wcspr, Token::Long, Token::Short];
 
-const DIFF_THRESHOLD: f64 = 2.5f64;
+    /// The market's token at `address`, or `Other` if it is none of them.
+    pub fn at(address: Address, refs: &ContractRefs) -> Result<Token, Error> {
+        for token in Token::ALL {
+            if token.address(refs)? == address {
+                return Ok(token);
+            }
+        }
+        Ok(Token::Other(address))
+    }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Path {
-    LongWcsprShort,
-    ShortWcsprLong,
-    LongWcspr,
-    ShortWcspr,
-    WcsprLong,
-    WcsprShort,
-    Empty,
+    pub fn address(self, refs: &ContractRefs) -> Result<Address, Error> {
+        match self {
+            Token::Wcspr => Ok(refs.wcspr()?.address()),
+            Token::Long => Ok(refs.long()?.address()),
+            Token::Short => Ok(refs.short()?.address()),
+            Token::Other(address) => Ok(address),
+        }
+    }
 }
 
-impl From<&PriceData> for Path {
-    fn from(data: &PriceData) -> Self {
-        Self::calc(data)
+/// The token's name in paths, e.g. `Long`, or its address in brackets, e.g.
+/// `[hash-4b15...]`.
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Wcspr => write!(f, "Wcspr"),
+            Token::Long => write!(f, "Long"),
+            Token::Short => write!(f, "Short"),
+            Token::Other(address) => write!(f, "[{}]", address.to_formatted_string()),
+        }
     }
 }
 
+/// A token by its name or bracketed address, at the start of `s`, and the rest of `s`.
+fn split_token(s: &str) -> Option<(Token, &str)> {
+    if let Some(rest) = s.strip_prefix('[') {
+        let (address, rest) = rest.split_once(']')?;
+        return Some((Token::Other(address.parse().ok()?), rest));
+    }
+    Token::ALL.into_iter().find_map(|token| {
+        let rest = s.strip_prefix(token.to_string().as_str())?;
+        Some((token, rest))
+    })
+}
+
+/// The most hops a path can take.
+pub const MAX_HOPS: usize = 3;
+
+/// The tokens a swap goes through, from the one paid to the one received.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Path {
+    tokens: [Token; MAX_HOPS + 1],
+    len: usize,
+}
+
+/// The paths through the LONG/wCSPR and wCSPR/SHORT pools.
+#[allow(non_upper_case_globals)]
 impl Path {
-    fn calc(data: &PriceData) -> Self {
-        let long_diff = data.long_diff.abs();
-        let short_diff = data.short_diff.abs();
-        let long_price_diff = data.long_price - data.long_fair_price;
-        let short_price_diff = data.short_price - data.short_fair_price;
-
-        if long_price_diff > 0.0f64
-            && short_price_diff < 0.0f64
-            && long_diff > DIFF_THRESHOLD
-            && short_diff > DIFF_THRESHOLD
-        {
-            Path::LongWcsprShort
-        } else if short_price_diff > 0.0f64
-            && long_price_diff < 0.0f64
-            && long_diff > DIFF_THRESHOLD
-            && short_diff > DIFF_THRESHOLD
-        {
-            Path::ShortWcsprLong
-        } else if long_price_diff > 0.0f64 && long_diff > DIFF_THRESHOLD {
-            Path::LongWcspr
-        } else if short_price_diff > 0.0f64 && short_diff > DIFF_THRESHOLD {
-            Path::ShortWcspr
-        } else if long_price_diff < 0.0f64 && long_diff > DIFF_THRESHOLD {
-            Path::WcsprLong
-        } else if short_price_diff < 0.0f64 && short_diff > DIFF_THRESHOLD {
-            Path::WcsprShort
-        } else {
-            Path::Empty
+    pub const LongWcsprShort: Path = Path::new(&[Token::Long, Token::Wcspr, Token::Short]);
+    pub const ShortWcsprLong: Path = Path::new(&[Token::Short, Token::Wcspr, Token::Long]);
+    pub const LongWcspr: Path = Path::new(&[Token::Long, Token::Wcspr]);
+    pub const ShortWcspr: Path = Path::new(&[Token::Short, Token::Wcspr]);
+    pub const WcsprLong: Path = Path::new(&[Token::Wcspr, Token::Long]);
+    pub const WcsprShort: Path = Path::new(&[Token::Wcspr, Token::Short]);
+    pub const Empty: Path = Path::new(&[]);
+}
+
+impl Path {
+    /// Panics if `tokens` takes more than `MAX_HOPS` hops.
+    pub const fn new(tokens: &[Token]) -> Self {
+        assert!(tokens.len() <= MAX_HOPS + 1, "Path is too long");
+        let mut path = Self {
+            tokens: [Token::Wcspr; MAX_HOPS + 1],
+            len: tokens.len(),
+        };
+        let mut i = 0;
+        while i < tokens.len() {
+            path.tokens[i] = tokens[i];
+            i += 1;
         }
+        path
     }
 
-    pub fn build(&self, refs: &ContractRefs) -> Result<Vec<Address>, Error> {
-        let long_address = refs.long()?.address();
-        let short_address = refs.short()?.address();
-        let wcspr_address = refs.wcspr()?.address();
-        match self {
-            Path::LongWcsprShort => Ok(vec![long_address, wcspr_address, short_address]),
-            Path::ShortWcsprLong => Ok(vec![short_address, wcspr_address, long_address]),
-            Path::LongWcspr => Ok(vec![long_address, wcspr_address]),
-            Path::ShortWcspr => Ok(vec![short_address, wcspr_address]),
-            Path::WcsprLong => Ok(vec![wcspr_address, long_address]),
-            Path::WcsprShort => Ok(vec![wcspr_address, short_address]),
-            Path::Empty => Ok(vec![]),
+    /// Every path worth trading through `graph` for its pool prices: those
+    /// whose rate beats the fair values of their end tokens by more than the
+    /// threshold, most mispriced first. Several may apply at once.
+    pub fn candidates(graph: &RouteGraph, data: &PriceData, config: &StrategyConfig) -> Vec<Self> {
+        let threshold = config.diff_threshold_bps() as f64 / 10_000.0;
+        let mut edges = graph
+            .routes(config.max_hops)
+            .into_iter()
+            .filter_map(|path| {
+                let edge = path.edge(graph, data)?;
+                (edge > threshold).then_some((path, edge))
+            })
+            .collect::<Vec<_>>();
+        edges.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        edges.into_iter().map(|(path, _)| path).collect()
+    }
+
+    /// How much more the path's spot output is worth at fair value than its
+    /// input, as a fraction. A screen before the exact sizing, so f64 precision
+    /// is plenty.
+    fn edge(&self, graph: &RouteGraph, data: &PriceData) -> Option<f64> {
+        let rate = graph.spot_rate(*self)?;
+        let fair_in = data.fair_price(self.token_in()?).to_f64();
+        let fair_out = data.fair_price(self.token_out()?).to_f64();
+        if fair_in == 0.0 {
+            return None;
         }
+        Some(rate * fair_out / fair_in - 1.0)
+    }
+
+    pub fn build(&self, refs: &ContractRefs) -> Result<Vec<Address>, Error> {
+        self.tokens()
+            .iter()
+            .map(|token| token.address(refs))
+            .collect()
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens[..self.len]
+    }
+
+    pub fn hops(&self) -> usize {
+        self.len.saturating_sub(1)
+    }
+
+    /// The token paid, unless the path has no hops.
+    pub fn token_in(&self) -> Option<Token> {
+        (self.hops() > 0).then_some(self.tokens[0])
+    }
+
+    /// The token received, unless the path has no hops.
+    pub fn token_out(&self) -> Option<Token> {
+        (self.hops() > 0).then_some(self.tokens[self.len.saturating_sub(1)])
+    }
+
+    /// `(token_in, token_out)` of every hop.
+    pub fn pairs(&self) -> impl Iterator<Item = (Token, Token)> + '_ {
+        self.tokens().windows(2).map(|pair| (pair[0], pair[1]))
+    }
+
+    /// The path continued to `token`. Panics if it already takes `MAX_HOPS` hops.
+    pub fn then(self, token: Token) -> Self {
+        assert!(self.len <= MAX_HOPS, "Path is too long");
+        let mut path = self;
+        path.tokens[path.len] = token;
+        path.len += 1;
+        path
     }
 
     pub fn is_multi_hop(&self) -> bool {
-        matches!(self, Path::LongWcsprShort | Path::ShortWcsprLong)
+        self.hops() > 1
+    }
+
+    /// Whether both paths trade through a common pool, so a swap along one
+    /// moves the reserves the other was sized against.
+    pub fn shares_pool_with(&self, other: Path) -> bool {
+        self.pairs()
+            .any(|(a, b)| other.pairs().any(|pool| pool == (a, b) || pool == (b, a)))
+    }
+}
+
+/// The token names joined, e.g. `LongWcsprShort`, or `Empty`.
+impl Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.len == 0 {
+            return write!(f, "Empty");
+        }
+        self.tokens()
+            .iter()
+            .try_for_each(|token| write!(f, "{}", token))
+    }
+}
+
+impl fmt::Debug for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl FromStr for Path {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "Empty" {
+            return Ok(Path::Empty);
+        }
+        let mut tokens = vec![];
+        let mut rest = s;
+        while !rest.is_empty() {
+            let (token, remaining) =
+                split_token(rest).ok_or_else(|| format!("invalid path: {}", s))?;
+            rest = remaining;
+            tokens.push(token);
+        }
+        if tokens.len() > MAX_HOPS + 1 {
+            return Err(format!("path of more than {} hops: {}", MAX_HOPS, s));
+        }
+        Ok(Path::new(&tokens))
+    }
+}
+
+/// Serialized by name, e.g. `"LongWcspr"`.
+impl Serialize for Path {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Path {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::bot::data::PriceData;
+    use odra::casper_types::U256;
+
+    use crate::bot::data::{PoolReserves, PriceData};
 
     use super::*;
 
+    /// Candidates through pools priced at the DEX prices of `data`.
+    fn candidates(data: &PriceData) -> Vec<Path> {
+        let position = U256::from(1_000_000_000_000_000u64);
+        let graph = RouteGraph::from(&PoolReserves {
+            long: position,
+            wcspr_long: data.long_price.quote(position),
+            wcspr_short: data.short_price.quote(position),
+            short: position,
+        });
+        Path::candidates(&graph, data, &StrategyConfig::default())
+    }
+
     #[test]
-    fn test_path_calc_long_overvalued_short_undervalued() {
+    fn test_path_candidates_long_overvalued_short_undervalued() {
         // Long is overvalued (100 > 90), short is undervalued (60 < 77)
         // Both diffs > 2.5% threshold
         let long_price = 100.0;
@@ -89,18 +273,21 @@ mod tests {
         let short_price = 60.0;
         let short_fair_price = 77.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::LongWcsprShort);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::LongWcsprShort, Path::WcsprShort, Path::LongWcspr]
+        );
     }
 
     #[test]
-    fn test_path_calc_short_overvalued_long_undervalued() {
+    fn test_path_candidates_short_overvalued_long_undervalued() {
         // Short is overvalued (100 > 90), long is undervalued (60 < 77)
         // Both diffs > 2.5% threshold
         let long_price = 60.0;
@@ -108,148 +295,163 @@ mod tests {
         let short_price = 100.0;
         let short_fair_price = 90.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::ShortWcsprLong);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::ShortWcsprLong, Path::WcsprLong, Path::ShortWcspr]
+        );
     }
 
     #[test]
-    fn test_path_calc_long_overvalued_only() {
-        // Long is overvalued (100 > 90), short diff is below threshold
+    fn test_path_candidates_long_overvalued_only() {
+        // Long is overvalued (100 > 90), short diff is below threshold, but
+        // adds to the edge of selling long for short
         let long_price = 100.0;
         let long_fair_price = 90.0;
         let short_price = 50.0;
         let short_fair_price = 50.5;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::LongWcspr);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::LongWcsprShort, Path::LongWcspr]
+        );
     }
 
     #[test]
-    fn test_path_calc_short_overvalued_only() {
-        // Short is overvalued (100 > 90), long diff is below threshold
+    fn test_path_candidates_short_overvalued_only() {
+        // Short is overvalued (100 > 90), long diff is below threshold, but
+        // adds to the edge of selling short for long
         let long_price = 50.0;
         let long_fair_price = 50.5;
         let short_price = 100.0;
         let short_fair_price = 90.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::ShortWcspr);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::ShortWcsprLong, Path::ShortWcspr]
+        );
     }
 
     #[test]
-    fn test_path_calc_long_undervalued_only() {
-        // Long is undervalued (60 < 77), short diff is below threshold
+    fn test_path_candidates_long_undervalued_only() {
+        // Long is undervalued (60 < 77), short diff is below threshold and
+        // takes from the edge of buying long with short
         let long_price = 60.0;
         let long_fair_price = 77.0;
         let short_price = 50.0;
         let short_fair_price = 50.5;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::WcsprLong);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::WcsprLong, Path::ShortWcsprLong]
+        );
     }
 
     #[test]
-    fn test_path_calc_short_undervalued_only() {
-        // Short is undervalued (60 < 77), long diff is below threshold
+    fn test_path_candidates_short_undervalued_only() {
+        // Short is undervalued (60 < 77), long diff is below threshold and
+        // takes from the edge of buying short with long
         let long_price = 50.0;
         let long_fair_price = 50.5;
         let short_price = 60.0;
         let short_fair_price = 77.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::WcsprShort);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::WcsprShort, Path::LongWcsprShort]
+        );
     }
 
     #[test]
-    fn test_path_calc_empty_no_significant_diff() {
+    fn test_path_candidates_empty_no_significant_diff() {
         // Both prices are close to fair prices (diffs < 2.5% threshold)
         let long_price = 100.0;
         let long_fair_price = 100.5;
         let short_price = 50.0;
         let short_fair_price = 50.5;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::Empty);
+        assert!(candidates(&data).is_empty());
     }
 
     #[test]
-    fn test_path_calc_both_overvalued() {
-        // Both are overvalued with significant diffs
-        // Since the paired condition (long > 0 && short < 0) fails,
-        // it falls through to check long_price_diff > 0, returning LongWcspr
+    fn test_path_candidates_both_overvalued() {
+        // Both are overvalued with significant diffs, so both can be sold
         let long_price = 100.0;
         let long_fair_price = 90.0;
         let short_price = 100.0;
         let short_fair_price = 90.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::LongWcspr);
+        assert_eq!(candidates(&data), vec![Path::LongWcspr, Path::ShortWcspr]);
     }
 
     #[test]
-    fn test_path_calc_both_undervalued() {
-        // Both are undervalued with significant diffs
-        // Since the paired condition (short > 0 && long < 0) fails,
-        // it falls through to check long_price_diff < 0, returning WcsprLong
+    fn test_path_candidates_both_undervalued() {
+        // Both are undervalued with significant diffs, so both can be bought
         let long_price = 60.0;
         let long_fair_price = 77.0;
         let short_price = 60.0;
         let short_fair_price = 77.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::WcsprLong);
+        assert_eq!(candidates(&data), vec![Path::WcsprLong, Path::WcsprShort]);
     }
 
     #[test]
-    fn test_path_calc_threshold_boundary_above() {
+    fn test_path_candidates_threshold_boundary_above() {
         // Test exactly at the threshold boundary (2.5%)
         // Long diff = 11.11% (100/90 = 1.111), short diff = 22.07% (60/77.3 = 0.776)
         let long_price = 100.0;
@@ -257,18 +459,21 @@ mod tests {
         let short_price = 60.0;
         let short_fair_price = 77.3;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::LongWcsprShort);
+        assert_eq!(
+            candidates(&data),
+            vec![Path::LongWcsprShort, Path::WcsprShort, Path::LongWcspr]
+        );
     }
 
     #[test]
-    fn test_path_calc_threshold_boundary_below() {
+    fn test_path_candidates_threshold_boundary_below() {
         // Test just below the threshold (< 2.5%)
         // Long diff = 2.0% (102/100 = 1.02), short diff = 2.0% (51/50 = 1.02)
         let long_price = 102.0;
@@ -276,13 +481,55 @@ mod tests {
         let short_price = 51.0;
         let short_fair_price = 50.0;
         let wcspr_price = 1.0;
-        let data = PriceData::new(
+        let data = PriceData::from_f64(
             long_price,
             short_price,
             wcspr_price,
             long_fair_price,
             short_fair_price,
         );
-        assert_eq!(Path::calc(&data), Path::Empty);
+        assert!(candidates(&data).is_empty());
+    }
+
+    #[test]
+    fn test_shares_pool_with() {
+        assert!(Path::LongWcspr.shares_pool_with(Path::WcsprLong));
+        assert!(Path::LongWcsprShort.shares_pool_with(Path::WcsprShort));
+        assert!(!Path::LongWcspr.shares_pool_with(Path::ShortWcspr));
+        assert!(!Path::WcsprLong.shares_pool_with(Path::WcsprShort));
+        assert!(!Path::Empty.shares_pool_with(Path::LongWcsprShort));
+        let long_short: Path = "LongShort".parse().unwrap();
+        assert!(long_short.shares_pool_with("WcsprShortLong".parse().unwrap()));
+        assert!(!long_short.shares_pool_with(Path::LongWcsprShort));
+    }
+
+    #[test]
+    fn test_path_names_round_trip() {
+        assert_eq!(Path::LongWcsprShort.to_string(), "LongWcsprShort");
+        assert_eq!(Path::Empty.to_string(), "Empty");
+        let cycle = Path::new(&[Token::Wcspr, Token::Long, Token::Short, Token::Wcspr]);
+        assert_eq!("WcsprLongShortWcspr".parse(), Ok(cycle));
+        assert_eq!(
+            serde_json::to_string(&Path::LongWcspr).unwrap(),
+            "\"LongWcspr\""
+        );
+        assert_eq!(
+            serde_json::from_str::<Path>("\"ShortWcsprLong\"").unwrap(),
+            Path::ShortWcsprLong
+        );
+        assert!("LongDoge".parse::<Path>().is_err());
+        assert!("WcsprLongShortWcsprLong".parse::<Path>().is_err());
+    }
+
+    #[test]
+    fn test_other_tokens_round_trip_by_address() {
+        let address = odra_test::env().get_account(1);
+        let other = Token::Other(address);
+        let path = Path::new(&[Token::Long, other, Token::Short]);
+        let name = format!("Long[{}]Short", address.to_formatted_string());
+        assert_eq!(path.to_string(), name);
+        assert_eq!(name.parse(), Ok(path));
+        assert!("Long[hash-nope]Short".parse::<Path>().is_err());
+        assert!("Long[Short".parse::<Path>().is_err());
     }
 }
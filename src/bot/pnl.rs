@@ -0,0 +1,559 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+use serde::Serialize;
+
+use crate::bot::{
+    config::StrategyConfig,
+    journal::{MarketCall, Outcome, PriceSnapshot, TradeRecord},
+    path::{Path, Token},
+    price::{motes_to_cspr, Gain, Price},
+    sizing::BPS_DENOMINATOR,
+};
+
+/// Time range of journal records included in a report. Open ends are unbounded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Window {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Window {
+    pub fn contains(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| *timestamp >= from) && self.to.is_none_or(|to| *timestamp < to)
+    }
+
+    /// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date (midnight UTC).
+    pub fn parse_time(value: &str) -> Result<DateTime<Utc>, Error> {
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+            return Ok(timestamp.with_timezone(&Utc));
+        }
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|midnight| midnight.and_utc())
+            .ok_or_else(|| Error::OdraError {
+                message: format!("Invalid time {}, expected RFC 3339 or YYYY-MM-DD", value),
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(Error::OdraError {
+                message: format!(
+                    "Unknown report format {}, expected text, csv or json",
+                    value
+                ),
+            }),
+        }
+    }
+}
+
+/// Current balances and fair prices, used to mark inventory to market.
+#[derive(Debug, Clone)]
+pub struct Inventory {
+    pub cspr: U256,
+    pub wcspr: U256,
+    pub long: U256,
+    pub short: U256,
+    pub long_fair_price: Price,
+    pub short_fair_price: Price,
+}
+
+#[derive(Debug, Clone)]
+struct PathStats {
+    trades: u64,
+    wins: u64,
+    realised: Gain,
+    tx_costs: U256,
+    pair_fees_cspr: f64,
+    market_fees_cspr: f64,
+}
+
+impl Default for PathStats {
+    fn default() -> Self {
+        Self {
+            trades: 0,
+            wins: 0,
+            realised: Gain::zero(),
+            tx_costs: U256::zero(),
+            pair_fees_cspr: 0.0,
+            market_fees_cspr: 0.0,
+        }
+    }
+}
+
+impl PathStats {
+    fn add(&mut self, gain: Gain, tx_cost: U256, pair_fees_cspr: f64, market_fees_cspr: f64) {
+        self.trades += 1;
+        if gain > Gain::zero() {
+            self.wins += 1;
+        }
+        self.realised = self.realised + gain;
+        self.tx_costs = self.tx_costs.saturating_add(tx_cost);
+        self.pair_fees_cspr += pair_fees_cspr;
+        self.market_fees_cspr += market_fees_cspr;
+    }
+
+    fn row(&self, scope: &str) -> ReportRow {
+        ReportRow {
+            scope: scope.to_string(),
+            trades: self.trades,
+            wins: self.wins,
+            win_rate: if self.trades == 0 {
+                0.0
+            } else {
+                self.wins as f64 / self.trades as f64
+            },
+            realised_cspr: self.realised.to_cspr(),
+            pair_fees_cspr: self.pair_fees_cspr,
+            market_fees_cspr: self.market_fees_cspr,
+            tx_costs_cspr: motes_to_cspr(self.tx_costs),
+        }
+    }
+}
+
+/// One line of the report, in CSPR. Realised PnL is already net of pair and market
+/// fees and transaction costs; those are shown separately for reference.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportRow {
+    pub scope: String,
+    pub trades: u64,
+    pub wins: u64,
+    pub win_rate: f64,
+    pub realised_cspr: f64,
+    pub pair_fees_cspr: f64,
+    pub market_fees_cspr: f64,
+    pub tx_costs_cspr: f64,
+}
+
+/// Inventory marked at fair prices, in CSPR.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryRow {
+    pub cspr: f64,
+    pub wcspr: f64,
+    pub long_value_cspr: f64,
+    pub short_value_cspr: f64,
+    pub total_value_cspr: f64,
+    /// Fair-price change since the start of the window applied to the LONG/SHORT
+    /// tokens held now. `None` if the window has no records to take prices from.
+    pub unrealised_cspr: Option<f64>,
+}
+
+/// Profit and loss of executed swaps, mints and redeems within a window, by path.
+#[derive(Debug, Clone)]
+pub struct PnlSummary {
+    window: Window,
+    evaluations: u64,
+    failures: u64,
+    total: PathStats,
+    by_path: Vec<((Option<MarketCall>, Path), PathStats)>,
+    start_prices: Option<PriceSnapshot>,
+    inventory: Option<InventoryRow>,
+}
+
+impl PnlSummary {
+    /// Aggregates live (non dry-run) journal records within `window`.
+    pub fn from_records(
+        records: &[TradeRecord],
+        window: Window,
+        strategy: &StrategyConfig,
+    ) -> Self {
+        let mut summary = Self {
+            window,
+            evaluations: 0,
+            failures: 0,
+            total: PathStats::default(),
+            by_path: vec![],
+            start_prices: None,
+            inventory: None,
+        };
+        let mut in_window = records
+            .iter()
+            .filter(|record| !record.dry_run && summary.window.contains(&record.timestamp))
+            .collect::<Vec<_>>();
+        in_window.sort_by_key(|record| record.timestamp);
+        summary.start_prices = in_window.first().map(|record| record.prices.clone());
+
+        for record in in_window {
+            summary.evaluations += 1;
+            match record.outcome {
+                Outcome::Failed => summary.failures += 1,
+                Outcome::Executed => summary.add_trade(record, strategy),
+                _ => {}
+            }
+        }
+        summary
+    }
+
+    fn add_trade(&mut self, record: &TradeRecord, strategy: &StrategyConfig) {
+        let Some(gain) = record.amounts.actual_gain else {
+            return;
+        };
+        let amount_in = record
+            .amounts
+            .actual_amount_in
+            .or(record.amounts.quoted_amount_in)
+            .unwrap_or_default();
+        let (tx_cost, pair_fees_cspr) = match record.market_call {
+            Some(_) => (strategy.market_tx_cost(), 0.0),
+            None => (
+                strategy.tx_cost(record.path),
+                pair_fees_cspr(record.path, amount_in, &record.prices, strategy),
+            ),
+        };
+        let market_fees_cspr = motes_to_cspr(record.amounts.market_fee.unwrap_or_default());
+
+        self.total
+            .add(gain, tx_cost, pair_fees_cspr, market_fees_cspr);
+        let key = (record.market_call, record.path);
+        match self.by_path.iter_mut().find(|(scope, _)| *scope == key) {
+            Some((_, stats)) => stats.add(gain, tx_cost, pair_fees_cspr, market_fees_cspr),
+            None => {
+                let mut stats = PathStats::default();
+                stats.add(gain, tx_cost, pair_fees_cspr, market_fees_cspr);
+                self.by_path.push((key, stats));
+            }
+        }
+    }
+
+    /// Marks the current inventory at fair prices.
+    pub fn mark_inventory(&mut self, inventory: &Inventory) {
+        let long_value = motes_to_cspr(inventory.long_fair_price.quote(inventory.long));
+        let short_value = motes_to_cspr(inventory.short_fair_price.quote(inventory.short));
+        let cspr = motes_to_cspr(inventory.cspr);
+        let wcspr = motes_to_cspr(inventory.wcspr);
+        let unrealised = self.start_prices.as_ref().map(|start| {
+            motes_to_cspr(inventory.long)
+                * (inventory.long_fair_price.to_f64() - start.long_fair_price)
+                + motes_to_cspr(inventory.short)
+                    * (inventory.short_fair_price.to_f64() - start.short_fair_price)
+        });
+        self.inventory = Some(InventoryRow {
+            cspr,
+            wcspr,
+            long_value_cspr: long_value,
+            short_value_cspr: short_value,
+            total_value_cspr: cspr + wcspr + long_value + short_value,
+            unrealised_cspr: unrealised,
+        });
+    }
+
+    pub fn total(&self) -> ReportRow {
+        self.total.row("total")
+    }
+
+    pub fn by_path(&self) -> Vec<ReportRow> {
+        self.by_path
+            .iter()
+            .map(|((market_call, path), stats)| match market_call {
+                Some(call) => stats.row(&format!("{:?} {:?}", call, path)),
+                None => stats.row(&format!("{:?}", path)),
+            })
+            .collect()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<String, Error> {
+        match format {
+            ReportFormat::Text => Ok(self.render_text()),
+            ReportFormat::Csv => Ok(self.render_csv()),
+            ReportFormat::Json => self.render_json(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let bound = |time: Option<DateTime<Utc>>| time.map_or("-".to_string(), |t| t.to_rfc3339());
+        let mut lines = vec![
+            format!(
+                "PnL report {} .. {}",
+                bound(self.window.from),
+                bound(self.window.to)
+            ),
+            format!(
+                "Evaluations: {}  Failures: {}",
+                self.evaluations, self.failures
+            ),
+        ];
+        let mut rows = self.by_path();
+        rows.push(self.total());
+        for row in rows {
+            lines.push(format!(
+                "{:<15} trades {:>4}  win rate {:>6.2}%  realised {:>12.4} CSPR  pair fees {:>10.4} CSPR  market fees {:>10.4} CSPR  tx costs {:>10.4} CSPR",
+                row.scope,
+                row.trades,
+                row.win_rate * 100.0,
+                row.realised_cspr,
+                row.pair_fees_cspr,
+                row.market_fees_cspr,
+                row.tx_costs_cspr
+            ));
+        }
+        if let Some(inventory) = &self.inventory {
+            lines.push(format!(
+                "Inventory: CSPR {:.4}  wCSPR {:.4}  LONG {:.4} CSPR  SHORT {:.4} CSPR  total {:.4} CSPR",
+                inventory.cspr,
+                inventory.wcspr,
+                inventory.long_value_cspr,
+                inventory.short_value_cspr,
+                inventory.total_value_cspr
+            ));
+            match inventory.unrealised_cspr {
+                Some(unrealised) => lines.push(format!("Unrealised: {:.4} CSPR", unrealised)),
+                None => lines.push("Unrealised: n/a (no records in window)".to_string()),
+            }
+        }
+        lines.join("\n")
+    }
+
+    fn render_csv(&self) -> String {
+        let mut lines = vec![
+            "scope,trades,wins,win_rate,realised_cspr,pair_fees_cspr,market_fees_cspr,tx_costs_cspr,unrealised_cspr"
+                .to_string(),
+        ];
+        let unrealised = self
+            .inventory
+            .as_ref()
+            .and_then(|inventory| inventory.unrealised_cspr);
+        for row in self.by_path() {
+            lines.push(csv_line(&row, None));
+        }
+        lines.push(csv_line(&self.total(), unrealised));
+        lines.join("\n")
+    }
+
+    fn render_json(&self) -> Result<String, Error> {
+        #[derive(Serialize)]
+        struct JsonReport<'a> {
+            from: Option<DateTime<Utc>>,
+            to: Option<DateTime<Utc>>,
+            evaluations: u64,
+            failures: u64,
+            total: ReportRow,
+            by_path: Vec<ReportRow>,
+            inventory: &'a Option<InventoryRow>,
+        }
+        let report = JsonReport {
+            from: self.window.from,
+            to: self.window.to,
+            evaluations: self.evaluations,
+            failures: self.failures,
+            total: self.total(),
+            by_path: self.by_path(),
+            inventory: &self.inventory,
+        };
+        serde_json::to_string_pretty(&report).map_err(|e| Error::OdraError {
+            message: format!("Failed to render report: {}", e),
+        })
+    }
+}
+
+fn csv_line(row: &ReportRow, unrealised: Option<f64>) -> String {
+    format!(
+        "{},{},{},{:.4},{:.9},{:.9},{:.9},{:.9},{}",
+        row.scope,
+        row.trades,
+        row.wins,
+        row.win_rate,
+        row.realised_cspr,
+        row.pair_fees_cspr,
+        row.market_fees_cspr,
+        row.tx_costs_cspr,
+        unrealised.map_or(String::new(), |value| format!("{:.9}", value))
+    )
+}
+
+/// Estimated pair fees of a swap, in CSPR: the fee on the input value, once per hop.
+fn pair_fees_cspr(
+    path: Path,
+    amount_in: U256,
+    prices: &PriceSnapshot,
+    strategy: &StrategyConfig,
+) -> f64 {
+    let input_price = match path.token_in() {
+        Some(Token::Long) => prices.long_fair_price,
+        Some(Token::Short) => prices.short_fair_price,
+        Some(Token::Wcspr) => 1.0,
+        Some(Token::Other(_)) | None => 0.0,
+    };
+    let hops = path.hops() as f64;
+    motes_to_cspr(amount_in) * input_price * strategy.pair_fee_bps as f64 / BPS_DENOMINATOR as f64
+        * hops
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::{data::PriceData, journal::TradeAmounts, price::MOTES_PER_CSPR};
+
+    use super::*;
+
+    fn cspr(amount: u64) -> U256 {
+        U256::from(amount * MOTES_PER_CSPR)
+    }
+
+    fn record(day: u32, path: Path, outcome: Outcome, gain_cspr: f64) -> TradeRecord {
+        let prices = PriceData::from_f64(1.1, 0.9, 0.05, 1.0 + day as f64 / 100.0, 1.0);
+        let mut amounts = TradeAmounts::default();
+        if outcome == Outcome::Executed {
+            amounts.actual_amount_in = Some(cspr(1_000));
+            amounts.actual_gain = Some(Gain::from_cspr(gain_cspr));
+        }
+        let mut record = TradeRecord::new(path, outcome, &prices, amounts);
+        record.timestamp = Window::parse_time(&format!("2026-03-{:02}", day)).unwrap();
+        record
+    }
+
+    fn records() -> Vec<TradeRecord> {
+        let mut dry_run = record(3, Path::LongWcspr, Outcome::Executed, 1_000.0);
+        dry_run.dry_run = true;
+        vec![
+            record(1, Path::LongWcspr, Outcome::Executed, 100.0),
+            record(2, Path::LongWcspr, Outcome::Executed, 50.0),
+            record(3, Path::LongWcspr, Outcome::Executed, -20.0),
+            record(3, Path::ShortWcsprLong, Outcome::Executed, 30.0),
+            record(4, Path::WcsprShort, Outcome::Failed, 0.0),
+            record(4, Path::WcsprShort, Outcome::BelowMinGain, 0.0),
+            dry_run,
+            record(9, Path::LongWcspr, Outcome::Executed, 500.0),
+        ]
+    }
+
+    fn week() -> Window {
+        Window {
+            from: Some(Window::parse_time("2026-03-02").unwrap()),
+            to: Some(Window::parse_time("2026-03-09T00:00:00Z").unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            Window::parse_time("2026-03-02").unwrap(),
+            Window::parse_time("2026-03-02T00:00:00+00:00").unwrap()
+        );
+        assert_eq!(
+            Window::parse_time("2026-03-02T02:00:00+02:00").unwrap(),
+            Window::parse_time("2026-03-02").unwrap()
+        );
+        assert!(Window::parse_time("last week").is_err());
+    }
+
+    #[test]
+    fn test_summary_within_window() {
+        let summary = PnlSummary::from_records(&records(), week(), &StrategyConfig::default());
+        assert_eq!(summary.evaluations, 5);
+        assert_eq!(summary.failures, 1);
+
+        let total = summary.total();
+        assert_eq!(total.trades, 3);
+        assert_eq!(total.wins, 2);
+        assert_eq!(total.realised_cspr, 60.0);
+        assert_eq!(total.tx_costs_cspr, 7.0 + 7.0 + 12.5);
+
+        let by_path = summary.by_path();
+        assert_eq!(by_path.len(), 2);
+        assert_eq!(by_path[0].scope, "LongWcspr");
+        assert_eq!(by_path[0].realised_cspr, 30.0);
+        assert_eq!(by_path[0].win_rate, 0.5);
+        assert_eq!(by_path[1].scope, "ShortWcsprLong");
+        assert_eq!(by_path[1].trades, 1);
+    }
+
+    #[test]
+    fn test_pair_fees_per_hop() {
+        let summary = PnlSummary::from_records(&records(), week(), &StrategyConfig::default());
+        let by_path = summary.by_path();
+        // 1_000 LONG at fair 1.02 and 1.03, 0.3% fee
+        assert!((by_path[0].pair_fees_cspr - (3.06 + 3.09)).abs() < 1e-9);
+        // 1_000 SHORT at fair 1.0, two hops
+        assert!((by_path[1].pair_fees_cspr - 6.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_mints_count_the_market_fee_and_transaction() {
+        let mut mint = record(5, Path::WcsprLong, Outcome::Executed, -5.5);
+        mint.market_call = Some(MarketCall::Mint);
+        mint.amounts.market_fee = Some(cspr(2));
+        let mut records = records();
+        records.push(mint);
+
+        let summary = PnlSummary::from_records(&records, week(), &StrategyConfig::default());
+        let total = summary.total();
+        assert_eq!(total.trades, 4);
+        assert_eq!(total.realised_cspr, 60.0 - 5.5);
+        assert_eq!(total.market_fees_cspr, 2.0);
+        assert_eq!(total.tx_costs_cspr, 7.0 + 7.0 + 12.5 + 3.5);
+
+        let by_path = summary.by_path();
+        assert_eq!(by_path[2].scope, "Mint WcsprLong");
+        assert_eq!(by_path[2].pair_fees_cspr, 0.0);
+    }
+
+    #[test]
+    fn test_unrealised_uses_fair_price_change_since_window_start() {
+        let mut summary = PnlSummary::from_records(&records(), week(), &StrategyConfig::default());
+        summary.mark_inventory(&Inventory {
+            cspr: cspr(10),
+            wcspr: cspr(20),
+            long: cspr(100),
+            short: cspr(50),
+            long_fair_price: Price::from_f64(1.5),
+            short_fair_price: Price::from_f64(0.8),
+        });
+        let inventory = summary.inventory.clone().unwrap();
+        assert_eq!(inventory.total_value_cspr, 10.0 + 20.0 + 150.0 + 40.0);
+        // LONG fair price moved 1.02 -> 1.5, SHORT 1.0 -> 0.8
+        let unrealised = inventory.unrealised_cspr.unwrap();
+        assert!((unrealised - (100.0 * 0.48 - 50.0 * 0.2)).abs() < 1e-9);
+
+        let mut empty =
+            PnlSummary::from_records(&[], Window::default(), &StrategyConfig::default());
+        empty.mark_inventory(&Inventory {
+            cspr: cspr(10),
+            wcspr: cspr(20),
+            long: cspr(100),
+            short: cspr(50),
+            long_fair_price: Price::from_f64(1.5),
+            short_fair_price: Price::from_f64(0.8),
+        });
+        assert_eq!(empty.inventory.unwrap().unrealised_cspr, None);
+    }
+
+    #[test]
+    fn test_render_csv_and_json() {
+        let summary = PnlSummary::from_records(&records(), week(), &StrategyConfig::default());
+        let csv = summary.render(ReportFormat::Csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("scope,trades,wins,win_rate"));
+        assert!(lines[3].starts_with("total,3,2,0.6667,60.000000000,"));
+        assert!(lines[3].ends_with(','));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&summary.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["total"]["trades"], 3);
+        assert_eq!(json["by_path"][1]["scope"], "ShortWcsprLong");
+        assert_eq!(json["from"], "2026-03-02T00:00:00Z");
+
+        assert!(summary
+            .render(ReportFormat::Text)
+            .unwrap()
+            .contains("total"));
+        assert!("XML".parse::<ReportFormat>().is_err());
+    }
+}
@@ -0,0 +1,482 @@
+use std::cell::RefCell;
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use odra::casper_types::U256;
+
+use crate::bot::{
+    config::PoolsConfig,
+    data::PriceData,
+    graph::{Pool, RouteGraph},
+    path::{Path, Token, MAX_HOPS},
+    price::Price,
+};
+
+/// The reserves of every pool with the block times (in ms) of the pairs' last updates.
+#[derive(Debug, Clone)]
+pub struct PoolReading {
+    pub pools: Vec<(Pool, u64)>,
+    /// Block time the reserves were read at, in ms.
+    pub read_at: u64,
+}
+
+impl PoolReading {
+    pub fn graph(&self) -> RouteGraph {
+        RouteGraph::new(self.pools.iter().map(|(pool, _)| *pool).collect())
+    }
+}
+
+/// Why a pool's price can't be trusted. Pools are named by their tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolAnomaly {
+    /// A reserve of the pool is below the configured minimum.
+    Dust { pool: (Token, Token) },
+    /// The pool was last updated too long ago.
+    Stale { pool: (Token, Token), age: Duration },
+    /// The pool price moved much further since the previous check than the fair price.
+    Jumped {
+        pool: (Token, Token),
+        pool_move_pct: f64,
+        fair_move_pct: f64,
+    },
+    /// The rate of a route through tokens without a fair price moved much
+    /// further since the previous check than the fair prices of its ends.
+    RouteJumped {
+        route: Path,
+        route_move_pct: f64,
+        fair_move_pct: f64,
+    },
+}
+
+impl Display for PoolAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolAnomaly::Dust { pool: (a, b) } => {
+                write!(f, "the {}/{} pool is (nearly) empty", a, b)
+            }
+            PoolAnomaly::Stale { pool: (a, b), age } => {
+                write!(f, "the {}/{} pool was last updated {:?} ago", a, b, age)
+            }
+            PoolAnomaly::Jumped {
+                pool: (a, b),
+                pool_move_pct,
+                fair_move_pct,
+            } => write!(
+                f,
+                "the {}/{} price moved {:.2}% since the previous check, the fair price {:.2}%",
+                a, b, pool_move_pct, fair_move_pct
+            ),
+            PoolAnomaly::RouteJumped {
+                route,
+                route_move_pct,
+                fair_move_pct,
+            } => write!(
+                f,
+                "the {} rate moved {:.2}% since the previous check, the fair prices {:.2}%",
+                route, route_move_pct, fair_move_pct
+            ),
+        }
+    }
+}
+
+/// Rejects pool snapshots that look drained, stale or manipulated.
+pub struct PoolGuard {
+    config: PoolsConfig,
+    /// Longest route the market trades, in hops.
+    max_hops: usize,
+    /// Pools and prices seen by the previous check.
+    previous: RefCell<Option<(RouteGraph, PriceData)>>,
+}
+
+impl PoolGuard {
+    pub fn new(config: &PoolsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            max_hops: MAX_HOPS,
+            previous: RefCell::new(None),
+        }
+    }
+
+    /// Guards only the pools of routes of up to `max_hops` hops.
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = max_hops;
+        self
+    }
+
+    /// Checks the pools of a snapshot the market's routes trade through and
+    /// remembers their prices for the next check. Returns the graph of those
+    /// pools, less any dusty or stale one; only a dusty or stale LONG/wCSPR or
+    /// wCSPR/SHORT pool, which the market is priced off, fails the check. A
+    /// price jump only blocks a single check; if it holds, the next one
+    /// accepts it.
+    pub fn check(
+        &self,
+        reading: &PoolReading,
+        price_data: &PriceData,
+    ) -> Result<RouteGraph, PoolAnomaly> {
+        let routed = market_routes(&reading.graph(), self.max_hops);
+        let previous = self
+            .previous
+            .replace(Some((reading.graph(), price_data.clone())));
+        let min_reserve = U256::from(self.config.min_reserve);
+        let age =
+            |updated_at: u64| Duration::from_millis(reading.read_at.saturating_sub(updated_at));
+        let max_age = self.config.max_pair_age();
+        let mut graph = RouteGraph::default();
+        for (pool, updated_at) in &reading.pools {
+            if !routed.iter().any(|route| trades_through(*route, pool)) {
+                continue;
+            }
+            let (reserve0, reserve1) = pool.reserves;
+            let anomaly = if reserve0 < min_reserve || reserve1 < min_reserve {
+                Some(PoolAnomaly::Dust { pool: pool.tokens })
+            } else if age(*updated_at) > max_age {
+                Some(PoolAnomaly::Stale {
+                    pool: pool.tokens,
+                    age: age(*updated_at),
+                })
+            } else {
+                None
+            };
+            match anomaly {
+                Some(anomaly) if is_market_pool(pool.tokens) => return Err(anomaly),
+                Some(anomaly) => tracing::warn!("Leaving out a pool: {}", anomaly),
+                None => graph.add(*pool),
+            }
+        }
+
+        let Some((previous_graph, previous_data)) = previous else {
+            return Ok(graph);
+        };
+        for pool in graph.pools() {
+            let quoted = Path::new(&quoted_in(pool.tokens));
+            let (Some(previous), Some(current)) =
+                (price(&previous_graph, quoted), price(&graph, quoted))
+            else {
+                continue;
+            };
+            let (Some(previous_fair), Some(current_fair)) = (
+                fair_price(quoted, &previous_data),
+                fair_price(quoted, price_data),
+            ) else {
+                continue;
+            };
+            if let Some((pool_move_pct, fair_move_pct)) = self.jump(
+                (previous.to_f64(), current.to_f64()),
+                (previous_fair, current_fair),
+            ) {
+                return Err(PoolAnomaly::Jumped {
+                    pool: pool.tokens,
+                    pool_move_pct,
+                    fair_move_pct,
+                });
+            }
+        }
+        // Pools of tokens without a fair price are held to the fair prices of
+        // the ends of the routes through them
+        for route in market_routes(&graph, self.max_hops) {
+            if route.tokens().iter().all(|token| has_fair_price(*token)) {
+                continue;
+            }
+            let (Some(previous), Some(current)) =
+                (previous_graph.spot_rate(route), graph.spot_rate(route))
+            else {
+                continue;
+            };
+            let ends = match (route.token_in(), route.token_out()) {
+                (Some(token_in), Some(token_out)) => Path::new(&[token_in, token_out]),
+                _ => continue,
+            };
+            let (Some(previous_fair), Some(current_fair)) = (
+                fair_price(ends, &previous_data),
+                fair_price(ends, price_data),
+            ) else {
+                continue;
+            };
+            if let Some((route_move_pct, fair_move_pct)) =
+                self.jump((previous, current), (previous_fair, current_fair))
+            {
+                return Err(PoolAnomaly::RouteJumped {
+                    route,
+                    route_move_pct,
+                    fair_move_pct,
+                });
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Forgets the previous prices, after the bot's own swap moved the pools.
+    pub fn reset(&self) {
+        self.previous.replace(None);
+    }
+
+    /// Compares the move of a pool price or route rate to the move of its
+    /// fair price. Returns both moves in percent if they differ by more than
+    /// `max_jump_bps`. A sanity bound, so f64 precision is plenty.
+    fn jump(
+        &self,
+        (previous, current): (f64, f64),
+        (previous_fair, current_fair): (f64, f64),
+    ) -> Option<(f64, f64)> {
+        if previous == 0.0 || previous_fair == 0.0 || current_fair == 0.0 {
+            return None;
+        }
+        let pool_move = current / previous;
+        let fair_move = current_fair / previous_fair;
+        let excess_bps = (pool_move / fair_move - 1.0).abs() * 10_000.0;
+        (excess_bps > self.config.max_jump_bps as f64)
+            .then_some(((pool_move - 1.0) * 100.0, (fair_move - 1.0) * 100.0))
+    }
+}
+
+/// The routes of `graph` the market can trade: those between tokens with a
+/// fair price, like `Path::candidates` picks from.
+fn market_routes(graph: &RouteGraph, max_hops: usize) -> Vec<Path> {
+    graph
+        .routes(max_hops)
+        .into_iter()
+        .filter(|route| {
+            route.token_in().is_some_and(has_fair_price)
+                && route.token_out().is_some_and(has_fair_price)
+        })
+        .collect()
+}
+
+fn trades_through(route: Path, pool: &Pool) -> bool {
+    route.pairs().any(|(token_in, token_out)| {
+        [(token_in, token_out), (token_out, token_in)].contains(&pool.tokens)
+    })
+}
+
+/// Whether `tokens` are those of the LONG/wCSPR or wCSPR/SHORT pool.
+fn is_market_pool(tokens: (Token, Token)) -> bool {
+    [Token::Long, Token::Short]
+        .into_iter()
+        .any(|token| tokens == (token, Token::Wcspr) || tokens == (Token::Wcspr, token))
+}
+
+fn has_fair_price(token: Token) -> bool {
+    !matches!(token, Token::Other(_))
+}
+
+/// The tokens of a pool as `[base, quote]`, quoting in wCSPR where the pool trades it.
+fn quoted_in((token0, token1): (Token, Token)) -> [Token; 2] {
+    if token0 == Token::Wcspr {
+        [token1, token0]
+    } else {
+        [token0, token1]
+    }
+}
+
+/// The pool price of the first token of `quoted` in the second, if `graph` has the pool.
+fn price(graph: &RouteGraph, quoted: Path) -> Option<Price> {
+    let (reserve_base, reserve_quote) = *graph.hops(quoted)?.first()?;
+    Some(Price::new(reserve_quote, reserve_base))
+}
+
+/// The fair price of the first token of `quoted` in the second. `None` for
+/// tokens of no market the bot reads, which have no fair price, or if the
+/// quote token's fair price is zero.
+fn fair_price(quoted: Path, data: &PriceData) -> Option<f64> {
+    let (base, quote) = (quoted.token_in()?, quoted.token_out()?);
+    if !has_fair_price(base) || !has_fair_price(quote) {
+        return None;
+    }
+    let fair = |token: Token| data.fair_price(token).to_f64();
+    (fair(quote) != 0.0).then(|| fair(base) / fair(quote))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::data::PoolReserves;
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000_000;
+    const NOW: u64 = 1_700_000_000_000;
+    const LONG_WCSPR: (Token, Token) = (Token::Long, Token::Wcspr);
+    const WCSPR_SHORT: (Token, Token) = (Token::Wcspr, Token::Short);
+    const LONG_SHORT: (Token, Token) = (Token::Long, Token::Short);
+
+    fn reading(long: u64, short: u64, age_secs: u64) -> PoolReading {
+        let graph = RouteGraph::from(&PoolReserves {
+            long: U256::from(long) * ONE,
+            wcspr_long: U256::from(1_000u64) * ONE,
+            wcspr_short: U256::from(1_000u64) * ONE,
+            short: U256::from(short) * ONE,
+        });
+        PoolReading {
+            pools: vec![
+                (graph.pools()[0], NOW - age_secs * 1_000),
+                (graph.pools()[1], NOW),
+            ],
+            read_at: NOW,
+        }
+    }
+
+    /// `reading` with a LONG/SHORT pool of `long` and `short` reserves.
+    fn with_long_short(long: u64, short: u64, age_secs: u64) -> PoolReading {
+        let mut pools = reading(1_000, 1_000, 0);
+        let pool = Pool {
+            tokens: LONG_SHORT,
+            reserves: (U256::from(long) * ONE, U256::from(short) * ONE),
+        };
+        pools.pools.push((pool, NOW - age_secs * 1_000));
+        pools
+    }
+
+    fn price_data(long: f64, long_fair: f64) -> PriceData {
+        PriceData::from_f64(long, 1.0, 0.02, long_fair, 1.0)
+    }
+
+    #[test]
+    fn test_rejects_dust_and_stale_pools() {
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        let prices = price_data(1.0, 1.0);
+        assert_eq!(
+            guard.check(&reading(1_000, 99, 0), &prices),
+            Err(PoolAnomaly::Dust { pool: WCSPR_SHORT })
+        );
+        assert_eq!(
+            guard.check(&reading(1_000, 1_000, 86_401), &prices),
+            Err(PoolAnomaly::Stale {
+                pool: LONG_WCSPR,
+                age: Duration::from_secs(86_401)
+            })
+        );
+        assert!(guard.check(&reading(1_000, 1_000, 60), &prices).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_a_jump_the_fair_price_does_not_explain() {
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        // LONG priced at 1.0, 1.25 and 0.8 wCSPR
+        let (at_1, at_1_25, at_0_8) = (
+            reading(1_000, 1_000, 0),
+            reading(800, 1_000, 0),
+            reading(1_250, 1_000, 0),
+        );
+        assert!(guard.check(&at_1, &price_data(1.0, 1.0)).is_ok());
+
+        // The market moved as much as the pool
+        assert!(guard.check(&at_1_25, &price_data(1.25, 1.2)).is_ok());
+
+        assert!(matches!(
+            guard.check(&at_0_8, &price_data(0.8, 1.2)),
+            Err(PoolAnomaly::Jumped {
+                pool: LONG_WCSPR,
+                ..
+            })
+        ));
+        // A move that holds is accepted on the next check
+        assert!(guard.check(&at_0_8, &price_data(0.8, 1.2)).is_ok());
+    }
+
+    #[test]
+    fn test_checks_pools_beyond_the_wcspr_ones() {
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        let prices = price_data(1.0, 1.0);
+        let market_pools = reading(1_000, 1_000, 0).graph();
+        // A dusty or stale LONG/SHORT pool is left out rather than failing the check
+        assert_eq!(
+            guard.check(&with_long_short(1_000, 99, 0), &prices),
+            Ok(market_pools.clone())
+        );
+        assert_eq!(
+            guard.check(&with_long_short(1_000, 1_000, 86_401), &prices),
+            Ok(market_pools)
+        );
+
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        assert_eq!(
+            guard
+                .check(&with_long_short(1_000, 1_000, 0), &prices)
+                .map(|graph| graph.pools().len()),
+            Ok(3)
+        );
+        // Only the LONG/SHORT price moved, the fair prices did not
+        assert!(matches!(
+            guard.check(&with_long_short(1_000, 1_300, 0), &prices),
+            Err(PoolAnomaly::Jumped {
+                pool: LONG_SHORT,
+                ..
+            })
+        ));
+    }
+
+    /// `reading` with `pools` of another market's tokens.
+    fn with_other(pools: &[((Token, Token), u64, u64)]) -> PoolReading {
+        let mut reading = reading(1_000, 1_000, 0);
+        for &(tokens, reserve0, reserve1) in pools {
+            let pool = Pool {
+                tokens,
+                reserves: (U256::from(reserve0) * ONE, U256::from(reserve1) * ONE),
+            };
+            reading.pools.push((pool, NOW));
+        }
+        reading
+    }
+
+    #[test]
+    fn test_guards_only_the_pools_of_the_market_routes() {
+        let env = odra_test::env();
+        let (btc_long, btc_short) = (
+            Token::Other(env.get_account(1)),
+            Token::Other(env.get_account(2)),
+        );
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        let prices = price_data(1.0, 1.0);
+        // Another market's pools no route of this one trades through: a dusty
+        // one, and one whose price doubles from one check to the next
+        let other_market = |btc_price| {
+            with_other(&[
+                ((Token::Wcspr, btc_long), 1_000, btc_price),
+                ((Token::Wcspr, btc_short), 1_000, 1),
+            ])
+        };
+        assert_eq!(
+            guard.check(&other_market(1_000), &prices),
+            Ok(reading(1_000, 1_000, 0).graph())
+        );
+        assert!(guard.check(&other_market(500), &prices).is_ok());
+    }
+
+    #[test]
+    fn test_holds_routes_through_other_tokens_to_their_ends() {
+        let btc_long = Token::Other(odra_test::env().get_account(1));
+        let guard = PoolGuard::new(&PoolsConfig::default());
+        let prices = price_data(1.0, 1.0);
+        // wCSPR -> BTC LONG -> LONG trades at 1.0 throughout
+        assert!(guard
+            .check(
+                &with_other(&[
+                    ((Token::Wcspr, btc_long), 1_000, 1_000),
+                    ((btc_long, Token::Long), 1_000, 1_000),
+                ]),
+                &prices,
+            )
+            .is_ok());
+        // BTC LONG doubles against both, which the route does not see
+        assert!(guard
+            .check(
+                &with_other(&[
+                    ((Token::Wcspr, btc_long), 1_000, 500),
+                    ((btc_long, Token::Long), 500, 1_000),
+                ]),
+                &prices,
+            )
+            .is_ok());
+        // Only the BTC LONG/LONG price moves
+        assert!(matches!(
+            guard.check(
+                &with_other(&[
+                    ((Token::Wcspr, btc_long), 1_000, 500),
+                    ((btc_long, Token::Long), 500, 1_500),
+                ]),
+                &prices,
+            ),
+            Err(PoolAnomaly::RouteJumped { .. })
+        ));
+    }
+}
@@ -0,0 +1,461 @@
+use std::cmp::Ordering;
+
+use odra::casper_types::{U256, U512};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::bot::sizing::BPS_DENOMINATOR;
+
+pub const MOTES_PER_CSPR: u64 = 1_000_000_000;
+
+/// An exact price, kept as the ratio of two on-chain amounts.
+/// `Price::new(a, b)` is the amount of `a`-units worth one `b`-unit, e.g. the wCSPR
+/// reserve over the LONG reserve is the LONG price in CSPR.
+/// A zero denominator (empty pool, no supply) yields a zero price.
+#[derive(Debug, Clone, Copy)]
+pub struct Price {
+    numerator: U256,
+    denominator: U256,
+}
+
+impl Price {
+    pub fn new(numerator: U256, denominator: U256) -> Self {
+        if denominator.is_zero() {
+            return Self::zero();
+        }
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self {
+            numerator: U256::zero(),
+            denominator: U256::one(),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    /// Value of `amount` base units in quote units.
+    pub fn quote(&self, amount: U256) -> U256 {
+        narrow(widen(amount) * widen(self.numerator) / widen(self.denominator))
+    }
+
+    /// Base units worth `value` quote units. Zero if the price is zero.
+    pub fn base_for(&self, value: U256) -> U256 {
+        if self.is_zero() {
+            return U256::zero();
+        }
+        narrow(widen(value) * widen(self.denominator) / widen(self.numerator))
+    }
+
+    /// Whether this price differs from `fair` by more than `threshold_bps` of `fair`.
+    /// A zero fair price never counts as a deviation.
+    pub fn deviates_from(&self, fair: &Price, threshold_bps: u64) -> bool {
+        if fair.is_zero() {
+            return false;
+        }
+        // |a/b - c/d| > t/10_000 * c/d  <=>  |a*d - c*b| * 10_000 > t * c*b
+        let own = widen(self.numerator) * widen(fair.denominator);
+        let other = widen(fair.numerator) * widen(self.denominator);
+        let diff = if own > other {
+            own - other
+        } else {
+            other - own
+        };
+        compare_scaled(diff, BPS_DENOMINATOR, other, threshold_bps) == Ordering::Greater
+    }
+
+    /// Lossy conversion, for logging only.
+    pub fn to_f64(&self) -> f64 {
+        u256_to_f64(self.numerator) / u256_to_f64(self.denominator)
+    }
+
+    /// Lossy percentage deviation from `fair`, for logging only.
+    pub fn deviation_pct(&self, fair: &Price) -> f64 {
+        (self.to_f64() / fair.to_f64()) * 100.0f64 - 100.0f64
+    }
+
+    #[cfg(test)]
+    pub fn from_f64(value: f64) -> Self {
+        Self::new(
+            U256::from((value * MOTES_PER_CSPR as f64).round() as u64),
+            U256::from(MOTES_PER_CSPR),
+        )
+    }
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let own = widen(self.numerator) * widen(other.denominator);
+        let other = widen(other.numerator) * widen(self.denominator);
+        own.cmp(&other)
+    }
+}
+
+/// A signed amount of motes, used for trade gains and losses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gain {
+    magnitude: U256,
+    negative: bool,
+}
+
+impl Gain {
+    pub fn zero() -> Self {
+        Self {
+            magnitude: U256::zero(),
+            negative: false,
+        }
+    }
+
+    /// `income - cost`, without overflow.
+    pub fn between(income: U256, cost: U256) -> Self {
+        if income >= cost {
+            Self {
+                magnitude: income - cost,
+                negative: false,
+            }
+        } else {
+            Self {
+                magnitude: cost - income,
+                negative: true,
+            }
+        }
+    }
+
+    /// Converts a (config) CSPR amount, rounded to the nearest mote.
+    pub fn from_cspr(cspr: f64) -> Self {
+        let magnitude = cspr_to_motes(cspr.abs());
+        Self {
+            magnitude,
+            negative: cspr < 0.0f64 && !magnitude.is_zero(),
+        }
+    }
+
+    pub fn magnitude(&self) -> U256 {
+        self.magnitude
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// Lossy conversion, for logging only.
+    pub fn to_cspr(&self) -> f64 {
+        let cspr = motes_to_cspr(self.magnitude);
+        if self.negative {
+            -cspr
+        } else {
+            cspr
+        }
+    }
+}
+
+/// Serialized as a signed decimal string of motes, e.g. `"-1500000000"`.
+impl Serialize for Gain {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sign = if self.negative { "-" } else { "" };
+        serializer.serialize_str(&format!("{}{}", sign, self.magnitude))
+    }
+}
+
+impl<'de> Deserialize<'de> for Gain {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value.as_str()),
+        };
+        let magnitude = U256::from_dec_str(digits)
+            .map_err(|_| de::Error::custom(format!("invalid gain: {}", value)))?;
+        Ok(Self {
+            magnitude,
+            negative: negative && !magnitude.is_zero(),
+        })
+    }
+}
+
+impl std::ops::Add for Gain {
+    type Output = Self;
+
+    /// Saturates at `U256::MAX` in either direction.
+    fn add(self, other: Self) -> Self {
+        if self.negative == other.negative {
+            return Self {
+                magnitude: self.magnitude.saturating_add(other.magnitude),
+                negative: self.negative,
+            };
+        }
+        let (positive, negative) = if self.negative {
+            (other.magnitude, self.magnitude)
+        } else {
+            (self.magnitude, other.magnitude)
+        };
+        Self::between(positive, negative)
+    }
+}
+
+impl std::ops::Neg for Gain {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            magnitude: self.magnitude,
+            negative: !self.negative && !self.magnitude.is_zero(),
+        }
+    }
+}
+
+impl std::ops::Sub for Gain {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl PartialOrd for Gain {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Gain {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => self.magnitude.cmp(&other.magnitude),
+            (true, true) => other.magnitude.cmp(&self.magnitude),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+        }
+    }
+}
+
+/// Converts a (config) CSPR amount to motes, rounded to the nearest mote.
+/// Negative and non-finite amounts become zero.
+pub fn cspr_to_motes(cspr: f64) -> U256 {
+    U256::from((cspr * MOTES_PER_CSPR as f64).round() as u64)
+}
+
+/// Lossy conversion, for logging only.
+pub fn motes_to_cspr(motes: U256) -> f64 {
+    u256_to_f64(motes) / MOTES_PER_CSPR as f64
+}
+
+/// Lossy conversion that, unlike `as_u64`, never panics.
+pub fn u256_to_f64(value: U256) -> f64 {
+    value.0.iter().rev().fold(0.0f64, |acc, limb| {
+        acc * 18_446_744_073_709_551_616.0f64 + *limb as f64
+    })
+}
+
+pub(super) fn widen(value: U256) -> U512 {
+    let mut bytes = [0u8; 32];
+    value.to_little_endian(&mut bytes);
+    U512::from_little_endian(&bytes)
+}
+
+/// Saturates at `U256::MAX`.
+pub(super) fn narrow(value: U512) -> U256 {
+    if value.bits() > 256 {
+        return U256::MAX;
+    }
+    let mut bytes = [0u8; 64];
+    value.to_little_endian(&mut bytes);
+    U256::from_little_endian(&bytes[..32])
+}
+
+/// Compares `a * x` with `b * y`. Exact unless a product overflows 512 bits, in
+/// which case both sides are scaled down by the same factor before comparing.
+fn compare_scaled(mut a: U512, x: u64, mut b: U512, y: u64) -> Ordering {
+    loop {
+        match (a.checked_mul(U512::from(x)), b.checked_mul(U512::from(y))) {
+            (Some(left), Some(right)) => return left.cmp(&right),
+            _ => {
+                a >>= 64;
+                b >>= 64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn any_u256() -> impl Strategy<Value = U256> {
+        any::<[u8; 32]>().prop_map(|bytes| U256::from_little_endian(&bytes))
+    }
+
+    #[test]
+    fn test_price_ordering() {
+        let half = Price::new(U256::from(1), U256::from(2));
+        let also_half = Price::new(U256::from(500), U256::from(1_000));
+        let third = Price::new(U256::from(1), U256::from(3));
+        assert_eq!(half, also_half);
+        assert!(third < half);
+        assert!(Price::zero() < third);
+    }
+
+    #[test]
+    fn test_zero_denominator_is_zero_price() {
+        let price = Price::new(U256::from(10), U256::zero());
+        assert!(price.is_zero());
+        assert!(price.quote(U256::from(1_000)).is_zero());
+        assert!(price.base_for(U256::from(1_000)).is_zero());
+    }
+
+    #[test]
+    fn test_quote_and_base_for() {
+        let price = Price::new(U256::from(3), U256::from(4));
+        assert_eq!(price.quote(U256::from(400)), U256::from(300));
+        assert_eq!(price.base_for(U256::from(300)), U256::from(400));
+    }
+
+    #[test]
+    fn test_tiny_prices_do_not_round_to_zero() {
+        // One mote of wCSPR against a reserve far beyond u64
+        let price = Price::new(U256::one(), U256::MAX);
+        assert!(!price.is_zero());
+        assert!(Price::zero() < price);
+        assert_eq!(price.base_for(U256::one()), U256::MAX);
+    }
+
+    #[test]
+    fn test_deviates_from() {
+        let fair = Price::from_f64(100.0);
+        assert!(Price::from_f64(103.0).deviates_from(&fair, 250));
+        assert!(Price::from_f64(97.0).deviates_from(&fair, 250));
+        assert!(!Price::from_f64(102.0).deviates_from(&fair, 250));
+        assert!(!Price::from_f64(102.5).deviates_from(&fair, 250));
+        assert!(!Price::from_f64(103.0).deviates_from(&Price::zero(), 250));
+    }
+
+    #[test]
+    fn test_gain_ordering_and_sign() {
+        let profit = Gain::between(U256::from(10), U256::from(4));
+        let loss = Gain::between(U256::from(4), U256::from(10));
+        let big_loss = Gain::between(U256::from(0), U256::from(100));
+        assert_eq!(profit.magnitude(), U256::from(6));
+        assert!(loss.is_negative());
+        assert!(big_loss < loss);
+        assert!(loss < Gain::zero());
+        assert!(Gain::zero() < profit);
+        assert_eq!(Gain::from_cspr(-1.5).to_cspr(), -1.5);
+        assert_eq!(Gain::from_cspr(-0.0), Gain::zero());
+    }
+
+    #[test]
+    fn test_gain_add() {
+        let profit = Gain::between(U256::from(10), U256::from(4));
+        let loss = Gain::between(U256::from(4), U256::from(10));
+        assert_eq!(profit + loss, Gain::zero());
+        assert_eq!(profit + profit, Gain::between(U256::from(12), U256::zero()));
+        assert_eq!(loss + loss, Gain::between(U256::zero(), U256::from(12)));
+        assert_eq!(profit - profit, Gain::zero());
+        assert_eq!(loss - profit, Gain::between(U256::zero(), U256::from(12)));
+        assert_eq!(-Gain::zero(), Gain::zero());
+        assert_eq!(
+            loss + Gain::from_cspr(0.000000008),
+            Gain::between(U256::from(2), U256::zero())
+        );
+    }
+
+    #[test]
+    fn test_gain_serde_round_trip() {
+        let loss = Gain::between(U256::zero(), U256::MAX);
+        let json = serde_json::to_string(&loss).unwrap();
+        assert_eq!(json, format!("\"-{}\"", U256::MAX));
+        assert_eq!(serde_json::from_str::<Gain>(&json).unwrap(), loss);
+        assert_eq!(
+            serde_json::from_str::<Gain>("\"-0\"").unwrap(),
+            Gain::zero()
+        );
+        assert!(serde_json::from_str::<Gain>("\"1.5\"").is_err());
+    }
+
+    #[test]
+    fn test_u256_to_f64() {
+        assert_eq!(u256_to_f64(U256::from(1_234_567_890u64)), 1_234_567_890.0);
+        assert_eq!(
+            u256_to_f64(U256::from(u64::MAX) + 1),
+            18_446_744_073_709_551_616.0
+        );
+        assert!(u256_to_f64(U256::MAX) > 1e77);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_non_zero_reserves_give_non_zero_price(a in any_u256(), b in any_u256()) {
+            prop_assume!(!a.is_zero() && !b.is_zero());
+            let price = Price::new(a, b);
+            prop_assert!(!price.is_zero());
+            prop_assert!(price > Price::zero());
+        }
+
+        #[test]
+        fn prop_price_ops_never_panic(
+            a in any_u256(),
+            b in any_u256(),
+            c in any_u256(),
+            d in any_u256(),
+            amount in any_u256(),
+            threshold_bps in any::<u64>(),
+        ) {
+            let price = Price::new(a, b);
+            let fair = Price::new(c, d);
+            let _ = price.quote(amount);
+            let _ = price.base_for(amount);
+            let _ = price.cmp(&fair);
+            let _ = price.deviates_from(&fair, threshold_bps);
+            let _ = price.to_f64();
+        }
+
+        #[test]
+        fn prop_ordering_matches_cross_multiplication(
+            a in 1u64..,
+            b in 1u64..,
+            c in 1u64..,
+            d in 1u64..,
+        ) {
+            let left = Price::new(U256::from(a), U256::from(b));
+            let right = Price::new(U256::from(c), U256::from(d));
+            let expected = (a as u128 * d as u128).cmp(&(c as u128 * b as u128));
+            prop_assert_eq!(left.cmp(&right), expected);
+        }
+
+        #[test]
+        fn prop_quote_is_exact_for_small_values(amount in any::<u64>(), a in 1u64.., b in 1u64..) {
+            let price = Price::new(U256::from(a), U256::from(b));
+            let expected = amount as u128 * a as u128 / b as u128;
+            prop_assert_eq!(price.quote(U256::from(amount)), U256::from(expected));
+        }
+
+        #[test]
+        fn prop_gain_between_is_antisymmetric(x in any_u256(), y in any_u256()) {
+            let gain = Gain::between(x, y);
+            let reverse = Gain::between(y, x);
+            prop_assert_eq!(gain.magnitude(), reverse.magnitude());
+            prop_assert_eq!(gain.cmp(&Gain::zero()), x.cmp(&y));
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use chrono::Utc;
+use odra::host::HostEnv;
+use odra::schema::casper_contract_schema::NamedCLType;
+use odra_cli::{
+    scenario::{Args, Error, Scenario, ScenarioMetadata},
+    DeployedContractsContainer,
+};
+
+use crate::bot::{
+    build_event_source,
+    config::BotConfig,
+    events::{BotEvent, EventSource, ShutdownSignal},
+    snapshot::{MarketSnapshot, SnapshotWriter},
+    utils::PriceCalculator,
+};
+use crate::contracts::ContractRefs;
+
+pub struct Record;
+
+impl ScenarioMetadata for Record {
+    const NAME: &'static str = "Record";
+    const DESCRIPTION: &'static str =
+        "Records pool reserves, market state and the oracle price for backtesting.";
+}
+
+impl Scenario for Record {
+    fn args(&self) -> Vec<odra_cli::CommandArg> {
+        vec![
+            odra_cli::CommandArg::new(
+                "config",
+                "Path to the bot config TOML file. Defaults are used if omitted.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "dir",
+                "Directory to write snapshots to. Defaults to recorder.dir from the config.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "interval",
+                "Seconds between snapshots. Defaults to recorder.interval_secs from the config.",
+                NamedCLType::U32,
+            ),
+        ]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args,
+    ) -> Result<(), Error> {
+        let config_path: Option<String> = args.get_single("config").ok();
+        let mut config = BotConfig::load(config_path.as_deref())?;
+        if let Ok(dir) = args.get_single::<String>("dir") {
+            config.recorder.dir = dir;
+        }
+        if let Ok(interval) = args.get_single::<u32>("interval") {
+            config.recorder.interval_secs = interval.into();
+        }
+        config.validate()?;
+        let shutdown = ShutdownSignal::new();
+        shutdown.install_handler()?;
+
+        let contracts = ContractRefs::new(env, container);
+        let calc = PriceCalculator::new(&contracts);
+        let recorder = &config.recorder;
+        let mut writer = SnapshotWriter::new(&recorder.dir, recorder.max_file_bytes)?;
+        let mut event_source =
+            build_event_source(&contracts, &config, recorder.interval(), &shutdown)?;
+
+        let mut recorded = 0u64;
+        while let Some(event) = event_source.next_event() {
+            if event == BotEvent::Shutdown {
+                break;
+            }
+            let result = self
+                .snapshot(&calc, &config.oracle.price_feed_id)
+                .and_then(|snapshot| writer.write(&snapshot).map(|_| ()));
+            match result {
+                Ok(()) => recorded += 1,
+                Err(e) => tracing::error!("Failed to record snapshot: {:?}", e),
+            }
+        }
+        tracing::info!("Recorded {} snapshots to {}", recorded, recorder.dir);
+        Ok(())
+    }
+}
+
+impl Record {
+    fn snapshot(
+        &self,
+        calc: &PriceCalculator,
+        price_feed_id: &str,
+    ) -> Result<MarketSnapshot, Error> {
+        let oracle_price = calc.oracle_price(price_feed_id).unwrap_or_else(|e| {
+            tracing::warn!("Failed to read the oracle price: {:?}", e);
+            None
+        });
+        Ok(MarketSnapshot {
+            timestamp: Utc::now(),
+            reserves: calc.pool_reserves()?,
+            market: calc.market_reading()?,
+            oracle_price,
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+
+use crate::bot::config::BotConfig;
+use crate::{CD_LONG_ID, CD_SHORT_ID, LP_LONG_WCSPR_ID, LP_WCSPR_SHORT_ID, MARKET_ID};
+
+/// The contract name of casper-trade pairs in the contracts file.
+const PAIR_CONTRACT_NAME: &str = "Pair";
+
+/// Named contracts the CLI registers on top of the default market, its
+/// position tokens and pairs: those of every market in the bot config, and
+/// every pair in the contracts file.
+#[derive(Debug, Default, PartialEq)]
+pub struct ContractNames {
+    pub markets: Vec<String>,
+    pub position_tokens: Vec<String>,
+    pub pairs: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ContractsFile {
+    #[serde(default)]
+    contracts: Vec<ContractEntry>,
+}
+
+#[derive(Deserialize)]
+struct ContractEntry {
+    name: String,
+    package_name: String,
+}
+
+impl ContractNames {
+    /// Reads the bot config passed as `--config` and the contracts file passed
+    /// as `-c` on the command line. Either is skipped, with a warning, if it is
+    /// missing or doesn't load; the scenario reports the error when it runs.
+    pub fn from_args(args: &[String]) -> Self {
+        let mut names = Self::default();
+        if let Some(path) = arg_value(args, "--config") {
+            match BotConfig::load(Some(path)) {
+                Ok(config) => names.add_markets(&config),
+                Err(e) => tracing::warn!("Not registering the markets of {}: {}", path, e),
+            }
+        }
+        if let Some(path) = arg_value(args, "-c") {
+            let pairs = std::fs::read_to_string(path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| pair_names(&content));
+            match pairs {
+                Ok(pairs) => names.pairs = pairs,
+                Err(e) => tracing::warn!("Not registering the pairs of {}: {}", path, e),
+            }
+        }
+        names
+    }
+
+    fn add_markets(&mut self, config: &BotConfig) {
+        for market in config.markets() {
+            push_new(&mut self.markets, &market.market, &[MARKET_ID]);
+            for token in [&market.long, &market.short] {
+                push_new(&mut self.position_tokens, token, &[CD_LONG_ID, CD_SHORT_ID]);
+            }
+        }
+    }
+}
+
+/// Pairs of the contracts file `content`, other than the default ones.
+fn pair_names(content: &str) -> Result<Vec<String>, String> {
+    let file: ContractsFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    let mut pairs = vec![];
+    for contract in file.contracts {
+        if contract.name == PAIR_CONTRACT_NAME {
+            push_new(
+                &mut pairs,
+                &contract.package_name,
+                &[LP_LONG_WCSPR_ID, LP_WCSPR_SHORT_ID],
+            );
+        }
+    }
+    Ok(pairs)
+}
+
+/// Adds `name` to `names` unless it is already there or one of the `defaults`.
+fn push_new(names: &mut Vec<String>, name: &str, defaults: &[&str]) {
+    if !defaults.contains(&name) && !names.iter().any(|known| known == name) {
+        names.push(name.to_string());
+    }
+}
+
+/// The value following `flag` in `args`.
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|index| args.get(index + 1))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_arg_value() {
+        let args = args(&["bot", "-c", "contracts.toml", "scenario", "Bot"]);
+        assert_eq!(arg_value(&args, "-c"), Some("contracts.toml"));
+        assert_eq!(arg_value(&args, "--config"), None);
+        assert_eq!(arg_value(&args, "Bot"), None);
+    }
+
+    #[test]
+    fn test_pair_names_skip_the_defaults() {
+        let content = r#"
+            last_updated = "2025-10-06T08:53:19Z"
+
+            [[contracts]]
+            name = "Pair"
+            package_name = "CD_LONG-WCSPR LP"
+            package_hash = "hash-01"
+
+            [[contracts]]
+            name = "Pair"
+            package_name = "BTC_LONG-WCSPR LP"
+            package_hash = "hash-02"
+
+            [[contracts]]
+            name = "Market"
+            package_name = "BTC_Market"
+            package_hash = "hash-03"
+        "#;
+        assert_eq!(
+            pair_names(content).unwrap(),
+            vec!["BTC_LONG-WCSPR LP".to_string()]
+        );
+        assert!(pair_names("contracts = 1").is_err());
+    }
+
+    #[test]
+    fn test_markets_of_the_config() {
+        let mut config = BotConfig::default();
+        config.markets = vec![
+            Default::default(),
+            crate::bot::config::MarketConfig {
+                name: "btc".to_string(),
+                market: "BTC_Market".to_string(),
+                long: "BTC_LONG".to_string(),
+                short: "BTC_SHORT".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut names = ContractNames::default();
+        names.add_markets(&config);
+        assert_eq!(names.markets, vec!["BTC_Market".to_string()]);
+        assert_eq!(
+            names.position_tokens,
+            vec!["BTC_LONG".to_string(), "BTC_SHORT".to_string()]
+        );
+    }
+}
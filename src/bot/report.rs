@@ -0,0 +1,139 @@
+use chrono::{Duration, Utc};
+use odra::host::HostEnv;
+use odra::schema::casper_contract_schema::NamedCLType;
+use odra_cli::{
+    scenario::{Args, Error, Scenario, ScenarioMetadata},
+    DeployedContractsContainer,
+};
+
+use crate::bot::{
+    asset_manager::{Balances, RealBalances},
+    config::{BotConfig, MarketConfig},
+    journal::TradeJournal,
+    pnl::{Inventory, PnlSummary, ReportFormat, Window},
+    utils::PriceCalculator,
+};
+use crate::contracts::ContractRefs;
+
+pub struct PnlReport;
+
+impl ScenarioMetadata for PnlReport {
+    const NAME: &'static str = "PnlReport";
+    const DESCRIPTION: &'static str =
+        "Reports the bot's realised and unrealised PnL from its trade journal.";
+}
+
+impl Scenario for PnlReport {
+    fn args(&self) -> Vec<odra_cli::CommandArg> {
+        vec![
+            odra_cli::CommandArg::new(
+                "config",
+                "Path to the bot config TOML file, used for the journal path and costs.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "market",
+                "Market to report on, by its name in the config. Defaults to the first.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "journal",
+                "Path to the trade journal. Defaults to the market's journal from the config.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "from",
+                "Start of the window (RFC 3339 or YYYY-MM-DD). Defaults to the first record.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "to",
+                "End of the window, exclusive (RFC 3339 or YYYY-MM-DD). Defaults to now.",
+                NamedCLType::String,
+            ),
+            odra_cli::CommandArg::new(
+                "days",
+                "Report the last N days instead of from/to.",
+                NamedCLType::U32,
+            ),
+            odra_cli::CommandArg::new(
+                "format",
+                "Output format: text, csv or json. Defaults to text.",
+                NamedCLType::String,
+            ),
+        ]
+    }
+
+    fn run(
+        &self,
+        env: &HostEnv,
+        container: &DeployedContractsContainer,
+        args: Args,
+    ) -> Result<(), Error> {
+        let config_path: Option<String> = args.get_single("config").ok();
+        let config = BotConfig::load(config_path.as_deref())?;
+        let market = self.market(&config, &args)?;
+        let journal_path: String = args
+            .get_single("journal")
+            .unwrap_or_else(|_| config.market_journal_path(&market));
+        let format: ReportFormat = args
+            .get_single::<String>("format")
+            .map(|format| format.parse())
+            .unwrap_or(Ok(ReportFormat::Text))?;
+        let window = self.window(&args)?;
+
+        let records = TradeJournal::read(&journal_path)?;
+        let mut summary = PnlSummary::from_records(&records, window, &config.strategy);
+
+        let contracts = ContractRefs::new(env, container).for_market(market.names());
+        let (long_fair_price, short_fair_price, _) =
+            PriceCalculator::new(&contracts).fair_prices()?;
+        let balances = RealBalances::new(env, &contracts);
+        summary.mark_inventory(&Inventory {
+            cspr: balances.my_cspr_balance()?,
+            wcspr: balances.my_wcspr_balance()?,
+            long: balances.my_long_balance()?,
+            short: balances.my_short_balance()?,
+            long_fair_price,
+            short_fair_price,
+        });
+
+        println!("{}", summary.render(format)?);
+        Ok(())
+    }
+}
+
+impl PnlReport {
+    fn market(&self, config: &BotConfig, args: &Args) -> Result<MarketConfig, Error> {
+        let markets = config.markets();
+        let Ok(name) = args.get_single::<String>("market") else {
+            return Ok(markets[0].clone());
+        };
+        markets
+            .into_iter()
+            .find(|market| market.name == name)
+            .ok_or_else(|| Error::OdraError {
+                message: format!("Unknown market: {}", name),
+            })
+    }
+
+    fn window(&self, args: &Args) -> Result<Window, Error> {
+        if let Ok(days) = args.get_single::<u32>("days") {
+            let from = Utc::now()
+                .checked_sub_signed(Duration::days(days.into()))
+                .ok_or_else(|| Error::OdraError {
+                    message: format!("Invalid number of days: {}", days),
+                })?;
+            return Ok(Window {
+                from: Some(from),
+                to: None,
+            });
+        }
+        let from = args.get_single::<String>("from").ok();
+        let to = args.get_single::<String>("to").ok();
+        Ok(Window {
+            from: from.as_deref().map(Window::parse_time).transpose()?,
+            to: to.as_deref().map(Window::parse_time).transpose()?,
+        })
+    }
+}
@@ -0,0 +1,530 @@
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use odra::casper_types::U256;
+use odra::prelude::Address;
+
+use crate::bot::{
+    asset_manager::{Balances, TokenManager},
+    config::RetryConfig,
+    error::BotError,
+    events::ShutdownSignal,
+    path::Path,
+    slippage::SwapOrder,
+};
+
+/// How often node calls were retried since the bot started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetryStats {
+    /// Attempts made after a transient failure.
+    pub retries: u64,
+    /// Calls that succeeded after at least one retry.
+    pub recovered: u64,
+    /// Calls that still failed after their last attempt.
+    pub exhausted: u64,
+    /// Failed writes that turned out to have executed, so were not resubmitted.
+    pub landed: u64,
+}
+
+/// Retries transient (transport) failures with exponential backoff and jitter.
+pub struct Retry {
+    config: RetryConfig,
+    shutdown: ShutdownSignal,
+    stats: Cell<RetryStats>,
+}
+
+impl Retry {
+    pub fn new(config: &RetryConfig, shutdown: ShutdownSignal) -> Self {
+        Self {
+            config: config.clone(),
+            shutdown,
+            stats: Cell::new(RetryStats::default()),
+        }
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        self.stats.get()
+    }
+
+    /// Runs a read-only call, retrying it while it fails in transit.
+    pub fn read<T>(
+        &self,
+        call: &'static str,
+        mut f: impl FnMut() -> Result<T, BotError>,
+    ) -> Result<T, BotError> {
+        self.run(call, &mut f, &mut || Ok(None))
+    }
+
+    /// Runs a state-changing call. A call that failed in transit may still have
+    /// executed, so it is only resubmitted once `landed` confirms it did not.
+    pub fn write(
+        &self,
+        call: &'static str,
+        mut f: impl FnMut() -> Result<(), BotError>,
+        mut landed: impl FnMut() -> Result<bool, BotError>,
+    ) -> Result<(), BotError> {
+        self.run(call, &mut f, &mut || match landed() {
+            Ok(landed) => Ok(landed.then_some(())),
+            Err(_) => Err(BotError::OutcomeUnknown { call }),
+        })
+    }
+
+    fn run<T>(
+        &self,
+        call: &'static str,
+        f: &mut dyn FnMut() -> Result<T, BotError>,
+        landed: &mut dyn FnMut() -> Result<Option<T>, BotError>,
+    ) -> Result<T, BotError> {
+        let max_attempts = self.config.max_attempts;
+        let mut attempt = 1;
+        loop {
+            let error = match f() {
+                Ok(value) => {
+                    if attempt > 1 {
+                        self.update(|stats| stats.recovered += 1);
+                    }
+                    return Ok(value);
+                }
+                Err(e) if !e.is_transient() => return Err(e),
+                Err(e) => e,
+            };
+            if let Some(value) = landed()? {
+                tracing::warn!("{} failed ({}), but it executed", call, error);
+                self.update(|stats| stats.landed += 1);
+                return Ok(value);
+            }
+            if attempt >= max_attempts {
+                tracing::warn!("{} failed ({}), giving up", call, error);
+                self.update(|stats| stats.exhausted += 1);
+                return Err(error);
+            }
+            let delay = self.delay(attempt);
+            tracing::warn!(
+                "{} failed ({}), retrying in {:?} ({}/{})",
+                call,
+                error,
+                delay,
+                attempt,
+                max_attempts - 1
+            );
+            if self.shutdown.sleep(delay) {
+                return Err(error);
+            }
+            self.update(|stats| stats.retries += 1);
+            attempt += 1;
+        }
+    }
+
+    /// Delay before retry number `attempt`: `initial_delay` doubled per earlier
+    /// retry, capped at `max_delay`, then spread by `jitter` either way.
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let base = self
+            .config
+            .initial_delay()
+            .saturating_mul(factor)
+            .min(self.config.max_delay());
+        base.mul_f64(1.0 + self.config.jitter * (2.0 * random_unit() - 1.0))
+    }
+
+    fn update(&self, f: impl FnOnce(&mut RetryStats)) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+/// A random number in `[0, 1)`, drawn from the randomly keyed std hasher.
+fn random_unit() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Reads balances through `Retry`.
+pub struct RetryingBalances<'a> {
+    inner: &'a dyn Balances,
+    retry: &'a Retry,
+}
+
+impl<'a> RetryingBalances<'a> {
+    pub fn new(inner: &'a dyn Balances, retry: &'a Retry) -> Self {
+        Self { inner, retry }
+    }
+}
+
+impl Balances for RetryingBalances<'_> {
+    fn my_cspr_balance(&self) -> Result<U256, BotError> {
+        self.retry
+            .read("CSPR balance", || self.inner.my_cspr_balance())
+    }
+
+    fn my_wcspr_balance(&self) -> Result<U256, BotError> {
+        self.retry
+            .read("wCSPR balance", || self.inner.my_wcspr_balance())
+    }
+
+    fn my_long_balance(&self) -> Result<U256, BotError> {
+        self.retry
+            .read("LONG balance", || self.inner.my_long_balance())
+    }
+
+    fn my_short_balance(&self) -> Result<U256, BotError> {
+        self.retry
+            .read("SHORT balance", || self.inner.my_short_balance())
+    }
+}
+
+/// Sends token operations through `Retry`. Every write is resubmitted only
+/// after the balance it moves shows it did not execute; swaps never are.
+pub struct RetryingTokenManager<'a> {
+    inner: &'a dyn TokenManager,
+    balances: &'a dyn Balances,
+    retry: &'a Retry,
+}
+
+impl<'a> RetryingTokenManager<'a> {
+    pub fn new(inner: &'a dyn TokenManager, balances: &'a dyn Balances, retry: &'a Retry) -> Self {
+        Self {
+            inner,
+            balances,
+            retry,
+        }
+    }
+
+    /// Runs `f`, treating a change of `balance` after a failure as proof it executed.
+    fn write(
+        &self,
+        call: &'static str,
+        balance: impl Fn() -> Result<U256, BotError>,
+        f: impl FnMut() -> Result<(), BotError>,
+    ) -> Result<(), BotError> {
+        let before = balance()?;
+        self.retry.write(call, f, || Ok(balance()? != before))
+    }
+}
+
+impl TokenManager for RetryingTokenManager<'_> {
+    fn approve_markets(&self) -> Result<(), BotError> {
+        // Allowances that are already set are skipped, so resubmitting is safe.
+        self.retry.write(
+            "approve_markets",
+            || self.inner.approve_markets(),
+            || Ok(false),
+        )
+    }
+
+    fn wrap_cspr(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "wrap_cspr",
+            || self.balances.my_wcspr_balance(),
+            || self.inner.wrap_cspr(amount),
+        )
+    }
+
+    fn unwrap_wcspr(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "unwrap_wcspr",
+            || self.balances.my_wcspr_balance(),
+            || self.inner.unwrap_wcspr(amount),
+        )
+    }
+
+    fn buy_longs(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "buy_longs",
+            || self.balances.my_long_balance(),
+            || self.inner.buy_longs(amount),
+        )
+    }
+
+    fn buy_shorts(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "buy_shorts",
+            || self.balances.my_short_balance(),
+            || self.inner.buy_shorts(amount),
+        )
+    }
+
+    fn redeem_longs(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "redeem_longs",
+            || self.balances.my_long_balance(),
+            || self.inner.redeem_longs(amount),
+        )
+    }
+
+    fn redeem_shorts(&self, amount: U256) -> Result<(), BotError> {
+        self.write(
+            "redeem_shorts",
+            || self.balances.my_short_balance(),
+            || self.inner.redeem_shorts(amount),
+        )
+    }
+
+    fn swap(
+        &self,
+        path: Path,
+        order: SwapOrder,
+        recipient: Address,
+    ) -> Result<Vec<U256>, BotError> {
+        // The amounts a lost swap filled at can't be recovered from balances,
+        // so it is left to the next check to re-quote against fresh state.
+        self.inner.swap(path, order, recipient).map_err(|e| {
+            if e.is_transient() {
+                BotError::OutcomeUnknown { call: "swap" }
+            } else {
+                e
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::bot::slippage::SwapMode;
+
+    fn retry(max_attempts: u32) -> Retry {
+        let config = RetryConfig {
+            max_attempts,
+            initial_delay_ms: 1,
+            max_delay_ms: 1,
+            jitter: 0.0,
+        };
+        Retry::new(&config, ShutdownSignal::new())
+    }
+
+    fn transport() -> BotError {
+        BotError::Transport("connection reset".to_string())
+    }
+
+    /// Fails the first `failures` calls in transit, optionally after executing them.
+    #[derive(Default)]
+    struct FaultyNode {
+        failures: Cell<u32>,
+        execute_failed_calls: bool,
+        calls: Cell<u32>,
+        wcspr: Cell<u64>,
+        swaps: RefCell<Vec<SwapOrder>>,
+    }
+
+    impl FaultyNode {
+        fn failing(failures: u32) -> Self {
+            Self {
+                failures: Cell::new(failures),
+                ..Default::default()
+            }
+        }
+
+        /// Counts a call and injects the next fault, if any is left.
+        fn call(&self, execute: impl FnOnce()) -> Result<(), BotError> {
+            self.calls.set(self.calls.get() + 1);
+            if self.failures.get() == 0 {
+                execute();
+                return Ok(());
+            }
+            self.failures.set(self.failures.get() - 1);
+            if self.execute_failed_calls {
+                execute();
+            }
+            Err(transport())
+        }
+    }
+
+    impl Balances for FaultyNode {
+        fn my_cspr_balance(&self) -> Result<U256, BotError> {
+            self.call(|| ())?;
+            Ok(U256::from(1_000))
+        }
+
+        fn my_wcspr_balance(&self) -> Result<U256, BotError> {
+            Ok(U256::from(self.wcspr.get()))
+        }
+
+        fn my_long_balance(&self) -> Result<U256, BotError> {
+            Ok(U256::zero())
+        }
+
+        fn my_short_balance(&self) -> Result<U256, BotError> {
+            Ok(U256::zero())
+        }
+    }
+
+    impl TokenManager for FaultyNode {
+        fn approve_markets(&self) -> Result<(), BotError> {
+            self.call(|| ())
+        }
+
+        fn wrap_cspr(&self, amount: U256) -> Result<(), BotError> {
+            self.call(|| self.wcspr.set(self.wcspr.get() + amount.as_u64()))
+        }
+
+        fn unwrap_wcspr(&self, amount: U256) -> Result<(), BotError> {
+            self.call(|| self.wcspr.set(self.wcspr.get() - amount.as_u64()))
+        }
+
+        fn buy_longs(&self, _amount: U256) -> Result<(), BotError> {
+            self.call(|| ())
+        }
+
+        fn buy_shorts(&self, _amount: U256) -> Result<(), BotError> {
+            self.call(|| ())
+        }
+
+        fn redeem_longs(&self, _amount: U256) -> Result<(), BotError> {
+            self.call(|| ())
+        }
+
+        fn redeem_shorts(&self, _amount: U256) -> Result<(), BotError> {
+            self.call(|| ())
+        }
+
+        fn swap(
+            &self,
+            _path: Path,
+            order: SwapOrder,
+            _recipient: Address,
+        ) -> Result<Vec<U256>, BotError> {
+            self.call(|| self.swaps.borrow_mut().push(order))?;
+            Ok(vec![order.amount_in, order.amount_out])
+        }
+    }
+
+    #[test]
+    fn test_read_recovers_from_transient_failures() {
+        let node = FaultyNode::failing(2);
+        let retry = retry(3);
+        let balances = RetryingBalances::new(&node, &retry);
+
+        assert_eq!(balances.my_cspr_balance().unwrap(), U256::from(1_000));
+        assert_eq!(node.calls.get(), 3);
+        assert_eq!(
+            retry.stats(),
+            RetryStats {
+                retries: 2,
+                recovered: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_gives_up_after_max_attempts() {
+        let node = FaultyNode::failing(5);
+        let retry = retry(3);
+        let balances = RetryingBalances::new(&node, &retry);
+
+        assert!(matches!(
+            balances.my_cspr_balance(),
+            Err(BotError::Transport(_))
+        ));
+        assert_eq!(node.calls.get(), 3);
+        assert_eq!(retry.stats().exhausted, 1);
+    }
+
+    #[test]
+    fn test_non_transient_errors_are_not_retried() {
+        let retry = retry(3);
+        let calls = Cell::new(0);
+        let result: Result<(), BotError> = retry.read("get_reserves", || {
+            calls.set(calls.get() + 1);
+            Err(BotError::Reverted {
+                call: "get_reserves",
+                code: 7,
+            })
+        });
+
+        assert!(matches!(result, Err(BotError::Reverted { code: 7, .. })));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(retry.stats(), RetryStats::default());
+    }
+
+    #[test]
+    fn test_write_is_resubmitted_when_it_did_not_execute() {
+        let node = FaultyNode::failing(1);
+        let retry = retry(3);
+        let token_manager = RetryingTokenManager::new(&node, &node, &retry);
+
+        token_manager.wrap_cspr(U256::from(100)).unwrap();
+        assert_eq!(node.calls.get(), 2);
+        assert_eq!(node.wcspr.get(), 100);
+        assert_eq!(retry.stats().retries, 1);
+    }
+
+    #[test]
+    fn test_write_that_executed_is_not_resubmitted() {
+        let node = FaultyNode {
+            execute_failed_calls: true,
+            ..FaultyNode::failing(1)
+        };
+        let retry = retry(3);
+        let token_manager = RetryingTokenManager::new(&node, &node, &retry);
+
+        token_manager.wrap_cspr(U256::from(100)).unwrap();
+        assert_eq!(node.calls.get(), 1);
+        assert_eq!(node.wcspr.get(), 100);
+        assert_eq!(
+            retry.stats(),
+            RetryStats {
+                landed: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_swap_with_unknown_outcome_is_never_resubmitted() {
+        let node = FaultyNode {
+            execute_failed_calls: true,
+            ..FaultyNode::failing(1)
+        };
+        let retry = retry(3);
+        let token_manager = RetryingTokenManager::new(&node, &node, &retry);
+        let order = SwapOrder {
+            mode: SwapMode::ExactOut,
+            amount_in: U256::from(110),
+            amount_out: U256::from(100),
+        };
+
+        let recipient = odra_test::env().get_account(0);
+        let result = token_manager.swap(Path::WcsprLong, order, recipient);
+        assert!(matches!(
+            result,
+            Err(BotError::OutcomeUnknown { call: "swap" })
+        ));
+        assert_eq!(node.swaps.borrow().len(), 1);
+        assert_eq!(node.calls.get(), 1);
+    }
+
+    #[test]
+    fn test_delay_grows_exponentially_up_to_the_cap() {
+        let config = RetryConfig {
+            max_attempts: 10,
+            initial_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter: 0.0,
+        };
+        let retry = Retry::new(&config, ShutdownSignal::new());
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(2), Duration::from_millis(200));
+        assert_eq!(retry.delay(4), Duration::from_millis(800));
+        assert_eq!(retry.delay(5), Duration::from_millis(1_000));
+        assert_eq!(retry.delay(40), Duration::from_millis(1_000));
+
+        let jittered = Retry::new(
+            &RetryConfig {
+                jitter: 0.5,
+                ..config
+            },
+            ShutdownSignal::new(),
+        );
+        for _ in 0..100 {
+            let delay = jittered.delay(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+    }
+}
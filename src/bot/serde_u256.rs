@@ -0,0 +1,42 @@
+/// `U256` as a decimal string, so amounts survive JSON exactly.
+pub mod decimal {
+    use odra::casper_types::U256;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        U256::from_dec_str(&value)
+            .map_err(|_| de::Error::custom(format!("invalid amount: {}", value)))
+    }
+}
+
+/// `Option<U256>` as an optional decimal string.
+pub mod option {
+    use odra::casper_types::U256;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &Option<U256>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&value.to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<U256>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| {
+                U256::from_dec_str(&value)
+                    .map_err(|_| de::Error::custom(format!("invalid amount: {}", value)))
+            })
+            .transpose()
+    }
+}
@@ -0,0 +1,557 @@
+use odra::casper_types::{U256, U512};
+
+use crate::bot::{
+    config::StrategyConfig,
+    data::PriceData,
+    graph::RouteGraph,
+    path::Path,
+    price::{narrow, widen, Gain, Price},
+    strategy::Position,
+    utils::PriceCalculator,
+};
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Constant-product output of a single hop, after the pair fee. Computed in
+/// 512 bits; zero in the (unreachable on-chain) case that even those overflow.
+pub fn get_amount_out(amount_in: U256, reserve_in: U256, reserve_out: U256, fee_bps: u64) -> U256 {
+    if amount_in.is_zero() || reserve_in.is_zero() || reserve_out.is_zero() {
+        return U256::zero();
+    }
+    let amount_in_with_fee = widen(amount_in) * U512::from(BPS_DENOMINATOR - fee_bps);
+    let numerator = amount_in_with_fee.checked_mul(widen(reserve_out));
+    let denominator = widen(reserve_in) * U512::from(BPS_DENOMINATOR) + amount_in_with_fee;
+    numerator.map_or(U256::zero(), |numerator| narrow(numerator / denominator))
+}
+
+/// Constant-product input a single hop needs to pay out `amount_out`, after the
+/// pair fee. Zero if the pool cannot pay it out, or if even 512 bits overflow.
+pub fn get_amount_in(amount_out: U256, reserve_in: U256, reserve_out: U256, fee_bps: u64) -> U256 {
+    if amount_out.is_zero() || reserve_in.is_zero() || amount_out >= reserve_out {
+        return U256::zero();
+    }
+    let numerator = widen(reserve_in)
+        .checked_mul(widen(amount_out))
+        .and_then(|product| product.checked_mul(U512::from(BPS_DENOMINATOR)));
+    let denominator = widen(reserve_out - amount_out) * U512::from(BPS_DENOMINATOR - fee_bps);
+    numerator.map_or(U256::zero(), |numerator| {
+        narrow(numerator / denominator + 1)
+    })
+}
+
+/// The input the whole path needs to pay out `amount_out` against the pools of
+/// `graph`. Zero if a hop has no pool or cannot pay out its share.
+pub fn quote_in(path: Path, amount_out: U256, graph: &RouteGraph, fee_bps: u64) -> U256 {
+    let Some(hops) = graph.hops(path) else {
+        return U256::zero();
+    };
+    hops.into_iter()
+        .rev()
+        .fold(amount_out, |amount, (reserve_in, reserve_out)| {
+            get_amount_in(amount, reserve_in, reserve_out, fee_bps)
+        })
+}
+
+/// Simulates the whole path against the pools of `graph`. Zero if a hop has no pool.
+pub fn quote(path: Path, amount_in: U256, graph: &RouteGraph, fee_bps: u64) -> U256 {
+    let Some(hops) = graph.hops(path) else {
+        return U256::zero();
+    };
+    hops.into_iter()
+        .fold(amount_in, |amount, (reserve_in, reserve_out)| {
+            get_amount_out(amount, reserve_in, reserve_out, fee_bps)
+        })
+}
+
+/// The largest input amount worth `max_notional` motes at fair value.
+pub fn max_notional_amount_in(path: Path, price_data: &PriceData, max_notional: u64) -> U256 {
+    path.token_in().map_or(U256::zero(), |token| {
+        price_data
+            .fair_price(token)
+            .base_for(U256::from(max_notional))
+    })
+}
+
+/// The fair CSPR value of `amount_in`, in motes; the inverse of `max_notional_amount_in`.
+pub fn notional(path: Path, amount_in: U256, price_data: &PriceData) -> U256 {
+    path.token_in().map_or(U256::zero(), |token| {
+        price_data.fair_value(token, amount_in)
+    })
+}
+
+/// Finds the input amount in `[0, max_amount_in]` that maximises the net gain
+/// of trading along `path`. Net gain is concave in the input for constant-product
+/// pools, so a ternary search converges on the optimum.
+/// Returns zero if no trade size gains anything.
+pub fn optimal_amount_in(
+    path: Path,
+    price_data: &PriceData,
+    graph: &RouteGraph,
+    max_amount_in: U256,
+    config: &StrategyConfig,
+) -> U256 {
+    if path.token_in().is_none() {
+        return U256::zero();
+    }
+    maximise(max_amount_in, |amount_in| {
+        let amount_out = quote(path, amount_in, graph, config.pair_fee_bps);
+        PriceCalculator::calc_gains_in_cspr(amount_in, amount_out, price_data, path, config)
+    })
+}
+
+/// Position tokens the market mints for `amount` wCSPR at `fair_price`, after its fee.
+pub fn minted_amount(amount: U256, fair_price: &Price, config: &StrategyConfig) -> U256 {
+    let after_fee = amount.saturating_mul(U256::from(BPS_DENOMINATOR - config.market_fee_bps))
+        / U256::from(BPS_DENOMINATOR);
+    fair_price.base_for(after_fee)
+}
+
+/// Fee the market charges on `amount` wCSPR deposited or paid out.
+pub fn market_fee(amount: U256, config: &StrategyConfig) -> U256 {
+    amount.saturating_mul(U256::from(config.market_fee_bps)) / U256::from(BPS_DENOMINATOR)
+}
+
+/// Net gain of depositing `amount` wCSPR for `position` tokens valued at their
+/// fair price: minus the market fee and the transaction.
+pub fn mint_gain(
+    position: Position,
+    amount: U256,
+    price_data: &PriceData,
+    config: &StrategyConfig,
+) -> Gain {
+    let fair_price = position.fair_price(price_data);
+    let minted = minted_amount(amount, fair_price, config);
+    Gain::between(
+        fair_price.quote(minted),
+        amount.saturating_add(config.market_tx_cost()),
+    )
+}
+
+/// Net gain of depositing `amount` wCSPR to mint `position` tokens and selling
+/// all of them into their pool, after the market fee and both transactions.
+pub fn mint_and_sell_gain(
+    position: Position,
+    amount: U256,
+    price_data: &PriceData,
+    graph: &RouteGraph,
+    config: &StrategyConfig,
+) -> Gain {
+    let path = position.sell_path();
+    let minted = minted_amount(amount, position.fair_price(price_data), config);
+    let amount_out = quote(path, minted, graph, config.pair_fee_bps);
+    let cost = amount
+        .saturating_add(config.market_tx_cost())
+        .saturating_add(config.tx_cost(path));
+    Gain::between(amount_out, cost)
+}
+
+/// Finds the wCSPR deposit in `[0, max_amount]` that maximises the net gain of
+/// minting `position` tokens and selling them. Returns zero if no deposit gains
+/// anything.
+pub fn optimal_mint_amount(
+    position: Position,
+    price_data: &PriceData,
+    graph: &RouteGraph,
+    max_amount: U256,
+    config: &StrategyConfig,
+) -> U256 {
+    maximise(max_amount, |amount| {
+        mint_and_sell_gain(position, amount, price_data, graph, config)
+    })
+}
+
+/// wCSPR the market pays out for redeeming `amount` tokens at `fair_price`, after its fee.
+pub fn redeemed_amount(amount: U256, fair_price: &Price, config: &StrategyConfig) -> U256 {
+    fair_price
+        .quote(amount)
+        .saturating_mul(U256::from(BPS_DENOMINATOR - config.market_fee_bps))
+        / U256::from(BPS_DENOMINATOR)
+}
+
+/// Net gain of redeeming `amount` `position` tokens valued at their fair price:
+/// minus the market fee and the transaction.
+pub fn redeem_gain(
+    position: Position,
+    amount: U256,
+    price_data: &PriceData,
+    config: &StrategyConfig,
+) -> Gain {
+    let fair_price = position.fair_price(price_data);
+    let redeemed = redeemed_amount(amount, fair_price, config);
+    Gain::between(
+        redeemed,
+        fair_price
+            .quote(amount)
+            .saturating_add(config.market_tx_cost()),
+    )
+}
+
+/// Net gain of buying `position` tokens with `amount` wCSPR and redeeming all
+/// of them in the market, after the market fee and both transactions.
+pub fn buy_and_redeem_gain(
+    position: Position,
+    amount: U256,
+    price_data: &PriceData,
+    graph: &RouteGraph,
+    config: &StrategyConfig,
+) -> Gain {
+    let path = position.buy_path();
+    let bought = quote(path, amount, graph, config.pair_fee_bps);
+    let amount_out = redeemed_amount(bought, position.fair_price(price_data), config);
+    let cost = amount
+        .saturating_add(config.tx_cost(path))
+        .saturating_add(config.market_tx_cost());
+    Gain::between(amount_out, cost)
+}
+
+/// Finds the wCSPR input in `[0, max_amount]` that maximises the net gain of
+/// buying `position` tokens and redeeming them. Returns zero if no input gains
+/// anything.
+pub fn optimal_buy_amount(
+    position: Position,
+    price_data: &PriceData,
+    graph: &RouteGraph,
+    max_amount: U256,
+    config: &StrategyConfig,
+) -> U256 {
+    maximise(max_amount, |amount| {
+        buy_and_redeem_gain(position, amount, price_data, graph, config)
+    })
+}
+
+/// The input in `[0, max]` with the highest `gain`, which must be concave, or
+/// zero if even that gain is not positive: trading nothing costs nothing, while
+/// `gain(0)` still pays the transaction.
+fn maximise(max: U256, gain: impl Fn(U256) -> Gain) -> U256 {
+    let mut low = U256::zero();
+    let mut high = max;
+    while high - low > U256::from(2) {
+        let third = (high - low) / 3;
+        let mid_low = low + third;
+        let mid_high = high - third;
+        if gain(mid_low) < gain(mid_high) {
+            low = mid_low;
+        } else {
+            high = mid_high;
+        }
+    }
+
+    let mut best = U256::zero();
+    let mut best_gain = gain(best);
+    let mut candidate = low;
+    while candidate <= high {
+        let candidate_gain = gain(candidate);
+        if candidate_gain > best_gain {
+            best = candidate;
+            best_gain = candidate_gain;
+        }
+        candidate += U256::one();
+    }
+    if best_gain <= Gain::zero() {
+        return U256::zero();
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::bot::data::PoolReserves;
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000_000;
+    const FEE: u64 = 30;
+
+    fn graph(long: u64, wcspr_long: u64, wcspr_short: u64, short: u64) -> RouteGraph {
+        RouteGraph::from(&PoolReserves {
+            long: U256::from(long) * ONE,
+            wcspr_long: U256::from(wcspr_long) * ONE,
+            wcspr_short: U256::from(wcspr_short) * ONE,
+            short: U256::from(short) * ONE,
+        })
+    }
+
+    #[test]
+    fn test_get_amount_out_applies_fee() {
+        // 100 in against 1_000/1_000 reserves: 100 * 0.997 * 1000 / (1000 + 99.7)
+        let out = get_amount_out(U256::from(100), U256::from(1_000), U256::from(1_000), FEE);
+        assert_eq!(out, U256::from(90));
+    }
+
+    #[test]
+    fn test_get_amount_out_zero_reserves() {
+        assert!(get_amount_out(U256::from(100), U256::zero(), U256::from(1_000), FEE).is_zero());
+        assert!(get_amount_out(U256::zero(), U256::from(1_000), U256::from(1_000), FEE).is_zero());
+    }
+
+    #[test]
+    fn test_quote_multi_hop_chains_both_pools() {
+        let graph = graph(1_000_000, 2_000_000, 3_000_000, 4_000_000);
+        let amount_in = U256::from(1_000) * ONE;
+        let reserve = |amount: u64| U256::from(amount) * ONE;
+        let via_wcspr = get_amount_out(amount_in, reserve(1_000_000), reserve(2_000_000), FEE);
+        let expected = get_amount_out(via_wcspr, reserve(3_000_000), reserve(4_000_000), FEE);
+        assert_eq!(
+            quote(Path::LongWcsprShort, amount_in, &graph, FEE),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_quote_in_pays_for_the_amount_out() {
+        let graph = graph(1_000_000, 2_000_000, 3_000_000, 4_000_000);
+        let amount_out = U256::from(1_000) * ONE;
+        let amount_in = quote_in(Path::LongWcsprShort, amount_out, &graph, FEE);
+        assert!(quote(Path::LongWcsprShort, amount_in, &graph, FEE) >= amount_out);
+        assert!(quote(Path::LongWcsprShort, amount_in - 1, &graph, FEE) < amount_out);
+    }
+
+    #[test]
+    fn test_get_amount_in_is_zero_past_the_reserve() {
+        let amount_in = get_amount_in(U256::from(1_000), U256::from(1_000), U256::from(1_000), FEE);
+        assert!(amount_in.is_zero());
+        // 99 in only pays out 89 against the reserves of the fee test above
+        let amount_in = get_amount_in(U256::from(90), U256::from(1_000), U256::from(1_000), FEE);
+        assert_eq!(amount_in, U256::from(100));
+    }
+
+    #[test]
+    fn test_quote_is_zero_without_a_pool() {
+        let graph = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        let long_short = "LongShort".parse().unwrap();
+        assert!(quote(long_short, U256::from(ONE), &graph, FEE).is_zero());
+    }
+
+    #[test]
+    fn test_optimal_amount_in_is_zero_without_mispricing() {
+        // DEX prices equal fair prices, every trade only pays fees and gas
+        let graph = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        let config = StrategyConfig::default();
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 1.0, 1.0);
+        let max = U256::from(100_000) * ONE;
+        let amount = optimal_amount_in(Path::LongWcspr, &price_data, &graph, max, &config);
+        assert!(amount.is_zero());
+    }
+
+    #[test]
+    fn test_optimal_amount_in_maximises_gain() {
+        // Long trades at 1.0 CSPR on the DEX but is worth 0.9 CSPR
+        let graph = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        let config = StrategyConfig::default();
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 0.9, 1.0);
+        let max = U256::from(100_000) * ONE;
+        let amount = optimal_amount_in(Path::LongWcspr, &price_data, &graph, max, &config);
+        assert!(!amount.is_zero());
+
+        let gain_at = |amount_in: U256| {
+            let out = quote(Path::LongWcspr, amount_in, &graph, FEE);
+            PriceCalculator::calc_gains_in_cspr(
+                amount_in,
+                out,
+                &price_data,
+                Path::LongWcspr,
+                &config,
+            )
+        };
+        let best = gain_at(amount);
+        assert!(best > Gain::from_cspr(1.0));
+        assert!(best >= gain_at(amount / 2));
+        assert!(best >= gain_at(amount + amount / 2));
+        // Roughly one dollar of longs, the old fixed probe size
+        assert!(best > gain_at(U256::from(25) * ONE));
+    }
+
+    #[test]
+    fn test_optimal_amount_in_respects_cap() {
+        let graph = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        let config = StrategyConfig::default();
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 0.9, 1.0);
+        let max = U256::from(500) * ONE;
+        let amount = optimal_amount_in(Path::LongWcspr, &price_data, &graph, max, &config);
+        // Gain is still increasing at the cap, up to mote-level rounding
+        assert!(amount <= max);
+        assert!(max - amount < U256::from(1_000));
+    }
+
+    #[test]
+    fn test_optimal_amount_in_multi_hop() {
+        // Long overvalued, short undervalued: sell long for short via wCSPR
+        let graph = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        let config = StrategyConfig::default();
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 0.9, 1.1);
+        let max = U256::from(100_000) * ONE;
+        let amount = optimal_amount_in(Path::LongWcsprShort, &price_data, &graph, max, &config);
+        assert!(!amount.is_zero());
+        assert!(amount < max);
+    }
+
+    #[test]
+    fn test_minted_amount_after_market_fee() {
+        let mut config = StrategyConfig::default();
+        let fair_price = Price::from_f64(0.5);
+        let amount = U256::from(100 * ONE);
+        assert_eq!(
+            minted_amount(amount, &fair_price, &config),
+            U256::from(200 * ONE)
+        );
+        config.market_fee_bps = 100;
+        assert_eq!(
+            minted_amount(amount, &fair_price, &config),
+            U256::from(198 * ONE)
+        );
+    }
+
+    #[test]
+    fn test_mint_gain_is_the_market_fee_and_transaction() {
+        let config = StrategyConfig {
+            market_fee_bps: 100,
+            ..StrategyConfig::default()
+        };
+        let price_data = PriceData::from_f64(1.1, 1.0, 0.04, 0.5, 1.0);
+        let amount = U256::from(200 * ONE);
+        assert_eq!(market_fee(amount, &config), U256::from(2 * ONE));
+        assert_eq!(
+            mint_gain(Position::Long, amount, &price_data, &config),
+            Gain::between(U256::from(198 * ONE), amount + config.market_tx_cost())
+        );
+    }
+
+    #[test]
+    fn test_optimal_mint_amount_shrinks_with_the_market_fee() {
+        // Long trades at 1.1 CSPR on the DEX and mints at 1.0
+        let graph = graph(1_000_000, 1_100_000, 1_000_000, 1_000_000);
+        let price_data = PriceData::from_f64(1.1, 1.0, 0.04, 1.0, 1.0);
+        let max = U256::from(100_000) * ONE;
+        let mut config = StrategyConfig::default();
+        let without_fee = optimal_mint_amount(Position::Long, &price_data, &graph, max, &config);
+        let gain = mint_and_sell_gain(Position::Long, without_fee, &price_data, &graph, &config);
+        assert!(gain > Gain::from_cspr(1.0));
+        assert!(without_fee < max);
+
+        config.market_fee_bps = 300;
+        let with_fee = optimal_mint_amount(Position::Long, &price_data, &graph, max, &config);
+        assert!(with_fee < without_fee);
+    }
+
+    #[test]
+    fn test_redeemed_amount_after_market_fee() {
+        let mut config = StrategyConfig::default();
+        let fair_price = Price::from_f64(0.5);
+        let amount = U256::from(200 * ONE);
+        assert_eq!(
+            redeemed_amount(amount, &fair_price, &config),
+            U256::from(100 * ONE)
+        );
+        config.market_fee_bps = 100;
+        assert_eq!(
+            redeemed_amount(amount, &fair_price, &config),
+            U256::from(99 * ONE)
+        );
+    }
+
+    #[test]
+    fn test_redeem_gain_is_the_market_fee_and_transaction() {
+        let config = StrategyConfig {
+            market_fee_bps: 100,
+            ..StrategyConfig::default()
+        };
+        let price_data = PriceData::from_f64(1.0, 0.9, 0.04, 1.0, 0.5);
+        let amount = U256::from(200 * ONE);
+        assert_eq!(
+            redeem_gain(Position::Short, amount, &price_data, &config),
+            Gain::between(
+                U256::from(99 * ONE),
+                U256::from(100 * ONE) + config.market_tx_cost()
+            )
+        );
+    }
+
+    #[test]
+    fn test_optimal_buy_amount_realises_the_gap_in_wcspr() {
+        // Short trades at 0.9 CSPR on the DEX and redeems at 1.0
+        let cheap_short = graph(1_000_000, 1_000_000, 900_000, 1_000_000);
+        let price_data = PriceData::from_f64(1.0, 0.9, 0.04, 1.0, 1.0);
+        let max = U256::from(100_000) * ONE;
+        let config = StrategyConfig::default();
+        let gain_at = |amount: U256| {
+            buy_and_redeem_gain(Position::Short, amount, &price_data, &cheap_short, &config)
+        };
+        let amount = optimal_buy_amount(Position::Short, &price_data, &cheap_short, max, &config);
+        assert!(!amount.is_zero());
+        assert!(amount < max);
+        assert!(gain_at(amount) > Gain::from_cspr(1.0));
+        assert!(gain_at(amount) >= gain_at(amount / 2));
+
+        // Nothing to gain when the pool trades at fair value
+        let fair = PriceData::from_f64(1.0, 1.0, 0.04, 1.0, 1.0);
+        let pools = graph(1_000_000, 1_000_000, 1_000_000, 1_000_000);
+        assert!(optimal_buy_amount(Position::Short, &fair, &pools, max, &config).is_zero());
+    }
+
+    #[test]
+    fn test_max_notional_amount_in() {
+        let max_notional = 10_000 * ONE;
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 0.5, 2.0);
+        assert_eq!(
+            max_notional_amount_in(Path::LongWcspr, &price_data, max_notional),
+            U256::from(max_notional * 2)
+        );
+        assert_eq!(
+            max_notional_amount_in(Path::ShortWcspr, &price_data, max_notional),
+            U256::from(max_notional / 2)
+        );
+        assert_eq!(
+            max_notional_amount_in(Path::WcsprLong, &price_data, max_notional),
+            U256::from(max_notional)
+        );
+        assert!(max_notional_amount_in(Path::Empty, &price_data, max_notional).is_zero());
+    }
+
+    #[test]
+    fn test_notional_at_fair_value() {
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 0.5, 2.0);
+        let amount = U256::from(100 * ONE);
+        assert_eq!(
+            notional(Path::LongWcspr, amount, &price_data),
+            U256::from(50 * ONE)
+        );
+        assert_eq!(
+            notional(Path::ShortWcsprLong, amount, &price_data),
+            U256::from(200 * ONE)
+        );
+        assert_eq!(notional(Path::WcsprShort, amount, &price_data), amount);
+    }
+
+    #[test]
+    fn test_maximise_is_zero_when_every_size_loses() {
+        // Larger sizes lose less, but none gains anything
+        let best = maximise(U256::from(100), |amount| {
+            Gain::between(amount, U256::from(200))
+        });
+        assert!(best.is_zero());
+        let best = maximise(U256::from(100), |amount| {
+            Gain::between(amount, U256::from(50))
+        });
+        assert_eq!(best, U256::from(100));
+    }
+
+    fn any_u256() -> impl Strategy<Value = U256> {
+        any::<[u8; 32]>().prop_map(|bytes| U256::from_little_endian(&bytes))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_hop_math_never_panics(
+            amount in any_u256(),
+            reserve_in in any_u256(),
+            reserve_out in any_u256(),
+            fee_bps in 0..BPS_DENOMINATOR,
+        ) {
+            let out = get_amount_out(amount, reserve_in, reserve_out, fee_bps);
+            prop_assert!(out <= reserve_out);
+            let _ = get_amount_in(amount, reserve_in, reserve_out, fee_bps);
+        }
+    }
+}
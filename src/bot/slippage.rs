@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use odra::casper_types::U256;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{config::SlippageConfig, sizing::BPS_DENOMINATOR};
+
+/// Which side of a router swap is fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapMode {
+    /// Spend exactly `amount_in`, receive at least `amount_out`.
+    ExactIn,
+    /// Receive exactly `amount_out`, spend at most `amount_in`.
+    ExactOut,
+}
+
+impl FromStr for SwapMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exact_in" => Ok(Self::ExactIn),
+            "exact_out" => Ok(Self::ExactOut),
+            _ => Err(format!("Unknown swap mode: {}", s)),
+        }
+    }
+}
+
+/// A router swap with its slippage bound applied.
+/// `amount_in` and `amount_out` are the worst case the router will accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapOrder {
+    pub mode: SwapMode,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Bounds a quoted swap by the configured tolerance: the fixed side keeps its
+/// quoted amount, the other side is moved against us by `tolerance_bps`.
+pub fn bound(quoted_in: U256, quoted_out: U256, config: &SlippageConfig) -> SwapOrder {
+    let denominator = U256::from(BPS_DENOMINATOR);
+    let tolerance = U256::from(config.tolerance_bps);
+    match config.mode {
+        SwapMode::ExactIn => bound_exact_in(quoted_in, quoted_out, config),
+        SwapMode::ExactOut => {
+            let scaled = quoted_in.saturating_mul(denominator + tolerance);
+            // Round up, so the cap never ends up below the quote.
+            let amount_in = (scaled + denominator - 1) / denominator;
+            SwapOrder {
+                mode: SwapMode::ExactOut,
+                amount_in,
+                amount_out: quoted_out,
+            }
+        }
+    }
+}
+
+/// Bounds a quoted swap as `ExactIn` whatever the configured mode, for an input
+/// that must be spent exactly, e.g. tokens an earlier action just produced.
+pub fn bound_exact_in(quoted_in: U256, quoted_out: U256, config: &SlippageConfig) -> SwapOrder {
+    let denominator = U256::from(BPS_DENOMINATOR);
+    let tolerance = U256::from(config.tolerance_bps);
+    SwapOrder {
+        mode: SwapMode::ExactIn,
+        amount_in: quoted_in,
+        amount_out: quoted_out.saturating_mul(denominator - tolerance) / denominator,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(tolerance_bps: u64, mode: SwapMode) -> SlippageConfig {
+        SlippageConfig {
+            tolerance_bps,
+            mode,
+        }
+    }
+
+    #[test]
+    fn test_exact_out_caps_input() {
+        let order = bound(
+            U256::from(10_000),
+            U256::from(9_000),
+            &config(50, SwapMode::ExactOut),
+        );
+        assert_eq!(order.amount_in, U256::from(10_050));
+        assert_eq!(order.amount_out, U256::from(9_000));
+
+        let order = bound(
+            U256::from(3),
+            U256::from(1),
+            &config(50, SwapMode::ExactOut),
+        );
+        assert_eq!(order.amount_in, U256::from(4));
+    }
+
+    #[test]
+    fn test_exact_in_sets_minimum_output() {
+        let order = bound(
+            U256::from(10_000),
+            U256::from(9_000),
+            &config(100, SwapMode::ExactIn),
+        );
+        assert_eq!(order.mode, SwapMode::ExactIn);
+        assert_eq!(order.amount_in, U256::from(10_000));
+        assert_eq!(order.amount_out, U256::from(8_910));
+    }
+
+    #[test]
+    fn test_bound_exact_in_ignores_configured_mode() {
+        let (quoted_in, quoted_out) = (U256::from(10_000), U256::from(9_000));
+        assert_eq!(
+            bound_exact_in(quoted_in, quoted_out, &config(100, SwapMode::ExactOut)),
+            bound(quoted_in, quoted_out, &config(100, SwapMode::ExactIn))
+        );
+    }
+
+    #[test]
+    fn test_zero_tolerance_keeps_quote() {
+        for mode in [SwapMode::ExactIn, SwapMode::ExactOut] {
+            let order = bound(U256::from(777), U256::from(555), &config(0, mode));
+            assert_eq!(
+                (order.amount_in, order.amount_out),
+                (U256::from(777), U256::from(555))
+            );
+        }
+    }
+}
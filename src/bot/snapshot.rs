@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::{
+    data::{MarketReading, PoolReserves},
+    serde_u256,
+};
+
+/// Columns of the CSV format, in order. Readers match columns by name;
+/// `oracle_price` may be empty or missing.
+const CSV_COLUMNS: [&str; 11] = [
+    "timestamp",
+    "long",
+    "wcspr_long",
+    "wcspr_short",
+    "short",
+    "long_liquidity",
+    "long_total_supply",
+    "short_liquidity",
+    "short_total_supply",
+    "price",
+    "oracle_price",
+];
+
+/// Pool reserves and market state at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SnapshotRecord", into = "SnapshotRecord")]
+pub struct MarketSnapshot {
+    pub timestamp: DateTime<Utc>,
+    pub reserves: PoolReserves,
+    pub market: MarketReading,
+    /// CSPR/USD TWAP of the Styks price feed, when it was available.
+    pub oracle_price: Option<U256>,
+}
+
+impl MarketSnapshot {
+    pub fn csv_header() -> String {
+        CSV_COLUMNS.join(",")
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        let record = SnapshotRecord::from(*self);
+        let mut fields = vec![record.timestamp.to_rfc3339()];
+        fields.extend(record.amounts().iter().map(|amount| amount.to_string()));
+        fields.push(
+            record
+                .oracle_price
+                .map(|price| price.to_string())
+                .unwrap_or_default(),
+        );
+        fields.join(",")
+    }
+
+    fn from_csv_row(columns: &HashMap<&str, usize>, row: &str) -> Result<Self, String> {
+        let fields = row.split(',').map(str::trim).collect::<Vec<_>>();
+        let field = |name: &str| -> Result<&str, String> {
+            columns
+                .get(name)
+                .and_then(|index| fields.get(*index))
+                .copied()
+                .ok_or_else(|| format!("missing column {}", name))
+        };
+        let amount = |name: &str| -> Result<U256, String> {
+            let value = field(name)?;
+            U256::from_dec_str(value).map_err(|_| format!("invalid {}: {}", name, value))
+        };
+        let oracle_price = match field("oracle_price").unwrap_or_default() {
+            "" => None,
+            _ => Some(amount("oracle_price")?),
+        };
+        let timestamp = DateTime::parse_from_rfc3339(field("timestamp")?)
+            .map_err(|e| format!("invalid timestamp: {}", e))?
+            .with_timezone(&Utc);
+        Ok(Self {
+            timestamp,
+            reserves: PoolReserves {
+                long: amount("long")?,
+                wcspr_long: amount("wcspr_long")?,
+                wcspr_short: amount("wcspr_short")?,
+                short: amount("short")?,
+            },
+            market: MarketReading {
+                long_liquidity: amount("long_liquidity")?,
+                long_total_supply: amount("long_total_supply")?,
+                short_liquidity: amount("short_liquidity")?,
+                short_total_supply: amount("short_total_supply")?,
+                price: amount("price")?,
+            },
+            oracle_price,
+        })
+    }
+}
+
+/// Reads snapshots from a CSV file with a header row, or from JSONL if the file
+/// name ends in `.jsonl`/`.json`. If `path` is a directory, every file in it is
+/// read. Snapshots are returned in timestamp order.
+pub fn read_snapshots(path: &str) -> Result<Vec<MarketSnapshot>, Error> {
+    if std::path::Path::new(path).is_dir() {
+        let mut snapshots = vec![];
+        let entries = fs::read_dir(path).map_err(|e| snapshot_error(path, e))?;
+        for entry in entries {
+            let file = entry.map_err(|e| snapshot_error(path, e))?.path();
+            if file.is_file() {
+                snapshots.extend(read_snapshots(&file.to_string_lossy())?);
+            }
+        }
+        snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+        return Ok(snapshots);
+    }
+
+    let file = File::open(path).map_err(|e| snapshot_error(path, e))?;
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| snapshot_error(path, e))?;
+    let jsonl = path.ends_with(".jsonl") || path.ends_with(".json");
+    let mut rows = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let mut snapshots = vec![];
+    if jsonl {
+        for (index, line) in rows {
+            let snapshot = serde_json::from_str(line)
+                .map_err(|e| snapshot_error(path, format!("line {}: {}", index + 1, e)))?;
+            snapshots.push(snapshot);
+        }
+    } else {
+        let Some((_, header)) = rows.next() else {
+            return Ok(snapshots);
+        };
+        let columns = header
+            .split(',')
+            .map(str::trim)
+            .enumerate()
+            .map(|(index, name)| (name, index))
+            .collect::<HashMap<_, _>>();
+        for (index, line) in rows {
+            let snapshot = MarketSnapshot::from_csv_row(&columns, line)
+                .map_err(|e| snapshot_error(path, format!("line {}: {}", index + 1, e)))?;
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+    Ok(snapshots)
+}
+
+/// Appends snapshots to CSV files in a directory, one file per UTC day.
+/// A day that outgrows `max_file_bytes` continues in `snapshots-<date>.1.csv`,
+/// `.2.csv` and so on. Restarts append to the latest file of the day.
+pub struct SnapshotWriter {
+    dir: PathBuf,
+    max_file_bytes: u64,
+    current: Option<SnapshotFile>,
+}
+
+struct SnapshotFile {
+    date: NaiveDate,
+    file: File,
+    path: String,
+    bytes: u64,
+}
+
+impl SnapshotWriter {
+    pub fn new(dir: &str, max_file_bytes: u64) -> Result<Self, Error> {
+        fs::create_dir_all(dir).map_err(|e| snapshot_error(dir, e))?;
+        Ok(Self {
+            dir: PathBuf::from(dir),
+            max_file_bytes,
+            current: None,
+        })
+    }
+
+    /// Appends `snapshot`, rotating files first if needed. Returns the file written to.
+    pub fn write(&mut self, snapshot: &MarketSnapshot) -> Result<&str, Error> {
+        let date = snapshot.timestamp.date_naive();
+        let rotate = self
+            .current
+            .as_ref()
+            .is_none_or(|current| current.date != date || current.bytes >= self.max_file_bytes);
+        if rotate {
+            self.current = Some(self.open(date)?);
+        }
+        let current = self.current.as_mut().expect("snapshot file is open");
+        let line = format!("{}\n", snapshot.to_csv_row());
+        (&current.file)
+            .write_all(line.as_bytes())
+            .and_then(|_| current.file.sync_data())
+            .map_err(|e| snapshot_error(&current.path, e))?;
+        current.bytes += line.len() as u64;
+        Ok(&current.path)
+    }
+
+    /// Opens the first file of `date` that still has room, writing the header
+    /// if it is new.
+    fn open(&self, date: NaiveDate) -> Result<SnapshotFile, Error> {
+        let mut part = 0;
+        loop {
+            let name = match part {
+                0 => format!("snapshots-{}.csv", date),
+                _ => format!("snapshots-{}.{}.csv", date, part),
+            };
+            let path = self.dir.join(name).to_string_lossy().to_string();
+            let bytes = fs::metadata(&path).map(|meta| meta.len()).unwrap_or(0);
+            if bytes >= self.max_file_bytes {
+                part += 1;
+                continue;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| snapshot_error(&path, e))?;
+            let mut bytes = bytes;
+            if bytes == 0 {
+                let header = format!("{}\n", MarketSnapshot::csv_header());
+                file.write_all(header.as_bytes())
+                    .map_err(|e| snapshot_error(&path, e))?;
+                bytes = header.len() as u64;
+            }
+            tracing::info!("Recording snapshots to {}", path);
+            return Ok(SnapshotFile {
+                date,
+                file,
+                path,
+                bytes,
+            });
+        }
+    }
+}
+
+fn snapshot_error(path: &str, e: impl std::fmt::Display) -> Error {
+    Error::OdraError {
+        message: format!("Snapshot file {}: {}", path, e),
+    }
+}
+
+/// Flat on-disk layout of a snapshot, shared by the CSV and JSONL formats.
+#[derive(Serialize, Deserialize)]
+struct SnapshotRecord {
+    timestamp: DateTime<Utc>,
+    #[serde(with = "serde_u256::decimal")]
+    long: U256,
+    #[serde(with = "serde_u256::decimal")]
+    wcspr_long: U256,
+    #[serde(with = "serde_u256::decimal")]
+    wcspr_short: U256,
+    #[serde(with = "serde_u256::decimal")]
+    short: U256,
+    #[serde(with = "serde_u256::decimal")]
+    long_liquidity: U256,
+    #[serde(with = "serde_u256::decimal")]
+    long_total_supply: U256,
+    #[serde(with = "serde_u256::decimal")]
+    short_liquidity: U256,
+    #[serde(with = "serde_u256::decimal")]
+    short_total_supply: U256,
+    #[serde(with = "serde_u256::decimal")]
+    price: U256,
+    #[serde(default, with = "serde_u256::option")]
+    oracle_price: Option<U256>,
+}
+
+impl SnapshotRecord {
+    /// Every amount column, in `CSV_COLUMNS` order.
+    fn amounts(&self) -> [U256; 9] {
+        [
+            self.long,
+            self.wcspr_long,
+            self.wcspr_short,
+            self.short,
+            self.long_liquidity,
+            self.long_total_supply,
+            self.short_liquidity,
+            self.short_total_supply,
+            self.price,
+        ]
+    }
+}
+
+impl From<MarketSnapshot> for SnapshotRecord {
+    fn from(snapshot: MarketSnapshot) -> Self {
+        Self {
+            timestamp: snapshot.timestamp,
+            long: snapshot.reserves.long,
+            wcspr_long: snapshot.reserves.wcspr_long,
+            wcspr_short: snapshot.reserves.wcspr_short,
+            short: snapshot.reserves.short,
+            long_liquidity: snapshot.market.long_liquidity,
+            long_total_supply: snapshot.market.long_total_supply,
+            short_liquidity: snapshot.market.short_liquidity,
+            short_total_supply: snapshot.market.short_total_supply,
+            price: snapshot.market.price,
+            oracle_price: snapshot.oracle_price,
+        }
+    }
+}
+
+impl From<SnapshotRecord> for MarketSnapshot {
+    fn from(record: SnapshotRecord) -> Self {
+        Self {
+            timestamp: record.timestamp,
+            reserves: PoolReserves {
+                long: record.long,
+                wcspr_long: record.wcspr_long,
+                wcspr_short: record.wcspr_short,
+                short: record.short,
+            },
+            market: MarketReading {
+                long_liquidity: record.long_liquidity,
+                long_total_supply: record.long_total_supply,
+                short_liquidity: record.short_liquidity,
+                short_total_supply: record.short_total_supply,
+                price: record.price,
+            },
+            oracle_price: record.oracle_price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn snapshot(second: u32) -> MarketSnapshot {
+        MarketSnapshot {
+            timestamp: DateTime::parse_from_rfc3339(&format!("2026-03-01T00:00:{:02}Z", second))
+                .unwrap()
+                .with_timezone(&Utc),
+            reserves: PoolReserves {
+                long: U256::from(1_000u64),
+                wcspr_long: U256::from(2_000u64),
+                wcspr_short: U256::from(3_000u64),
+                short: U256::MAX,
+            },
+            market: MarketReading {
+                long_liquidity: U256::from(5u64),
+                long_total_supply: U256::from(6u64),
+                short_liquidity: U256::from(7u64),
+                short_total_supply: U256::from(8u64),
+                price: U256::from(1_234u64),
+            },
+            oracle_price: Some(U256::from(1_230u64)),
+        }
+    }
+
+    fn write_file(name: &str, content: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("bot-snapshots-{}-{}", std::process::id(), name));
+        File::create(&path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_csv_round_trip_sorted_by_time() {
+        let content = [
+            MarketSnapshot::csv_header(),
+            snapshot(2).to_csv_row(),
+            snapshot(1).to_csv_row(),
+        ]
+        .join("\n");
+        let path = write_file("round-trip.csv", &content);
+        assert_eq!(
+            read_snapshots(&path).unwrap(),
+            vec![snapshot(1), snapshot(2)]
+        );
+    }
+
+    #[test]
+    fn test_csv_columns_are_matched_by_name() {
+        let row = snapshot(1).to_csv_row();
+        let mut fields = row.split(',').collect::<Vec<_>>();
+        fields.swap(1, 9);
+        let mut header = CSV_COLUMNS.to_vec();
+        header.swap(1, 9);
+        let content = format!("{},extra\n{},ignored\n", header.join(","), fields.join(","));
+        let path = write_file("reordered.csv", &content);
+        assert_eq!(read_snapshots(&path).unwrap(), vec![snapshot(1)]);
+
+        let path = write_file("missing.csv", "timestamp,long\n2026-03-01T00:00:00Z,1\n");
+        assert!(read_snapshots(&path).is_err());
+    }
+
+    #[test]
+    fn test_oracle_price_is_optional_and_directories_are_merged() {
+        let dir = std::env::temp_dir().join(format!("bot-snapshots-{}-dir", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut without_oracle = snapshot(1);
+        without_oracle.oracle_price = None;
+        let header = CSV_COLUMNS[..10].join(",");
+        let row = without_oracle.to_csv_row();
+        let row = row.strip_suffix(',').unwrap();
+        fs::write(dir.join("b.csv"), format!("{}\n{}\n", header, row)).unwrap();
+        let content = [MarketSnapshot::csv_header(), snapshot(0).to_csv_row()].join("\n");
+        fs::write(dir.join("a.csv"), content).unwrap();
+
+        let snapshots = read_snapshots(&dir.to_string_lossy()).unwrap();
+        assert_eq!(snapshots, vec![snapshot(0), without_oracle]);
+    }
+
+    #[test]
+    fn test_writer_rotates_daily_and_by_size() {
+        let dir = std::env::temp_dir().join(format!("bot-snapshots-{}-writer", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir = dir.to_string_lossy().to_string();
+        let row_bytes = snapshot(0).to_csv_row().len() as u64 + 1;
+        let header_bytes = MarketSnapshot::csv_header().len() as u64 + 1;
+        let max_file_bytes = header_bytes + 2 * row_bytes;
+
+        let mut writer = SnapshotWriter::new(&dir, max_file_bytes).unwrap();
+        let mut files = vec![];
+        for second in 0..3 {
+            files.push(writer.write(&snapshot(second)).unwrap().to_string());
+        }
+        let mut next_day = snapshot(0);
+        next_day.timestamp += chrono::Duration::days(1);
+        files.push(writer.write(&next_day).unwrap().to_string());
+        assert!(files[0].ends_with("snapshots-2026-03-01.csv"));
+        assert_eq!(files[1], files[0]);
+        assert!(files[2].ends_with("snapshots-2026-03-01.1.csv"));
+        assert!(files[3].ends_with("snapshots-2026-03-02.csv"));
+
+        // A restarted recorder skips the full file and appends to the next one.
+        let mut writer = SnapshotWriter::new(&dir, max_file_bytes).unwrap();
+        let file = writer.write(&snapshot(3)).unwrap().to_string();
+        assert_eq!(file, files[2]);
+        assert_eq!(
+            read_snapshots(&file).unwrap(),
+            vec![snapshot(2), snapshot(3)]
+        );
+        assert_eq!(read_snapshots(&dir).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let content = [snapshot(1), snapshot(2)]
+            .iter()
+            .map(|snapshot| serde_json::to_string(snapshot).unwrap())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(content.contains(&format!("\"short\":\"{}\"", U256::MAX)));
+        let path = write_file("round-trip.jsonl", &content);
+        assert_eq!(
+            read_snapshots(&path).unwrap(),
+            vec![snapshot(1), snapshot(2)]
+        );
+    }
+}
@@ -0,0 +1,735 @@
+use odra::casper_types::U256;
+use odra_cli::scenario::Error;
+
+use crate::bot::{
+    config::{BotConfig, MarketConfig, StrategyConfig},
+    data::PriceData,
+    graph::RouteGraph,
+    path::{Path, Token},
+    price::{Gain, Price},
+    sizing,
+    utils::PriceCalculator,
+};
+
+/// Strategy used when none is named.
+pub const DEFAULT_STRATEGY: &str = "deviation";
+
+/// A position token of the market.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    Long,
+    Short,
+}
+
+impl Position {
+    /// The position's price in its pool and its fair price, in CSPR.
+    pub fn prices(self, price_data: &PriceData) -> (&Price, &Price) {
+        match self {
+            Position::Long => (&price_data.long_price, &price_data.long_fair_price),
+            Position::Short => (&price_data.short_price, &price_data.short_fair_price),
+        }
+    }
+
+    pub fn fair_price(self, price_data: &PriceData) -> &Price {
+        self.prices(price_data).1
+    }
+
+    /// The swap selling the position for wCSPR.
+    pub fn sell_path(self) -> Path {
+        match self {
+            Position::Long => Path::LongWcspr,
+            Position::Short => Path::ShortWcspr,
+        }
+    }
+
+    /// The swap buying the position with wCSPR.
+    pub fn buy_path(self) -> Path {
+        match self {
+            Position::Long => Path::WcsprLong,
+            Position::Short => Path::WcsprShort,
+        }
+    }
+}
+
+/// A step proposed by a strategy, for the engine to execute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Swap `amount_in` of the path's input token along `path`. A zero
+    /// `amount_in` records that no trade size was profitable.
+    Swap { path: Path, amount_in: U256 },
+    /// Deposit `amount` wCSPR into the market for `position` tokens at fair value.
+    Mint { position: Position, amount: U256 },
+    /// Withdraw `amount` `position` tokens from the market for wCSPR at fair value.
+    Redeem { position: Position, amount: U256 },
+}
+
+/// Actions to execute in order, each building on the ones before it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+    /// Net gain expected at the prices the plan was made on.
+    pub expected_gain: Gain,
+}
+
+impl Plan {
+    /// The market deposit or withdrawal the plan makes next to its swaps, if any.
+    pub fn market_call(&self) -> Option<Action> {
+        self.actions
+            .iter()
+            .copied()
+            .find(|action| !matches!(action, Action::Swap { .. }))
+    }
+}
+
+/// What the bot holds when a strategy plans.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Inventory {
+    pub wcspr: U256,
+    pub long: U256,
+    pub short: U256,
+    /// wCSPR a single top-up adds before a swap, by wrapping CSPR or minting.
+    pub top_up: U256,
+}
+
+impl Inventory {
+    pub fn balance(&self, token: Token) -> U256 {
+        match token {
+            Token::Wcspr => self.wcspr,
+            Token::Long => self.long,
+            Token::Short => self.short,
+            Token::Other(_) => U256::zero(),
+        }
+    }
+
+    /// Largest input amount the bot can fund for `path`: the current balance of
+    /// the input token plus whatever a single top-up would add.
+    pub fn available_amount_in(&self, path: Path, price_data: &PriceData) -> U256 {
+        path.token_in().map_or(U256::zero(), |token| {
+            self.balance(token) + price_data.fair_price(token).base_for(self.top_up)
+        })
+    }
+
+    /// What is left once `amount_in` of the input token of `path` is spent.
+    /// Whatever the balance does not cover is funded by the swap's own top-up.
+    pub fn spend(mut self, path: Path, amount_in: U256) -> Self {
+        let balance = match path.token_in() {
+            Some(Token::Wcspr) => &mut self.wcspr,
+            Some(Token::Long) => &mut self.long,
+            Some(Token::Short) => &mut self.short,
+            Some(Token::Other(_)) | None => return self,
+        };
+        *balance = balance.saturating_sub(amount_in);
+        self
+    }
+}
+
+/// Detects opportunities in the current prices and proposes what to do about
+/// them. Quoting, risk checks and execution are left to the engine.
+pub trait Strategy {
+    fn name(&self) -> &'static str;
+
+    /// Independent plans, best first; empty if there is nothing to do. No two
+    /// plans trade through the same pool, and together they fit the inventory.
+    fn propose(
+        &self,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        inventory: &Inventory,
+    ) -> Vec<Plan>;
+}
+
+/// Swaps along every path whose pool prices deviate from fair value, sized for
+/// the largest net gain and ranked by it.
+pub struct DeviationStrategy<'a> {
+    config: &'a StrategyConfig,
+    max_notional: u64,
+}
+
+impl<'a> DeviationStrategy<'a> {
+    /// A strategy whose trades are worth at most `max_notional` motes.
+    pub fn new(config: &'a StrategyConfig, max_notional: u64) -> Self {
+        Self {
+            config,
+            max_notional,
+        }
+    }
+
+    /// A swap along `path`, capped by the inventory and the maximum notional.
+    fn size(
+        &self,
+        path: Path,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        inventory: &Inventory,
+    ) -> (Plan, Inventory) {
+        let available = inventory.available_amount_in(path, price_data);
+        let max_notional = sizing::max_notional_amount_in(path, price_data, self.max_notional);
+        let max_amount_in = available.min(max_notional);
+        let amount_in =
+            sizing::optimal_amount_in(path, price_data, graph, max_amount_in, self.config);
+        let amount_out = sizing::quote(path, amount_in, graph, self.config.pair_fee_bps);
+        let plan = Plan {
+            actions: vec![Action::Swap { path, amount_in }],
+            expected_gain: PriceCalculator::calc_gains_in_cspr(
+                amount_in,
+                amount_out,
+                price_data,
+                path,
+                self.config,
+            ),
+        };
+        (plan, inventory.spend(path, amount_in))
+    }
+}
+
+impl Strategy for DeviationStrategy<'_> {
+    fn name(&self) -> &'static str {
+        "deviation"
+    }
+
+    fn propose(
+        &self,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        inventory: &Inventory,
+    ) -> Vec<Plan> {
+        let candidates = Path::candidates(graph, price_data, self.config);
+        let size = |path, inventory: &Inventory| self.size(path, price_data, graph, inventory);
+        let plans = pick(self.config, &candidates, inventory, |path| path, size);
+        if !plans.is_empty() {
+            return plans;
+        }
+        // Let the engine quote the best candidate and journal why it is not traded
+        candidates
+            .iter()
+            .map(|path| size(*path, inventory).0)
+            .max_by_key(|plan| plan.expected_gain)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// When a position trades above fair value, deposits wCSPR into the market to
+/// mint exactly the tokens to sell and sells them into the overpriced pool.
+/// The deposit is sized by the gap between the mint and pool prices, after the
+/// market fee and the cost of both transactions.
+pub struct MintAndSellStrategy<'a> {
+    config: &'a StrategyConfig,
+    max_notional: u64,
+}
+
+impl<'a> MintAndSellStrategy<'a> {
+    /// A strategy whose trades are worth at most `max_notional` motes.
+    pub fn new(config: &'a StrategyConfig, max_notional: u64) -> Self {
+        Self {
+            config,
+            max_notional,
+        }
+    }
+}
+
+impl Strategy for MintAndSellStrategy<'_> {
+    fn name(&self) -> &'static str {
+        "mint-and-sell"
+    }
+
+    fn propose(
+        &self,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        inventory: &Inventory,
+    ) -> Vec<Plan> {
+        let candidates = mispriced(self.config, price_data, true);
+        let size = |position: Position, inventory: &Inventory| {
+            // A deposit short of wCSPR wraps a single top-up first
+            let max_amount = (inventory.wcspr + inventory.top_up).min(self.max_notional.into());
+            let amount =
+                sizing::optimal_mint_amount(position, price_data, graph, max_amount, self.config);
+            let minted =
+                sizing::minted_amount(amount, position.fair_price(price_data), self.config);
+            let plan = Plan {
+                actions: vec![
+                    Action::Mint { position, amount },
+                    Action::Swap {
+                        path: position.sell_path(),
+                        amount_in: minted,
+                    },
+                ],
+                expected_gain: sizing::mint_and_sell_gain(
+                    position,
+                    amount,
+                    price_data,
+                    graph,
+                    self.config,
+                ),
+            };
+            // The deposit spends wCSPR like a purchase would
+            (plan, inventory.spend(position.buy_path(), amount))
+        };
+        pick(
+            self.config,
+            &candidates,
+            inventory,
+            Position::sell_path,
+            size,
+        )
+    }
+}
+
+/// When a position trades below fair value, buys it in its pool and redeems
+/// what it bought in the market, so the position inventory is left as it was
+/// and the gap is realised in wCSPR. The purchase is sized by the gap between
+/// the pool and redemption prices, after the market fee and the cost of both
+/// transactions.
+pub struct BuyAndRedeemStrategy<'a> {
+    config: &'a StrategyConfig,
+    max_notional: u64,
+}
+
+impl<'a> BuyAndRedeemStrategy<'a> {
+    /// A strategy whose trades are worth at most `max_notional` motes.
+    pub fn new(config: &'a StrategyConfig, max_notional: u64) -> Self {
+        Self {
+            config,
+            max_notional,
+        }
+    }
+}
+
+impl Strategy for BuyAndRedeemStrategy<'_> {
+    fn name(&self) -> &'static str {
+        "buy-and-redeem"
+    }
+
+    fn propose(
+        &self,
+        price_data: &PriceData,
+        graph: &RouteGraph,
+        inventory: &Inventory,
+    ) -> Vec<Plan> {
+        let candidates = mispriced(self.config, price_data, false);
+        let size = |position: Position, inventory: &Inventory| {
+            let path = position.buy_path();
+            let available = inventory.available_amount_in(path, price_data);
+            let max_notional = sizing::max_notional_amount_in(path, price_data, self.max_notional);
+            let max_amount = available.min(max_notional);
+            let amount =
+                sizing::optimal_buy_amount(position, price_data, graph, max_amount, self.config);
+            let bought = sizing::quote(path, amount, graph, self.config.pair_fee_bps);
+            let plan = Plan {
+                actions: vec![
+                    Action::Swap {
+                        path,
+                        amount_in: amount,
+                    },
+                    Action::Redeem {
+                        position,
+                        amount: bought,
+                    },
+                ],
+                expected_gain: sizing::buy_and_redeem_gain(
+                    position,
+                    amount,
+                    price_data,
+                    graph,
+                    self.config,
+                ),
+            };
+            (plan, inventory.spend(path, amount))
+        };
+        pick(
+            self.config,
+            &candidates,
+            inventory,
+            Position::buy_path,
+            size,
+        )
+    }
+}
+
+/// The positions trading above (`overvalued`) or below fair value by more than
+/// the threshold.
+fn mispriced(config: &StrategyConfig, price_data: &PriceData, overvalued: bool) -> Vec<Position> {
+    let threshold_bps = config.diff_threshold_bps();
+    [Position::Long, Position::Short]
+        .into_iter()
+        .filter(|position| {
+            let (price, fair_price) = position.prices(price_data);
+            let mispriced = if overvalued {
+                price > fair_price
+            } else {
+                price < fair_price
+            };
+            mispriced && price.deviates_from(fair_price, threshold_bps)
+        })
+        .collect()
+}
+
+/// Ranks `candidates` by the gain `size` expects from each on its own, then
+/// picks them best first. Each pick is sized again against the inventory the
+/// earlier picks leave, and dropped if it falls below the minimum gain or
+/// trades through a pool an earlier pick uses, whose swap moves the reserves
+/// it was sized against.
+fn pick<C: Copy>(
+    config: &StrategyConfig,
+    candidates: &[C],
+    inventory: &Inventory,
+    path: impl Fn(C) -> Path,
+    size: impl Fn(C, &Inventory) -> (Plan, Inventory),
+) -> Vec<Plan> {
+    let mut ranked = candidates
+        .iter()
+        .map(|candidate| (*candidate, size(*candidate, inventory).0.expected_gain))
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let mut inventory = *inventory;
+    let mut paths: Vec<Path> = vec![];
+    let mut plans = vec![];
+    for (candidate, _) in ranked {
+        let candidate_path = path(candidate);
+        if paths
+            .iter()
+            .any(|picked| picked.shares_pool_with(candidate_path))
+        {
+            continue;
+        }
+        let (plan, left) = size(candidate, &inventory);
+        if plan.expected_gain < config.min_gain() {
+            continue;
+        }
+        paths.push(candidate_path);
+        inventory = left;
+        plans.push(plan);
+    }
+    plans
+}
+
+/// The strategy selected by the `strategy` scenario argument, sizing trades of
+/// `market` up to its per-trade limit.
+pub fn by_name<'a>(
+    name: &str,
+    config: &'a BotConfig,
+    market: &MarketConfig,
+) -> Result<Box<dyn Strategy + 'a>, Error> {
+    let strategy = &config.strategy;
+    let max_notional = config.market_limits(market).max_trade_notional;
+    match name {
+        "deviation" => Ok(Box::new(DeviationStrategy::new(strategy, max_notional))),
+        "mint-and-sell" => Ok(Box::new(MintAndSellStrategy::new(strategy, max_notional))),
+        "buy-and-redeem" => Ok(Box::new(BuyAndRedeemStrategy::new(strategy, max_notional))),
+        _ => Err(Error::OdraError {
+            message: format!(
+                "Unknown strategy: {} (expected deviation, mint-and-sell or buy-and-redeem)",
+                name
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bot::{data::PoolReserves, graph::Pool};
+
+    use super::*;
+
+    const ONE: u64 = 1_000_000_000;
+    const MAX_NOTIONAL: u64 = 10_000 * ONE;
+
+    fn pools(long: u64, wcspr: u64) -> RouteGraph {
+        RouteGraph::from(&PoolReserves {
+            long: U256::from(long) * ONE,
+            wcspr_long: U256::from(wcspr) * ONE,
+            wcspr_short: U256::from(1_000_000u64) * ONE,
+            short: U256::from(1_000_000u64) * ONE,
+        })
+    }
+
+    fn inventory(wcspr: u64, long: u64) -> Inventory {
+        Inventory {
+            wcspr: U256::from(wcspr),
+            long: U256::from(long),
+            short: U256::zero(),
+            top_up: U256::from(2_000 * ONE),
+        }
+    }
+
+    fn both_pools(long_wcspr: u64, wcspr_short: u64) -> RouteGraph {
+        RouteGraph::from(&PoolReserves {
+            long: U256::from(1_000_000u64) * ONE,
+            wcspr_long: U256::from(long_wcspr) * ONE,
+            wcspr_short: U256::from(wcspr_short) * ONE,
+            short: U256::from(1_000_000u64) * ONE,
+        })
+    }
+
+    /// The actions of the only plan proposed.
+    fn single_plan(plans: Vec<Plan>) -> Vec<Action> {
+        let [plan] = plans.as_slice() else {
+            panic!("expected a single plan: {:?}", plans);
+        };
+        plan.actions.clone()
+    }
+
+    fn swap_path(plan: &Plan) -> Path {
+        match plan.actions[..] {
+            [Action::Swap { path, .. }] => path,
+            _ => panic!("expected a single swap: {:?}", plan),
+        }
+    }
+
+    #[test]
+    fn test_available_amount_in_includes_a_top_up() {
+        let price_data = PriceData::from_f64(0.5, 0.5, 0.04, 0.5, 0.5);
+        // A 2_000 CSPR top-up at 0.5 CSPR per long mints 4_000 longs
+        assert_eq!(
+            inventory(0, 1_000 * ONE).available_amount_in(Path::LongWcspr, &price_data),
+            U256::from(5_000 * ONE)
+        );
+        assert_eq!(
+            inventory(100, 0).available_amount_in(Path::WcsprShort, &price_data),
+            U256::from(2_000 * ONE + 100)
+        );
+        assert!(inventory(100, 100)
+            .available_amount_in(Path::Empty, &price_data)
+            .is_zero());
+    }
+
+    #[test]
+    fn test_deviation_strategy_swaps_along_the_mispriced_path() {
+        let config = StrategyConfig::default();
+        let strategy = DeviationStrategy::new(&config, MAX_NOTIONAL);
+        // LONG trades at 1.1 CSPR in the pool, 1.0 at fair value
+        let price_data = PriceData::from_f64(1.1, 1.0, 0.04, 1.0, 1.0);
+        let actions = single_plan(strategy.propose(
+            &price_data,
+            &pools(1_000_000, 1_100_000),
+            &inventory(0, 10_000 * ONE),
+        ));
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Swap { path: Path::LongWcspr, amount_in }] if !amount_in.is_zero()
+        ));
+
+        let fair = PriceData::from_f64(1.0, 1.0, 0.04, 1.0, 1.0);
+        assert!(strategy
+            .propose(&fair, &pools(1_000_000, 1_000_000), &inventory(0, 0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_deviation_strategy_sells_both_overvalued_tokens() {
+        let config = StrategyConfig::default();
+        let strategy = DeviationStrategy::new(&config, MAX_NOTIONAL);
+        // LONG and SHORT both trade at 1.1 CSPR in their pools, 1.0 at fair value
+        let price_data = PriceData::from_f64(1.1, 1.1, 0.04, 1.0, 1.0);
+        let inventory = Inventory {
+            long: U256::from(10_000 * ONE),
+            short: U256::from(10_000 * ONE),
+            ..Inventory::default()
+        };
+        let plans = strategy.propose(&price_data, &both_pools(1_100_000, 1_100_000), &inventory);
+        let paths = plans.iter().map(swap_path).collect::<Vec<_>>();
+        assert_eq!(paths, vec![Path::LongWcspr, Path::ShortWcspr]);
+        assert!(plans
+            .iter()
+            .all(|plan| plan.expected_gain >= config.min_gain()));
+    }
+
+    #[test]
+    fn test_deviation_strategy_fits_purchases_into_the_inventory() {
+        let config = StrategyConfig::default();
+        let strategy = DeviationStrategy::new(&config, MAX_NOTIONAL);
+        // LONG and SHORT both trade at 0.9 CSPR, but the wCSPR only funds one purchase
+        let price_data = PriceData::from_f64(0.9, 0.9, 0.04, 1.0, 1.0);
+        let inventory = Inventory {
+            wcspr: U256::from(3_000 * ONE),
+            ..Inventory::default()
+        };
+        let actions =
+            single_plan(strategy.propose(&price_data, &both_pools(900_000, 900_000), &inventory));
+        // All of it, up to mote-level rounding of the search
+        assert!(matches!(
+            actions.as_slice(),
+            [Action::Swap { path: Path::WcsprLong | Path::WcsprShort, amount_in }]
+                if inventory.wcspr - *amount_in < U256::from(1_000)
+        ));
+    }
+
+    #[test]
+    fn test_deviation_strategy_never_trades_a_pool_twice() {
+        let config = StrategyConfig::default();
+        let strategy = DeviationStrategy::new(&config, MAX_NOTIONAL);
+        // LONG overvalued and SHORT undervalued: the multi-hop path competes with both single hops
+        let price_data = PriceData::from_f64(1.1, 0.9, 0.04, 1.0, 1.0);
+        let inventory = Inventory {
+            wcspr: U256::from(100_000 * ONE),
+            long: U256::from(100_000 * ONE),
+            ..Inventory::default()
+        };
+        let plans = strategy.propose(&price_data, &both_pools(1_100_000, 900_000), &inventory);
+        let paths = plans.iter().map(swap_path).collect::<Vec<_>>();
+        assert!(!paths.is_empty());
+        for (index, path) in paths.iter().enumerate() {
+            assert!(paths[index + 1..]
+                .iter()
+                .all(|other| !path.shares_pool_with(*other)));
+        }
+    }
+
+    #[test]
+    fn test_deviation_strategy_trades_through_a_new_pool() {
+        let config = StrategyConfig::default();
+        let strategy = DeviationStrategy::new(&config, MAX_NOTIONAL);
+        // Both tokens trade at fair value against wCSPR, but a LONG/SHORT pool
+        // pays 1.1 SHORT per LONG
+        let price_data = PriceData::from_f64(1.0, 1.0, 0.04, 1.0, 1.0);
+        let mut graph = both_pools(1_000_000, 1_000_000);
+        graph.add(Pool {
+            tokens: (Token::Long, Token::Short),
+            reserves: (
+                U256::from(1_000_000u64) * ONE,
+                U256::from(1_100_000u64) * ONE,
+            ),
+        });
+        let inventory = Inventory {
+            long: U256::from(10_000 * ONE),
+            ..Inventory::default()
+        };
+        let plans = strategy.propose(&price_data, &graph, &inventory);
+        let paths = plans.iter().map(swap_path).collect::<Vec<_>>();
+        assert_eq!(paths, vec!["LongShort".parse().unwrap()]);
+    }
+
+    #[test]
+    fn test_mint_and_sell_mints_what_it_sells() {
+        let config = StrategyConfig::default();
+        let strategy = MintAndSellStrategy::new(&config, MAX_NOTIONAL);
+        // LONG trades at 1.1 CSPR in the pool and mints at 1.0
+        let price_data = PriceData::from_f64(1.1, 1.0, 0.04, 1.0, 1.0);
+        let actions = single_plan(strategy.propose(
+            &price_data,
+            &pools(1_000_000, 1_100_000),
+            &inventory(100_000 * ONE, 0),
+        ));
+        let [Action::Mint {
+            position: Position::Long,
+            amount,
+        }, Action::Swap {
+            path: Path::LongWcspr,
+            amount_in,
+        }] = actions.as_slice()
+        else {
+            panic!("unexpected actions: {:?}", actions);
+        };
+        assert!(!amount.is_zero());
+        assert_eq!(
+            *amount_in,
+            sizing::minted_amount(*amount, &price_data.long_fair_price, &config)
+        );
+
+        // Underpriced positions are left to other strategies
+        let cheap = PriceData::from_f64(0.9, 1.0, 0.04, 1.0, 1.0);
+        assert!(strategy
+            .propose(
+                &cheap,
+                &pools(1_000_000, 900_000),
+                &inventory(100_000 * ONE, 0)
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_mint_and_sell_skips_a_gap_the_market_fee_eats() {
+        let config = StrategyConfig {
+            market_fee_bps: 1_000,
+            ..StrategyConfig::default()
+        };
+        let strategy = MintAndSellStrategy::new(&config, MAX_NOTIONAL);
+        let price_data = PriceData::from_f64(1.05, 1.0, 0.04, 1.0, 1.0);
+        assert!(strategy
+            .propose(
+                &price_data,
+                &pools(1_000_000, 1_050_000),
+                &inventory(100_000 * ONE, 0)
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_buy_and_redeem_redeems_what_it_buys() {
+        let config = StrategyConfig::default();
+        let strategy = BuyAndRedeemStrategy::new(&config, MAX_NOTIONAL);
+        // LONG trades at 0.9 CSPR in the pool and redeems at 1.0
+        let price_data = PriceData::from_f64(0.9, 1.0, 0.04, 1.0, 1.0);
+        let graph = pools(1_000_000, 900_000);
+        let actions =
+            single_plan(strategy.propose(&price_data, &graph, &inventory(100_000 * ONE, 0)));
+        let [Action::Swap {
+            path: Path::WcsprLong,
+            amount_in,
+        }, Action::Redeem {
+            position: Position::Long,
+            amount,
+        }] = actions.as_slice()
+        else {
+            panic!("unexpected actions: {:?}", actions);
+        };
+        assert_eq!(
+            *amount,
+            sizing::quote(Path::WcsprLong, *amount_in, &graph, config.pair_fee_bps)
+        );
+
+        // Overpriced positions are left to other strategies
+        let dear = PriceData::from_f64(1.1, 1.0, 0.04, 1.0, 1.0);
+        assert!(strategy
+            .propose(
+                &dear,
+                &pools(1_000_000, 1_100_000),
+                &inventory(100_000 * ONE, 0)
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_plan_market_call() {
+        let swap = Action::Swap {
+            path: Path::LongWcspr,
+            amount_in: U256::from(ONE),
+        };
+        let mint = Action::Mint {
+            position: Position::Long,
+            amount: U256::from(ONE),
+        };
+        let plan = |actions| Plan {
+            actions,
+            expected_gain: Gain::zero(),
+        };
+        let redeem = Action::Redeem {
+            position: Position::Long,
+            amount: U256::from(ONE),
+        };
+        assert_eq!(plan(vec![mint, swap]).market_call(), Some(mint));
+        assert_eq!(plan(vec![swap, redeem]).market_call(), Some(redeem));
+        assert_eq!(plan(vec![swap]).market_call(), None);
+    }
+
+    #[test]
+    fn test_by_name() {
+        let config = BotConfig::default();
+        let market = MarketConfig::default();
+        assert_eq!(
+            by_name(DEFAULT_STRATEGY, &config, &market).unwrap().name(),
+            "deviation"
+        );
+        assert_eq!(
+            by_name("mint-and-sell", &config, &market).unwrap().name(),
+            "mint-and-sell"
+        );
+        assert_eq!(
+            by_name("buy-and-redeem", &config, &market).unwrap().name(),
+            "buy-and-redeem"
+        );
+        assert!(by_name("martingale", &config, &market).is_err());
+    }
+}
@@ -1,83 +1,212 @@
-use odra::{casper_types::U256, prelude::Addressable};
+use std::cell::OnceCell;
+
+use casper_trade_contracts::pair::PairHostRef;
+use odra::{casper_types::U256, host::HostRef, prelude::Addressable};
 use odra_cli::scenario::Error;
 
 use crate::{
-    bot::{data::PriceData, path::Path},
+    bot::{
+        config::StrategyConfig,
+        data::{MarketReading, PoolReserves, PriceData},
+        error::{BotError, CallResult},
+        graph::{Pool, RouteGraph},
+        oracle::OracleReading,
+        path::{Path, Token},
+        pool_guard::PoolReading,
+        price::{Gain, Price},
+        retry::Retry,
+    },
     contracts::ContractRefs,
 };
 
+/// The market reports the CSPR price in USD with 5 decimal places.
+const WCSPR_PRICE_PRECISION: u64 = 100_000;
+
+/// A pair with its tokens in reserve order.
+type TokenPair = ((Token, Token), PairHostRef);
+
 pub(super) struct PriceCalculator<'a> {
     contracts: &'a ContractRefs<'a>,
+    retry: Option<&'a Retry>,
+    pairs: OnceCell<Vec<TokenPair>>,
 }
 
 impl<'a> PriceCalculator<'a> {
     pub(super) fn new(contracts: &'a ContractRefs<'a>) -> Self {
-        Self { contracts }
+        Self {
+            contracts,
+            retry: None,
+            pairs: OnceCell::new(),
+        }
+    }
+
+    /// Retries node reads that fail in transit.
+    pub(super) fn with_retry(self, retry: &'a Retry) -> Self {
+        Self {
+            retry: Some(retry),
+            ..self
+        }
+    }
+
+    fn read<T>(
+        &self,
+        call: &'static str,
+        mut f: impl FnMut() -> Result<T, BotError>,
+    ) -> Result<T, BotError> {
+        match self.retry {
+            Some(retry) => retry.read(call, f),
+            None => f(),
+        }
+    }
+
+    pub(super) fn pool_reserves(&self) -> Result<PoolReserves, BotError> {
+        Self::market_reserves(&self.pool_reading()?.graph())
     }
 
-    pub(super) fn casper_trade_prices(&self) -> Result<(f64, f64), Error> {
-        let (reserves_long, reserves_wcspr_long, _) =
-            self.contracts.long_wcspr_pair()?.get_reserves();
-        let (reserves_wcspr_short, reserves_short, _) =
-            self.contracts.wcspr_short_pair()?.get_reserves();
+    /// The reserves of every pair along with when each was last updated.
+    pub(super) fn pool_reading(&self) -> Result<PoolReading, BotError> {
+        let mut pools = vec![];
+        for (tokens, pair) in self.pairs()? {
+            let (reserve0, reserve1, updated_at) = self.read("get_reserves", || {
+                pair.try_get_reserves().during("get_reserves")
+            })?;
+            let pool = Pool {
+                tokens: *tokens,
+                reserves: (reserve0, reserve1),
+            };
+            pools.push((pool, u64::from(updated_at)));
+        }
 
-        let long_token_price = Self::calculate_price(reserves_wcspr_long, reserves_long);
-        let short_token_price = Self::calculate_price(reserves_wcspr_short, reserves_short);
+        Ok(PoolReading {
+            pools,
+            read_at: self.contracts.market()?.env().block_time(),
+        })
+    }
+
+    /// The reserves of the market's LONG/wCSPR and wCSPR/SHORT pools in `graph`.
+    pub(super) fn market_reserves(graph: &RouteGraph) -> Result<PoolReserves, BotError> {
+        graph.market_reserves().ok_or_else(|| {
+            BotError::Setup(Error::OdraError {
+                message: "No LONG/wCSPR and wCSPR/SHORT pairs in the contracts container"
+                    .to_string(),
+            })
+        })
+    }
 
-        Ok((long_token_price, short_token_price))
+    /// Every pair in the contracts container, with its tokens identified by
+    /// their addresses. Read on first use.
+    fn pairs(&self) -> Result<&[TokenPair], BotError> {
+        if let Some(pairs) = self.pairs.get() {
+            return Ok(pairs);
+        }
+        let pairs = self
+            .contracts
+            .pairs()?
+            .into_iter()
+            .map(|(_, pair)| {
+                let token0 = self.read("token0", || pair.try_token0().during("token0"))?;
+                let token1 = self.read("token1", || pair.try_token1().during("token1"))?;
+                let tokens = (
+                    Token::at(token0, self.contracts)?,
+                    Token::at(token1, self.contracts)?,
+                );
+                Ok((tokens, pair))
+            })
+            .collect::<Result<Vec<_>, BotError>>()?;
+        Ok(self.pairs.get_or_init(|| pairs))
     }
 
-    pub(super) fn fair_prices(&self) -> Result<(f64, f64, f64), Error> {
+    pub(super) fn casper_trade_prices(reserves: &PoolReserves) -> (Price, Price) {
+        let long_token_price = Price::new(reserves.wcspr_long, reserves.long);
+        let short_token_price = Price::new(reserves.wcspr_short, reserves.short);
+
+        (long_token_price, short_token_price)
+    }
+
+    pub(super) fn market_reading(&self) -> Result<MarketReading, BotError> {
         let market = self.contracts.market()?;
-        let state = market
-            .get_address_market_state(market.address())
+        let state = self
+            .read("get_address_market_state", || {
+                market
+                    .try_get_address_market_state(market.address())
+                    .during("get_address_market_state")
+            })?
             .market_state;
-        let long_token_price = Self::calculate_price(state.long_liquidity, state.long_total_supply);
-        let short_token_price =
-            Self::calculate_price(state.short_liquidity, state.short_total_supply);
-        let wcspr_price = state.price().as_u64() as f64 / 100_000.0f64;
+        Ok(MarketReading {
+            long_liquidity: state.long_liquidity,
+            long_total_supply: state.long_total_supply,
+            short_liquidity: state.short_liquidity,
+            short_total_supply: state.short_total_supply,
+            price: state.price(),
+        })
+    }
+
+    /// CSPR/USD TWAP of the Styks price feed `feed_id`, if it has one.
+    pub(super) fn oracle_price(&self, feed_id: &str) -> Result<Option<U256>, BotError> {
+        let price_feed = self.contracts.price_feed()?;
+        let price = self.read("get_twap_price", || {
+            price_feed
+                .try_get_twap_price(feed_id.to_string())
+                .during("get_twap_price")
+        })?;
+        Ok(price.map(U256::from))
+    }
+
+    /// CSPR/USD TWAP of the Styks price feed `feed_id` and when it was last updated.
+    pub(super) fn oracle_reading(&self, feed_id: &str) -> Result<OracleReading, BotError> {
+        let price = self.oracle_price(feed_id)?;
+        let price_feed = self.contracts.price_feed()?;
+        let updated_at = self.read("get_last_heartbeat", || {
+            price_feed
+                .try_get_last_heartbeat()
+                .during("get_last_heartbeat")
+        })?;
+        Ok(OracleReading {
+            price: price.map(|price| Price::new(price, U256::from(WCSPR_PRICE_PRECISION))),
+            updated_at,
+            read_at: price_feed.env().block_time() / 1000,
+        })
+    }
+
+    pub(super) fn fair_prices(&self) -> Result<(Price, Price, Price), BotError> {
+        Ok(Self::fair_prices_of(&self.market_reading()?))
+    }
+
+    pub(super) fn fair_prices_of(reading: &MarketReading) -> (Price, Price, Price) {
+        let long_token_price = Price::new(reading.long_liquidity, reading.long_total_supply);
+        let short_token_price = Price::new(reading.short_liquidity, reading.short_total_supply);
+        let wcspr_price = Price::new(reading.price, U256::from(WCSPR_PRICE_PRECISION));
 
-        Ok((long_token_price, short_token_price, wcspr_price))
+        (long_token_price, short_token_price, wcspr_price)
     }
 
-    fn calculate_price(amount0: U256, amount1: U256) -> f64 {
-        (amount0 * U256::from(1_000_000) / amount1).as_u64() as f64 / 1000_000.0f64
+    /// DEX and fair prices for a set of readings.
+    pub(super) fn price_data(reserves: &PoolReserves, reading: &MarketReading) -> PriceData {
+        let (long_price, short_price) = Self::casper_trade_prices(reserves);
+        let (long_fair_price, short_fair_price, wcspr_price) = Self::fair_prices_of(reading);
+        PriceData::new(
+            long_price,
+            short_price,
+            wcspr_price,
+            long_fair_price,
+            short_fair_price,
+        )
     }
 
+    /// Net gain of a trade in motes: the fair value of what we receive minus
+    /// the fair value of what we pay and the average transaction cost.
     pub(super) fn calc_gains_in_cspr(
         amount_in: U256,
         amount_out: U256,
         price_data: &PriceData,
         path: Path,
-    ) -> f64 {
-        let average_transaction_cost = if path.is_multi_hop() { 12.5f64 } else { 7.0f64 };
-        let (amount_in_cspr, amount_out_cspr) = match path {
-            Path::LongWcsprShort => (
-                amount_in.as_u64() as f64 * price_data.long_fair_price,
-                amount_out.as_u64() as f64 * price_data.short_fair_price,
-            ),
-            Path::ShortWcsprLong => (
-                amount_in.as_u64() as f64 * price_data.short_fair_price,
-                amount_out.as_u64() as f64 * price_data.long_fair_price,
-            ),
-            Path::LongWcspr => (
-                amount_in.as_u64() as f64 * price_data.long_fair_price,
-                amount_out.as_u64() as f64,
-            ),
-            Path::ShortWcspr => (
-                amount_in.as_u64() as f64 * price_data.short_fair_price,
-                amount_out.as_u64() as f64,
-            ),
-            Path::WcsprLong => (
-                amount_in.as_u64() as f64,
-                amount_out.as_u64() as f64 * price_data.long_fair_price,
-            ),
-            Path::WcsprShort => (
-                amount_in.as_u64() as f64,
-                amount_out.as_u64() as f64 * price_data.short_fair_price,
-            ),
-            Path::Empty => return 0.0f64,
+        config: &StrategyConfig,
+    ) -> Gain {
+        let (Some(token_in), Some(token_out)) = (path.token_in(), path.token_out()) else {
+            return Gain::zero();
         };
-        (amount_out_cspr - amount_in_cspr) / 1_000_000_000.0f64 - average_transaction_cost
+        let value_in = price_data.fair_value(token_in, amount_in);
+        let value_out = price_data.fair_value(token_out, amount_out);
+        Gain::between(value_out, value_in.saturating_add(config.tx_cost(path)))
     }
 }
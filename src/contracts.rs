@@ -9,52 +9,157 @@ use casper_trade_contracts::{
 };
 use odra::host::HostEnv;
 use odra_cli::{scenario::Error, ContractProvider, DeployedContractsContainer};
+use styks_contracts::styks_price_feed::{StyksPriceFeed, StyksPriceFeedHostRef};
+
+use crate::{CD_LONG_ID, CD_SHORT_ID, MARKET_ID};
+
+/// The contract name of casper-trade pairs in the contracts container.
+const PAIR_CONTRACT_NAME: &str = "Pair";
 
 pub struct ContractRefs<'a> {
     env: &'a HostEnv,
-    container: &'a DeployedContractsContainer,
+    source: ContractSource<'a>,
+    names: MarketNames,
+}
+
+/// Names of a Casper Delta market and its position tokens in the contracts container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketNames {
+    pub market: String,
+    pub long: String,
+    pub short: String,
+}
+
+impl Default for MarketNames {
+    fn default() -> Self {
+        Self {
+            market: MARKET_ID.to_string(),
+            long: CD_LONG_ID.to_string(),
+            short: CD_SHORT_ID.to_string(),
+        }
+    }
+}
+
+enum ContractSource<'a> {
+    Container(&'a DeployedContractsContainer),
+    #[cfg(test)]
+    Deployed(DeployedContracts),
+}
+
+/// Contracts deployed in-process, e.g. on the Odra MockVM.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct DeployedContracts {
+    pub router: RouterHostRef,
+    /// Pairs by their package name in the contracts container, e.g. `LP_LONG_WCSPR_ID`.
+    pub pairs: Vec<(String, PairHostRef)>,
+    pub market: MarketHostRef,
+    pub price_feed: StyksPriceFeedHostRef,
+    pub wcspr: WrappedNativeTokenHostRef,
+    pub long: PositionTokenHostRef,
+    pub short: PositionTokenHostRef,
 }
 
 impl<'a> ContractRefs<'a> {
     pub fn new(env: &'a HostEnv, container: &'a DeployedContractsContainer) -> Self {
-        Self { env, container }
+        Self {
+            env,
+            source: ContractSource::Container(container),
+            names: MarketNames::default(),
+        }
     }
 
-    pub fn router(&self) -> Result<RouterHostRef, Error> {
-        Ok(self.container.contract_ref::<Router>(self.env)?)
+    /// Resolves the market and position tokens named by `names` instead of the defaults.
+    pub fn for_market(self, names: MarketNames) -> Self {
+        Self { names, ..self }
     }
 
-    pub fn long_wcspr_pair(&self) -> Result<PairHostRef, Error> {
-        Ok(self
-            .container
-            .contract_ref_named::<Pair>(self.env, Some("CD_LONG-WCSPR LP".to_string()))?)
+    pub fn names(&self) -> &MarketNames {
+        &self.names
     }
 
-    pub fn wcspr_short_pair(&self) -> Result<PairHostRef, Error> {
-        Ok(self
-            .container
-            .contract_ref_named::<Pair>(self.env, Some("WCSPR-CD_SHORT LP".to_string()))?)
+    #[cfg(test)]
+    pub fn deployed(env: &'a HostEnv, contracts: DeployedContracts) -> Self {
+        Self {
+            env,
+            source: ContractSource::Deployed(contracts),
+            names: MarketNames::default(),
+        }
+    }
+
+    pub fn router(&self) -> Result<RouterHostRef, Error> {
+        match &self.source {
+            ContractSource::Container(container) => Ok(container.contract_ref::<Router>(self.env)?),
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.router.clone()),
+        }
+    }
+
+    /// Every pair in the contracts container, by its package name, e.g.
+    /// `CD_LONG-WCSPR LP`, whichever tokens it trades.
+    pub fn pairs(&self) -> Result<Vec<(String, PairHostRef)>, Error> {
+        match &self.source {
+            ContractSource::Container(container) => container
+                .all_contracts()
+                .into_iter()
+                .filter(|contract| contract.name == PAIR_CONTRACT_NAME)
+                .map(|contract| {
+                    let pair = container.contract_ref_named::<Pair>(
+                        self.env,
+                        Some(contract.package_name.clone()),
+                    )?;
+                    Ok((contract.package_name, pair))
+                })
+                .collect(),
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.pairs.clone()),
+        }
     }
 
     pub fn market(&self) -> Result<MarketHostRef, Error> {
-        Ok(self.container.contract_ref::<Market>(self.env)?)
+        match &self.source {
+            ContractSource::Container(container) => Ok(container
+                .contract_ref_named::<Market>(self.env, Some(self.names.market.clone()))?),
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.market.clone()),
+        }
+    }
+
+    pub fn price_feed(&self) -> Result<StyksPriceFeedHostRef, Error> {
+        match &self.source {
+            ContractSource::Container(container) => {
+                Ok(container.contract_ref::<StyksPriceFeed>(self.env)?)
+            }
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.price_feed.clone()),
+        }
     }
 
     pub fn wcspr(&self) -> Result<WrappedNativeTokenHostRef, Error> {
-        Ok(self
-            .container
-            .contract_ref::<WrappedNativeToken>(self.env)?)
+        match &self.source {
+            ContractSource::Container(container) => {
+                Ok(container.contract_ref::<WrappedNativeToken>(self.env)?)
+            }
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.wcspr.clone()),
+        }
     }
 
     pub fn long(&self) -> Result<PositionTokenHostRef, Error> {
-        Ok(self
-            .container
-            .contract_ref_named::<PositionToken>(self.env, Some("CD_LONG".to_string()))?)
+        match &self.source {
+            ContractSource::Container(container) => Ok(container
+                .contract_ref_named::<PositionToken>(self.env, Some(self.names.long.clone()))?),
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.long.clone()),
+        }
     }
 
     pub fn short(&self) -> Result<PositionTokenHostRef, Error> {
-        Ok(self
-            .container
-            .contract_ref_named::<PositionToken>(self.env, Some("CD_SHORT".to_string()))?)
+        match &self.source {
+            ContractSource::Container(container) => Ok(container
+                .contract_ref_named::<PositionToken>(self.env, Some(self.names.short.clone()))?),
+            #[cfg(test)]
+            ContractSource::Deployed(contracts) => Ok(contracts.short.clone()),
+        }
     }
 }
@@ -1,3 +1,4 @@
+pub const MARKET_ID: &str = "Market";
 pub const CD_LONG_ID: &str = "CD_LONG";
 pub const CD_SHORT_ID: &str = "CD_SHORT";
 pub const LP_LONG_WCSPR_ID: &str = "CD_LONG-WCSPR LP";
@@ -6,5 +7,5 @@ mod bot;
 mod contracts;
 mod unwrap_wcspr;
 
-pub use bot::Bot;
+pub use bot::{Backtest, Bot, ContractNames, PnlReport, Record};
 pub use unwrap_wcspr::UnwrapWcspr;